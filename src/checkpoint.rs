@@ -0,0 +1,372 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::{Shard, SlateConfig};
+use crate::lineup::{Lineup, LineupBuilder};
+use crate::player::LitePlayer;
+use crate::scorer::{rank_lineups, ScorerKind};
+use crate::scoring::{scoring_model, ScoringModel};
+use crate::site::Site;
+use crate::weather::WeatherRules;
+use crate::{clear_slate_caches, prime_proj_caches, Day};
+
+/// First line of a checkpoint, a file is only resumed for the same slate
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CheckpointHeader {
+    pub site: Site,
+    pub season: i16,
+    pub week: i8,
+    pub day: Day,
+    pub salary_cap: i32,
+    pub min_sal: i32,
+    pub combo_top_k: usize,
+    /// Every player in the slate, covers pool sizes and excluded players
+    pub player_ids: Vec<i16>,
+    /// Resolved `--lock` ids, locking a player already in the pool changes
+    /// the lineups without changing the slate
+    #[serde(default)]
+    pub locked: Vec<i16>,
+    #[serde(default)]
+    pub excluded: Vec<i16>,
+    pub shard: Option<Shard>,
+    #[serde(default)]
+    pub scorer: ScorerKind,
+    /// `scoring_hash` of the model and weather rules the run scored with
+    #[serde(default)]
+    pub scoring: u64,
+}
+
+impl CheckpointHeader {
+    pub fn new(
+        config: &SlateConfig,
+        players: &[LitePlayer],
+        locked: &[i16],
+        excluded: &[i16],
+    ) -> CheckpointHeader {
+        let mut player_ids: Vec<i16> = players.iter().map(|p| p.id).collect();
+        player_ids.sort();
+        let mut locked: Vec<i16> = locked.to_vec();
+        locked.sort();
+        let mut excluded: Vec<i16> = excluded.to_vec();
+        excluded.sort();
+        CheckpointHeader {
+            site: config.site,
+            season: config.season,
+            week: config.week,
+            day: config.day,
+            salary_cap: config.salary_cap(),
+            min_sal: config.min_sal(),
+            combo_top_k: config.combo_top_k,
+            player_ids,
+            locked,
+            excluded,
+            shard: config.shard,
+            scorer: config.scorer,
+            scoring: scoring_hash(&scoring_model(), &config.weather),
+        }
+    }
+}
+
+/// FNV-1a of the model and weather rules as JSON, the same on every machine
+/// and build so shard files from different runs can be compared
+pub fn scoring_hash(model: &ScoringModel, weather: &WeatherRules) -> u64 {
+    let json: String = serde_json::to_string(&(model, weather)).unwrap();
+    json.bytes().fold(0xcbf29ce484222325, |hash: u64, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Lineups found for one WR combination, `wrs` are its sorted player ids
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComboResult {
+    pub wrs: [i16; 3],
+    pub lineups: Vec<LineupBuilder>,
+}
+
+/// Collects finished WR combinations, appending each one to the checkpoint
+/// file when there is one. One JSON object per line, header first.
+#[derive(Default)]
+pub struct Checkpoint {
+    file: Option<File>,
+    results: BTreeMap<[i16; 3], Vec<LineupBuilder>>,
+}
+
+impl Checkpoint {
+    /// Picks up finished combinations from an existing file or starts a new one
+    pub fn open(path: &str, header: &CheckpointHeader) -> Result<Checkpoint, Box<dyn Error>> {
+        let mut results: BTreeMap<[i16; 3], Vec<LineupBuilder>> = BTreeMap::new();
+        if Path::new(path).exists() {
+            let (file_header, saved) = read_results(path)?;
+            if &file_header != header {
+                return Err(format!("Checkpoint {} is for a different slate", path).into());
+            }
+            for result in saved {
+                results.insert(result.wrs, result.lineups);
+            }
+            println!("Resuming {} finished combos from {}", results.len(), path);
+        }
+        if let Some(dir) = Path::new(path).parent() {
+            fs::create_dir_all(dir)?;
+        }
+        // Rewrite so a line cut off by a crash doesn't get appended to
+        let mut file: File = File::create(path)?;
+        writeln!(file, "{}", serde_json::to_string(header)?)?;
+        for (wrs, lineups) in &results {
+            let result: ComboResult = ComboResult {
+                wrs: *wrs,
+                lineups: lineups.clone(),
+            };
+            writeln!(file, "{}", serde_json::to_string(&result)?)?;
+        }
+        file.flush()?;
+        let file: File = OpenOptions::new().append(true).open(path)?;
+        Ok(Checkpoint {
+            file: Some(file),
+            results,
+        })
+    }
+
+    pub fn is_done(&self, wrs: &[i16; 3]) -> bool {
+        self.results.contains_key(wrs)
+    }
+
+    pub fn record(&mut self, result: ComboResult) -> Result<(), Box<dyn Error>> {
+        if let Some(file) = self.file.as_mut() {
+            writeln!(file, "{}", serde_json::to_string(&result)?)?;
+            file.flush()?;
+        }
+        self.results.insert(result.wrs, result.lineups);
+        Ok(())
+    }
+
+    pub fn into_lineups(self) -> Vec<LineupBuilder> {
+        self.results.into_values().flatten().collect()
+    }
+}
+
+/// Reads a checkpoint, skipping a last line that was only partly written
+pub fn read_results(path: &str) -> Result<(CheckpointHeader, Vec<ComboResult>), Box<dyn Error>> {
+    let reader: BufReader<File> = BufReader::new(File::open(path)?);
+    let lines: Vec<String> = reader.lines().collect::<Result<_, _>>()?;
+    let header_line: &String = lines
+        .first()
+        .ok_or(format!("Checkpoint {} is empty", path))?;
+    let header: CheckpointHeader = serde_json::from_str(header_line)?;
+    let mut results: Vec<ComboResult> = Vec::new();
+    for (i, line) in lines.iter().enumerate().skip(1) {
+        match serde_json::from_str::<ComboResult>(line) {
+            Ok(result) => results.push(result),
+            Err(_) if i == lines.len() - 1 => println!("Skipping partial line in {}", path),
+            Err(e) => return Err(format!("Bad line {} in {}: {}", i + 1, path, e).into()),
+        }
+    }
+    Ok((header, results))
+}
+
+/// Combines shard result files into one set of lineups. Every file has to be
+/// from the same slate and together they have to cover every shard.
+pub fn merge_results(
+    paths: &[String],
+) -> Result<(CheckpointHeader, Vec<LineupBuilder>), Box<dyn Error>> {
+    let mut merged: Option<CheckpointHeader> = None;
+    let mut shard_count: Option<usize> = None;
+    let mut shards_seen: Vec<usize> = Vec::new();
+    let mut results: BTreeMap<[i16; 3], Vec<LineupBuilder>> = BTreeMap::new();
+    for path in paths {
+        let (header, saved) = read_results(path)?;
+        if let Some(shard) = header.shard {
+            if shard_count.is_some_and(|count| count != shard.count) {
+                return Err(format!("{} is from a run with a different shard count", path).into());
+            }
+            shard_count = Some(shard.count);
+            shards_seen.push(shard.index);
+        }
+        let slate: CheckpointHeader = CheckpointHeader {
+            shard: None,
+            ..header
+        };
+        match &merged {
+            Some(first) if first != &slate => {
+                return Err(format!("{} is for a different slate", path).into())
+            }
+            Some(_) => {}
+            None => merged = Some(slate),
+        }
+        for result in saved {
+            results.insert(result.wrs, result.lineups);
+        }
+    }
+    if let Some(count) = shard_count {
+        if let Some(missing) = (0..count).find(|i| !shards_seen.contains(i)) {
+            return Err(format!("Missing shard {}/{}", missing, count).into());
+        }
+    }
+    let header: CheckpointHeader = merged.ok_or("No result files to merge")?;
+    Ok((header, results.into_values().flatten().collect()))
+}
+
+/// `merge_results` built and ranked on the slate the shards searched. Installs
+/// that slate's config, returned with the lineups, in place of `config`'s.
+pub fn merge_lineups(
+    paths: &[String],
+    config: &SlateConfig,
+) -> Result<(SlateConfig, Vec<Lineup>), Box<dyn Error>> {
+    let (header, builders) = merge_results(paths)?;
+    if header.scoring != scoring_hash(&scoring_model(), &config.weather) {
+        return Err("Shard files were scored with a different --model or weather rules".into());
+    }
+    let config: SlateConfig = SlateConfig {
+        site: header.site,
+        season: header.season,
+        week: header.week,
+        day: header.day,
+        salary_cap: Some(header.salary_cap),
+        min_sal: Some(header.min_sal),
+        combo_top_k: header.combo_top_k,
+        scorer: header.scorer,
+        ..config.clone()
+    };
+    clear_slate_caches();
+    config.install();
+    prime_proj_caches(config.week, config.season, &header.player_ids);
+    let lineups: Vec<Lineup> = builders
+        .into_iter()
+        .map(|l| l.build(config.week, config.season))
+        .collect::<Result<_, _>>()?;
+    let lineups: Vec<Lineup> = rank_lineups(lineups, config.scorer.build().as_ref());
+    Ok((config, lineups))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::{Pos, Team};
+
+    fn test_header() -> CheckpointHeader {
+        CheckpointHeader::new(&SlateConfig::default(), &[LitePlayer::test()], &[], &[])
+    }
+
+    fn test_result(wr: i16) -> ComboResult {
+        let player: LitePlayer = LitePlayer::test();
+        ComboResult {
+            wrs: [wr, wr + 1, wr + 2],
+            lineups: vec![LineupBuilder::new().set_pos(
+                &LitePlayer {
+                    pos: Pos::Qb,
+                    team: Team::Buf,
+                    ..player
+                },
+                crate::lineup::Slot::None,
+            )],
+        }
+    }
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir()
+            .join(name)
+            .to_string_lossy()
+            .to_string()
+    }
+
+    #[test]
+    fn test_merge_shards() {
+        let paths: Vec<String> = (0..2)
+            .map(|i| temp_path(&format!("dfstimizer-shard-test-{}.jsonl", i)))
+            .collect();
+        for (i, path) in paths.iter().enumerate() {
+            let _ = std::fs::remove_file(path);
+            let header: CheckpointHeader = CheckpointHeader {
+                shard: Some(Shard { index: i, count: 2 }),
+                ..test_header()
+            };
+            let mut checkpoint: Checkpoint = Checkpoint::open(path, &header).unwrap();
+            checkpoint.record(test_result(i as i16)).unwrap();
+            checkpoint.record(test_result(i as i16 + 10)).unwrap();
+        }
+        let (header, lineups) = merge_results(&paths).unwrap();
+        assert_eq!(header, test_header());
+        assert_eq!(lineups.len(), 4);
+        assert!(merge_results(&paths[0..1]).is_err());
+        for path in &paths {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_merge_installs_shard_slate() {
+        let paths: Vec<String> = (0..2)
+            .map(|i| temp_path(&format!("dfstimizer-merge-week-test-{}.jsonl", i)))
+            .collect();
+        let cli: SlateConfig = SlateConfig::default();
+        let shard_slate: SlateConfig = SlateConfig {
+            week: cli.week + 5,
+            day: Day::Mon,
+            ..cli.clone()
+        };
+        for (i, path) in paths.iter().enumerate() {
+            let _ = std::fs::remove_file(path);
+            // No players so there is nothing to look up in the database
+            let header: CheckpointHeader = CheckpointHeader {
+                shard: Some(Shard { index: i, count: 2 }),
+                ..CheckpointHeader::new(&shard_slate, &[], &[], &[])
+            };
+            Checkpoint::open(path, &header).unwrap();
+        }
+        let (config, lineups) = merge_lineups(&paths, &cli).unwrap();
+        assert!(lineups.is_empty());
+        assert_eq!((config.week, config.day), (shard_slate.week, Day::Mon));
+        let installed: SlateConfig = crate::config::slate_config();
+        assert_eq!(
+            (installed.week, installed.day),
+            (shard_slate.week, Day::Mon)
+        );
+        for path in &paths {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_resume() {
+        let path: String = temp_path("dfstimizer-checkpoint-test.jsonl");
+        let _ = std::fs::remove_file(&path);
+        let mut checkpoint: Checkpoint = Checkpoint::open(&path, &test_header()).unwrap();
+        checkpoint.record(test_result(3)).unwrap();
+        checkpoint.record(test_result(7)).unwrap();
+        drop(checkpoint);
+        // Crash in the middle of a write
+        let mut file: File = OpenOptions::new().append(true).open(&path).unwrap();
+        write!(file, "{{\"wrs\": [9, 10, 11], \"line").unwrap();
+        drop(file);
+
+        let resumed: Checkpoint = Checkpoint::open(&path, &test_header()).unwrap();
+        assert!(resumed.is_done(&[3, 4, 5]) && resumed.is_done(&[7, 8, 9]));
+        assert!(!resumed.is_done(&[9, 10, 11]));
+        let lineups: Vec<LineupBuilder> = resumed.into_lineups();
+        assert_eq!(lineups.len(), 2);
+        assert_eq!(lineups[0].qb.unwrap().team, Team::Buf);
+
+        let other: CheckpointHeader = CheckpointHeader {
+            week: 9,
+            ..test_header()
+        };
+        assert!(Checkpoint::open(&path, &other).is_err());
+        let locked: CheckpointHeader = CheckpointHeader {
+            locked: vec![LitePlayer::test().id],
+            ..test_header()
+        };
+        assert!(Checkpoint::open(&path, &locked).is_err());
+        let mut model: ScoringModel = ScoringModel::default();
+        model.qb.features[0].weight += 1.0;
+        let rescored: CheckpointHeader = CheckpointHeader {
+            scoring: scoring_hash(&model, &WeatherRules::default()),
+            ..test_header()
+        };
+        assert!(Checkpoint::open(&path, &rescored).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,397 @@
+use std::fs;
+
+use serde::{Deserialize, Serialize};
+
+use crate::player::Pos;
+use crate::scorer::ScorerKind;
+use crate::scoring::scoring_model;
+use crate::site::{IslandFormat, Site};
+use crate::weather::WeatherRules;
+use crate::{Day, SLATE_CONFIG};
+
+/// Which classic optimizer to run
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Solver {
+    /// Branch and bound, finds the top lineups without trying them all
+    Exact,
+    /// Every WR combination on the thread pool
+    Brute,
+}
+
+impl Solver {
+    pub fn try_from_str(input: &str) -> Option<Solver> {
+        match input.to_lowercase().as_str() {
+            "exact" => Some(Solver::Exact),
+            "brute" => Some(Solver::Brute),
+            _ => None,
+        }
+    }
+}
+
+/// Slice `index` of `count` of the WR combinations, for splitting a brute force
+/// run across machines. Written `0/4` through `3/4` on the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Shard {
+    pub index: usize,
+    pub count: usize,
+}
+
+impl Shard {
+    pub fn try_from_str(input: &str) -> Result<Shard, String> {
+        let (index, count) = input
+            .split_once('/')
+            .ok_or(format!("Expected a shard like 0/4: {}", input))?;
+        let shard: Shard = Shard {
+            index: parse_flag("--shard", index)?,
+            count: parse_flag("--shard", count)?,
+        };
+        if shard.index >= shard.count {
+            return Err(format!("Shard index must be under the count: {}", input));
+        }
+        Ok(shard)
+    }
+
+    pub fn owns(&self, combo: usize) -> bool {
+        combo % self.count == self.index
+    }
+}
+
+/// Everything that used to be edited in lib.rs before each slate.
+/// Loaded from a JSON file and/or CLI flags, flags win.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SlateConfig {
+    pub site: Site,
+    pub season: i16,
+    pub week: i8,
+    pub day: Day,
+    /// The site's cap when unset, see `salary_cap()`
+    pub salary_cap: Option<i32>,
+    /// The site's minimum spend when unset, see `min_sal()`
+    pub min_sal: Option<i32>,
+    pub qb_count: i8,
+    pub rb_count: i8,
+    pub wr_count: i8,
+    pub te_count: i8,
+    pub d_count: i8,
+    /// Kickers only play in single game contests, unset is none for classic
+    /// and `IslandFormat::kicker_count` for single game
+    pub k_count: Option<i8>,
+    /// How many lineups end up in the portfolio
+    pub lineup_count: usize,
+    /// Players each lineup must differ by from every other lineup in the portfolio
+    pub min_unique: usize,
+    /// Players in every lineup, a player id or `Name-TEAM`
+    pub lock: Vec<String>,
+    /// Players left out of the slate, a player id or `Name-TEAM`
+    pub exclude: Vec<String>,
+    pub solver: Solver,
+    /// Lineups the exact solver keeps for the portfolio to pick from
+    pub exact_top_n: usize,
+    /// Lineups the brute force keeps for each WR combination
+    pub combo_top_k: usize,
+    /// File the brute force saves finished WR combinations to, resumed if it exists
+    pub checkpoint: Option<String>,
+    pub shard: Option<Shard>,
+    /// Wind, rain and cold adjustments to QB, pass catcher, kicker and DST scores
+    pub weather: WeatherRules,
+    /// Scoring model file, the built in weights when unset
+    pub model: Option<String>,
+    /// How the optimizers score players and lineups
+    pub scorer: ScorerKind,
+}
+
+impl Default for SlateConfig {
+    fn default() -> Self {
+        SlateConfig {
+            site: Site::FanDuel,
+            season: 2023,
+            week: 4,
+            day: Day::Sun,
+            salary_cap: None,
+            min_sal: None,
+            qb_count: 12,
+            rb_count: 12,
+            wr_count: 25,
+            te_count: 12,
+            d_count: 12,
+            k_count: None,
+            lineup_count: 150,
+            min_unique: 1,
+            lock: Vec::new(),
+            exclude: Vec::new(),
+            solver: Solver::Exact,
+            exact_top_n: 1000,
+            combo_top_k: 5,
+            checkpoint: None,
+            shard: None,
+            weather: WeatherRules::default(),
+            model: None,
+            scorer: ScorerKind::Heuristic,
+        }
+    }
+}
+
+impl SlateConfig {
+    pub fn from_file(path: &str) -> Result<SlateConfig, Box<dyn std::error::Error>> {
+        let contents: String = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    /// Reads `--config <file>` first then applies any other flags on top of it.
+    /// Unknown flags are ignored so the caller can parse its own.
+    pub fn from_args(args: &[String]) -> Result<SlateConfig, String> {
+        let mut config: SlateConfig = match flag_value(args, "--config") {
+            Some(path) => SlateConfig::from_file(path)
+                .map_err(|e| format!("Failed to load config {}: {}", path, e))?,
+            None => SlateConfig::default(),
+        };
+        if let Some(site) = flag_value(args, "--site") {
+            config.site = Site::try_from_str(site).ok_or(format!("Not a site: {}", site))?;
+        }
+        if let Some(season) = flag_value(args, "--season") {
+            config.season = parse_flag("--season", season)?;
+        }
+        if let Some(week) = flag_value(args, "--week") {
+            config.week = parse_flag("--week", week)?;
+        }
+        if let Some(day) = flag_value(args, "--day") {
+            config.day = Day::try_from_str(day).ok_or(format!("Not a game day: {}", day))?;
+        }
+        if let Some(cap) = flag_value(args, "--salary-cap") {
+            config.salary_cap = Some(parse_flag("--salary-cap", cap)?);
+        }
+        if let Some(min_sal) = flag_value(args, "--min-sal") {
+            config.min_sal = Some(parse_flag("--min-sal", min_sal)?);
+        }
+        if let Some(count) = flag_value(args, "--qb-count") {
+            config.qb_count = parse_flag("--qb-count", count)?;
+        }
+        if let Some(count) = flag_value(args, "--rb-count") {
+            config.rb_count = parse_flag("--rb-count", count)?;
+        }
+        if let Some(count) = flag_value(args, "--wr-count") {
+            config.wr_count = parse_flag("--wr-count", count)?;
+        }
+        if let Some(count) = flag_value(args, "--te-count") {
+            config.te_count = parse_flag("--te-count", count)?;
+        }
+        if let Some(count) = flag_value(args, "--d-count") {
+            config.d_count = parse_flag("--d-count", count)?;
+        }
+        if let Some(count) = flag_value(args, "--k-count") {
+            config.k_count = Some(parse_flag("--k-count", count)?);
+        }
+        if let Some(count) = flag_value(args, "--lineups") {
+            config.lineup_count = parse_flag("--lineups", count)?;
+        }
+        if let Some(count) = flag_value(args, "--min-unique") {
+            config.min_unique = parse_flag("--min-unique", count)?;
+        }
+        if let Some(players) = flag_value(args, "--lock") {
+            config.lock = split_list(players);
+        }
+        if let Some(players) = flag_value(args, "--exclude") {
+            config.exclude = split_list(players);
+        }
+        if let Some(solver) = flag_value(args, "--solver") {
+            config.solver =
+                Solver::try_from_str(solver).ok_or(format!("Not a solver: {}", solver))?;
+        }
+        if let Some(count) = flag_value(args, "--exact-top-n") {
+            config.exact_top_n = parse_flag("--exact-top-n", count)?;
+        }
+        if let Some(count) = flag_value(args, "--combo-top-k") {
+            config.combo_top_k = parse_flag("--combo-top-k", count)?;
+        }
+        if let Some(path) = flag_value(args, "--checkpoint") {
+            config.checkpoint = Some(path.to_string());
+        }
+        if let Some(shard) = flag_value(args, "--shard") {
+            config.shard = Some(Shard::try_from_str(shard)?);
+        }
+        if let Some(path) = flag_value(args, "--model") {
+            config.model = Some(path.to_string());
+        }
+        if let Some(scorer) = flag_value(args, "--scorer") {
+            config.scorer =
+                ScorerKind::try_from_str(scorer).ok_or(format!("Not a scorer: {}", scorer))?;
+        }
+        Ok(config)
+    }
+
+    /// `--salary-cap`, or the site's cap when it wasn't set in the file or flags
+    pub fn salary_cap(&self) -> i32 {
+        self.salary_cap.unwrap_or(self.site.salary_cap())
+    }
+
+    /// `--min-sal`, or the site's minimum spend when it wasn't set
+    pub fn min_sal(&self) -> i32 {
+        self.min_sal.unwrap_or(self.site.min_salary())
+    }
+
+    /// How many players of a position make it into the slate
+    pub fn pool_size(&self, pos: &Pos) -> i8 {
+        match pos {
+            Pos::Qb => self.qb_count,
+            Pos::Rb => self.rb_count,
+            Pos::Wr => self.wr_count,
+            Pos::Te => self.te_count,
+            Pos::D => self.d_count,
+            Pos::K => self.k_count.unwrap_or(0),
+        }
+    }
+
+    /// The config for single game slates, kickers come in unless `k_count` was set
+    pub fn island(&self) -> SlateConfig {
+        SlateConfig {
+            k_count: Some(self.k_count.unwrap_or(IslandFormat::KICKER_COUNT)),
+            ..self.clone()
+        }
+    }
+
+    /// Where the brute force saves finished combinations. Shards always save
+    /// since the file is what gets merged.
+    pub fn checkpoint_path(&self) -> Option<String> {
+        match (&self.checkpoint, self.shard) {
+            (Some(path), _) => Some(path.clone()),
+            (None, Some(shard)) => Some(format!(
+                "lineups/shard-{}-of-{}-{}-{}.jsonl",
+                shard.index,
+                shard.count,
+                self.week,
+                self.day.to_str()
+            )),
+            (None, None) => None,
+        }
+    }
+
+    /// The max/min normalizers in lib.rs are computed from the installed config
+    /// the first time they are used, so install before scoring anything.
+    pub fn install(&self) {
+        *SLATE_CONFIG.write().unwrap() = self.clone();
+        scoring_model().install();
+    }
+}
+
+/// Returns a copy of the installed config
+pub fn slate_config() -> SlateConfig {
+    SLATE_CONFIG.read().unwrap().clone()
+}
+
+pub fn flag_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|a| a == flag)
+        .and_then(|i| args.get(i + 1))
+        .map(|v| v.as_str())
+}
+
+/// Comma separated flag value, `--lock "12,Justin Jefferson-MIN"`
+pub fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+        .collect()
+}
+
+pub fn parse_flag<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .parse::<T>()
+        .map_err(|_| format!("Bad value for {}: {}", flag, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn test_from_args_overrides_default() {
+        let config: SlateConfig = SlateConfig::from_args(&to_args(&[
+            "--week",
+            "7",
+            "--day",
+            "mon",
+            "--wr-count",
+            "30",
+        ]))
+        .unwrap();
+        assert_eq!(config.week, 7);
+        assert_eq!(config.day, Day::Mon);
+        assert_eq!(config.pool_size(&Pos::Wr), 30);
+        assert_eq!(config.season, SlateConfig::default().season);
+    }
+
+    #[test]
+    fn test_from_args_bad_value() {
+        assert!(SlateConfig::from_args(&to_args(&["--week", "seven"])).is_err());
+        assert!(SlateConfig::from_args(&to_args(&["--day", "fri"])).is_err());
+    }
+
+    #[test]
+    fn test_lock_exclude_flags() {
+        let config: SlateConfig = SlateConfig::from_args(&to_args(&[
+            "--lock",
+            "12, Justin Jefferson-MIN",
+            "--exclude",
+            "7,",
+        ]))
+        .unwrap();
+        assert_eq!(config.lock, vec!["12", "Justin Jefferson-MIN"]);
+        assert_eq!(config.exclude, vec!["7"]);
+    }
+
+    #[test]
+    fn test_island_kickers() {
+        let config: SlateConfig = SlateConfig::default();
+        assert_eq!(config.pool_size(&Pos::K), 0);
+        assert_eq!(config.island().pool_size(&Pos::K), 2);
+        let config: SlateConfig = SlateConfig::from_args(&to_args(&["--k-count", "1"])).unwrap();
+        assert_eq!(config.island().pool_size(&Pos::K), 1);
+    }
+
+    #[test]
+    fn test_shard() {
+        let config: SlateConfig = SlateConfig::from_args(&to_args(&["--shard", "1/3"])).unwrap();
+        let shard: Shard = config.shard.unwrap();
+        assert!(shard.owns(1) && shard.owns(4) && !shard.owns(3));
+        assert_eq!(
+            config.checkpoint_path().unwrap(),
+            "lineups/shard-1-of-3-4-sun.jsonl"
+        );
+        assert!(Shard::try_from_str("3/3").is_err());
+        assert!(Shard::try_from_str("3").is_err());
+    }
+
+    #[test]
+    fn test_site_cap() {
+        let config: SlateConfig = SlateConfig::from_args(&to_args(&["--site", "dk"])).unwrap();
+        assert_eq!(config.site, Site::DraftKings);
+        assert_eq!((config.salary_cap(), config.min_sal()), (50000, 49500));
+        let config: SlateConfig =
+            SlateConfig::from_args(&to_args(&["--salary-cap", "55000", "--site", "fd"])).unwrap();
+        assert_eq!((config.salary_cap(), config.min_sal()), (55000, 59500));
+        let config: SlateConfig = SlateConfig::default();
+        assert_eq!(config.site, Site::FanDuel);
+        assert_eq!(config.salary_cap(), 60000);
+        let config: SlateConfig = serde_json::from_str(r#"{"site": "draftkings"}"#).unwrap();
+        assert_eq!((config.salary_cap(), config.min_sal()), (50000, 49500));
+        let config: SlateConfig =
+            serde_json::from_str(r#"{"site": "draftkings", "min_sal": 45000}"#).unwrap();
+        assert_eq!((config.salary_cap(), config.min_sal()), (50000, 45000));
+    }
+
+    #[test]
+    fn test_partial_json() {
+        let config: SlateConfig =
+            serde_json::from_str(r#"{"season": 2024, "week": 2, "day": "thu"}"#).unwrap();
+        assert_eq!(config.season, 2024);
+        assert_eq!(config.day, Day::Thu);
+        assert_eq!(config.salary_cap(), SlateConfig::default().salary_cap());
+    }
+}
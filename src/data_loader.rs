@@ -1,9 +1,12 @@
+use crate::site::{Site, StatLine};
 use crate::{player::*, Day, DATABASE_FILE};
 
 use lazy_static::lazy_static;
-use rusqlite::{params, Connection};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::de::DeserializeOwned;
 use serde::Deserialize;
-use std::{collections::HashMap, fs};
+use std::error::Error;
+use std::{collections::HashMap, fmt, fs};
 
 lazy_static! {
     pub static ref TEAM_NAME_TO_ABV: HashMap<&'static str, &'static str> = HashMap::from([
@@ -42,17 +45,130 @@ lazy_static! {
     ]);
 }
 
+/// What a load did, rows that couldn't be stored are skipped instead of
+/// stopping the import
+#[derive(Debug, Default, PartialEq)]
+pub struct ImportReport {
+    pub inserted: usize,
+    /// Line number in the file and why
+    pub skipped: Vec<(usize, String)>,
+    /// Players or teams that aren't in the db
+    pub unmatched: Vec<String>,
+    /// Names that could be more than one player, fixed with `alias add`
+    pub ambiguous: Vec<AmbiguousName>,
+}
+
+impl ImportReport {
+    pub fn skip(&mut self, line: usize, reason: String) {
+        self.skipped.push((line, reason));
+    }
+
+    pub fn record(&mut self, line: usize, result: Result<(), String>) {
+        match result {
+            Ok(()) => self.inserted += 1,
+            Err(reason) => self.skip(line, reason),
+        }
+    }
+}
+
+impl fmt::Display for ImportReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Inserted {} rows, skipped {}, unmatched {}, ambiguous {}",
+            self.inserted,
+            self.skipped.len(),
+            self.unmatched.len(),
+            self.ambiguous.len()
+        )?;
+        for (line, reason) in &self.skipped {
+            write!(f, "\n  line {}: {}", line, reason)?;
+        }
+        for name in &self.unmatched {
+            write!(f, "\n  unmatched: {}", name)?;
+        }
+        for ambiguous in &self.ambiguous {
+            write!(
+                f,
+                "\n  ambiguous: {} {}",
+                ambiguous.name,
+                ambiguous.pos.to_str().unwrap()
+            )?;
+            for (id, name, team) in &ambiguous.candidates {
+                write!(f, "\n    {}: {} {}", id, name, team)?;
+            }
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct FantasyStats {
     player: Option<String>,
     team: String,
     position: Option<String>,
-    fantasy_pts: f32,
+    /// Left out, the points come from the stat columns and the site's scoring
+    #[serde(default)]
+    fantasy_pts: Option<f32>,
+    #[serde(default)]
+    pass_yds: Option<f32>,
+    #[serde(default)]
+    pass_tds: Option<f32>,
+    #[serde(default)]
+    interceptions: Option<f32>,
+    #[serde(default)]
+    rush_yds: Option<f32>,
+    #[serde(default)]
+    rush_tds: Option<f32>,
+    #[serde(default)]
+    receptions: Option<f32>,
+    #[serde(default)]
+    rec_yds: Option<f32>,
+    #[serde(default)]
+    rec_tds: Option<f32>,
+    #[serde(default)]
+    fumbles_lost: Option<f32>,
+}
+
+impl FantasyStats {
+    /// `fantasyPts` when the file has it, otherwise the stat line under the
+    /// site's rules so DraftKings gets full PPR and FanDuel half
+    fn points(&self, site: Site) -> Result<f32, String> {
+        if let Some(pts) = self.fantasy_pts {
+            return Ok(pts);
+        }
+        let stats: [Option<f32>; 9] = [
+            self.pass_yds,
+            self.pass_tds,
+            self.interceptions,
+            self.rush_yds,
+            self.rush_tds,
+            self.receptions,
+            self.rec_yds,
+            self.rec_tds,
+            self.fumbles_lost,
+        ];
+        if stats.iter().all(|s| s.is_none()) {
+            return Err("No fantasyPts or stat columns".to_string());
+        }
+        let stat_line: StatLine = StatLine {
+            pass_yds: self.pass_yds.unwrap_or(0.0),
+            pass_tds: self.pass_tds.unwrap_or(0.0),
+            interceptions: self.interceptions.unwrap_or(0.0),
+            rush_yds: self.rush_yds.unwrap_or(0.0),
+            rush_tds: self.rush_tds.unwrap_or(0.0),
+            receptions: self.receptions.unwrap_or(0.0),
+            rec_yds: self.rec_yds.unwrap_or(0.0),
+            rec_tds: self.rec_tds.unwrap_or(0.0),
+            fumbles_lost: self.fumbles_lost.unwrap_or(0.0),
+        };
+        Ok(site.scoring().points(&stat_line))
+    }
 }
 
 // There is more fields we can grab if needed
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 #[serde(rename_all = "snake_case")]
 #[allow(dead_code)]
 pub struct ProjRecord {
@@ -62,7 +178,10 @@ pub struct ProjRecord {
     salary: i16,
     team: String,
     opp: String,
-    /// Exposure
+    /// The site's own player id, needed for bulk upload files
+    #[serde(default)]
+    site_id: Option<String>,
+    /// Exposure percentages, 0-100
     exposure_min: f32,
     exposure_max: f32,
     pts_proj: f32,
@@ -141,6 +260,107 @@ pub struct ProjRecord {
     month_consistency: f32,
     month_upside: f32, // Load in month year stats when they exist...
 }
+/// Row of a FanDuel salary export
+#[derive(Debug, Deserialize)]
+pub struct FdSalaryRecord {
+    #[serde(rename = "Id")]
+    id: String,
+    #[serde(rename = "Position")]
+    position: String,
+    #[serde(rename = "Nickname")]
+    name: String,
+    #[serde(rename = "Salary")]
+    salary: i32,
+    #[serde(rename = "Game")]
+    game: String,
+    #[serde(rename = "Team")]
+    team: String,
+    #[serde(rename = "Opponent")]
+    opp: String,
+    #[serde(rename = "Injury Indicator", default)]
+    injury: Option<String>,
+}
+
+/// Row of a DraftKings salary export, the opponent comes from the game info
+#[derive(Debug, Deserialize)]
+pub struct DkSalaryRecord {
+    #[serde(rename = "Position")]
+    position: String,
+    #[serde(rename = "Name")]
+    name: String,
+    #[serde(rename = "ID")]
+    id: String,
+    #[serde(rename = "Roster Position")]
+    roster_position: String,
+    #[serde(rename = "Salary")]
+    salary: i32,
+    #[serde(rename = "Game Info")]
+    game: String,
+    #[serde(rename = "TeamAbbrev")]
+    team: String,
+}
+
+/// Salary file row with both sites in the same shape
+#[derive(Debug, PartialEq)]
+pub struct SalaryRecord {
+    pub site_id: String,
+    pub name: String,
+    pub pos: Pos,
+    pub team: Team,
+    pub opp: Team,
+    pub salary: i32,
+    pub game: String,
+    pub injury: Option<String>,
+}
+
+fn parse_team(team: &str) -> Result<Team, String> {
+    Team::try_from_str(team).ok_or(format!("Unknown team {}", team))
+}
+
+fn parse_pos(pos: &str) -> Result<Pos, String> {
+    Pos::from_str(pos).map_err(|_| format!("Unknown position {}", pos))
+}
+
+impl TryFrom<FdSalaryRecord> for SalaryRecord {
+    type Error = String;
+
+    fn try_from(rec: FdSalaryRecord) -> Result<Self, Self::Error> {
+        Ok(SalaryRecord {
+            site_id: rec.id,
+            name: rec.name.trim().to_string(),
+            pos: parse_pos(&rec.position)?,
+            team: parse_team(&rec.team)?,
+            opp: parse_team(&rec.opp)?,
+            salary: rec.salary,
+            game: rec.game,
+            injury: rec.injury.filter(|i| !i.trim().is_empty()),
+        })
+    }
+}
+
+impl TryFrom<DkSalaryRecord> for SalaryRecord {
+    type Error = String;
+
+    fn try_from(rec: DkSalaryRecord) -> Result<Self, Self::Error> {
+        // Game info looks like `KC@LV 10/01/2023 04:25PM ET`
+        let game: String = rec.game.split_whitespace().next().unwrap_or("").to_string();
+        let opp: &str = game
+            .split('@')
+            .find(|t| *t != rec.team)
+            .ok_or(format!("{} not in game {}", rec.team, rec.game))?;
+        Ok(SalaryRecord {
+            site_id: rec.id,
+            name: rec.name.trim().to_string(),
+            pos: parse_pos(&rec.position)?,
+            team: parse_team(&rec.team)?,
+            opp: parse_team(opp)?,
+            salary: rec.salary,
+            game: game.clone(),
+            injury: None,
+        })
+    }
+}
+
 // TODO get specific stats per pos
 #[derive(Debug, Deserialize, Default)]
 #[serde(rename_all = "camelCase")]
@@ -151,136 +371,423 @@ pub struct RecDefVsPos {
 }
 
 /// Used for the data loader
-fn query_def_id(team: &String, conn: &Connection) -> Result<i16, rusqlite::Error> {
+fn query_def_id(team: &str, conn: &Connection) -> Option<i16> {
     let select_player: &str = "SELECT id FROM player WHERE pos = 'D' AND team = ?1";
-    conn.query_row(
-        select_player,
-        (TEAM_NAME_TO_ABV.get(team.as_str()).unwrap(),),
-        |row| row.get(0),
-    )
+    let abv: &str = TEAM_NAME_TO_ABV.get(team)?;
+    conn.query_row(select_player, (abv,), |row| row.get(0)).ok()
 }
 
-/// Load def vs pos stats into sqlite
-pub fn load_in_def_vs_pos(path: &str, table: &str) {
-    let contents: String = fs::read_to_string(path).expect("Failed to read in file");
+/// Reads every row of a csv, rows that don't parse are skipped in the report
+fn parse_rows<T: DeserializeOwned>(contents: &str, report: &mut ImportReport) -> Vec<(usize, T)> {
     let mut reader: csv::Reader<&[u8]> = csv::ReaderBuilder::new()
         .has_headers(true)
         .flexible(true)
         .from_reader(contents.as_bytes());
-    let conn: Connection = Connection::open(DATABASE_FILE).unwrap();
+    let headers: csv::StringRecord = match reader.headers() {
+        Ok(headers) => headers.clone(),
+        Err(e) => {
+            report.skip(1, format!("Bad header: {}", e));
+            return Vec::new();
+        }
+    };
+    let mut rows: Vec<(usize, T)> = Vec::new();
+    for res in reader.records() {
+        let record: csv::StringRecord = match res {
+            Ok(record) => record,
+            Err(e) => {
+                report.skip(e.position().map_or(0, |p| p.line() as usize), e.to_string());
+                continue;
+            }
+        };
+        let line: usize = record.position().map_or(0, |p| p.line() as usize);
+        match record.deserialize(Some(&headers)) {
+            Ok(row) => rows.push((line, row)),
+            Err(e) => report.skip(line, e.to_string()),
+        }
+    }
+    rows
+}
+
+/// Load def vs pos stats into sqlite, the points given up going into `week`
+pub fn load_in_def_vs_pos(
+    path: &str,
+    table: &str,
+    season: i16,
+    week: i8,
+) -> Result<ImportReport, Box<dyn Error>> {
+    let contents: String = fs::read_to_string(path)?;
+    let mut report: ImportReport = ImportReport::default();
+    let rows: Vec<(usize, RecDefVsPos)> = parse_rows(&contents, &mut report);
+    let conn: Connection = Connection::open(DATABASE_FILE)?;
     let insert: String = format!(
-        "INSERT INTO {} (id, team_name, pts_given_pg) VALUES (?1, ?2, ?3)",
+        "INSERT INTO {} (id, season, week, team_name, pts_given_pg) VALUES (?1, ?2, ?3, ?4, ?5)",
         table
     );
-    for res in reader.deserialize() {
-        let rec: RecDefVsPos = res.unwrap();
-        let def_id: Result<i16, rusqlite::Error> = query_def_id(&rec.team, &conn);
-        if def_id.is_err() || rec.pts_pg.is_none() {
-            continue;
-        }
-        conn.execute(insert.as_str(), (def_id.unwrap(), rec.team, rec.pts_pg))
-            .expect("Failed to insert D vs Pos");
+    for (line, rec) in rows {
+        let pts_pg: f32 = match rec.pts_pg {
+            Some(pts_pg) => pts_pg,
+            None => {
+                report.skip(line, "No points given".to_string());
+                continue;
+            }
+        };
+        let def_id: i16 = match query_def_id(&rec.team, &conn) {
+            Some(id) => id,
+            None => {
+                report.unmatched.push(rec.team);
+                continue;
+            }
+        };
+        let res = conn.execute(insert.as_str(), (def_id, season, week, &rec.team, pts_pg));
+        report.record(line, res.map(|_| ()).map_err(|e| e.to_string()));
     }
+    Ok(report)
 }
 
 /// Load in proj for Sunday Slate
-pub fn load_in_proj(path: &str, season: i16, week: i8, pos: &Pos, day: &Day) {
-    let contents: String = fs::read_to_string(path).expect("Failed to read in file");
-    let mut reader: csv::Reader<&[u8]> = csv::ReaderBuilder::new()
-        .has_headers(true)
-        .flexible(true)
-        .from_reader(contents.as_bytes());
-    let conn: Connection = Connection::open(DATABASE_FILE).unwrap();
-    for res in reader.deserialize() {
-        let mut rec: ProjRecord = res.unwrap();
+pub fn load_in_proj(
+    path: &str,
+    site: Site,
+    season: i16,
+    week: i8,
+    pos: &Pos,
+    day: &Day,
+) -> Result<ImportReport, Box<dyn Error>> {
+    let contents: String = fs::read_to_string(path)?;
+    let mut report: ImportReport = ImportReport::default();
+    let rows: Vec<(usize, ProjRecord)> = parse_rows(&contents, &mut report);
+    let conn: Connection = Connection::open(DATABASE_FILE)?;
+    for (line, mut rec) in rows {
         rec.pos = Some(pos.to_str().unwrap().to_owned());
-        match pos {
-            Pos::Qb => store_qb_proj(&rec, season, week, day, &conn),
-            Pos::D => store_dst_proj(&rec, season, week, day, &conn),
-            Pos::Rb => store_rb_proj(&rec, season, week, day, &conn),
-            Pos::Te => store_rec_proj(&rec, season, week, day, &conn),
-            Pos::Wr => store_rec_proj(&rec, season, week, day, &conn),
-            Pos::K => store_kick_proj(&rec, season, week, day, &conn),
-        }
+        report.record(line, store_proj(&rec, pos, site, season, week, day, &conn));
     }
+    Ok(report)
 }
-/// Load in any flex projects for Monday, Thu
-pub fn load_in_fan_pts(path: &str, season: i16, week: i8) {
-    let contents: String = fs::read_to_string(path).expect("Failed to read in stats file");
-    let mut reader: csv::Reader<&[u8]> = csv::ReaderBuilder::new()
-        .has_headers(true)
-        .flexible(true)
-        .from_reader(contents.as_bytes());
-    let conn: Connection = Connection::open(DATABASE_FILE).unwrap();
-    for res in reader.deserialize() {
-        let rec: FantasyStats = res.unwrap();
-        let mut id: i16 = 0;
+
+/// Load in actual fantasy points, scored with the site's rules for rows that only have stats
+pub fn load_in_fan_pts(
+    path: &str,
+    season: i16,
+    week: i8,
+    site: Site,
+) -> Result<ImportReport, Box<dyn Error>> {
+    let contents: String = fs::read_to_string(path)?;
+    let mut report: ImportReport = ImportReport::default();
+    let rows: Vec<(usize, FantasyStats)> = parse_rows(&contents, &mut report);
+    let conn: Connection = Connection::open(DATABASE_FILE)?;
+    let stats_in: &str = "INSERT INTO fan_pts (id, week, season, pts) 
+        VALUES (?1, ?2, ?3, ?4)";
+    for (line, rec) in rows {
+        let team: Team = match Team::try_from_str(&rec.team) {
+            Some(team) => team,
+            None => {
+                report.skip(line, format!("Unknown team {}", rec.team));
+                continue;
+            }
+        };
+        let pts: f32 = match rec.points(site) {
+            Ok(pts) => pts,
+            Err(reason) => {
+                report.skip(line, reason);
+                continue;
+            }
+        };
         // if team and pos than D
         // if just pos than QB
-        let team = &Team::from_str(&rec.team);
-        if rec.player.is_none() && rec.position.is_none() {
-            id = get_def_id(team, &conn).unwrap();
-        } else if rec.position.is_none() {
-            let id_opt = get_player_id(&rec.player.as_ref().unwrap(), team, &Pos::Qb, &conn);
-            if id_opt.is_none() {
-                println!("Cant find player: {:?}", &rec.player.unwrap());
-                continue;
+        let found: Result<NameMatch, String> = match (&rec.player, &rec.position) {
+            (None, None) => {
+                get_def_id(&team, &conn).map(|id| id.map_or(NameMatch::NotFound, NameMatch::Found))
             }
-            id = id_opt.unwrap();
-        } else {
-            let id_opt = get_player_id(
-                &rec.player.as_ref().unwrap(),
-                team,
-                &Pos::from_string(rec.position.unwrap()).unwrap(),
-                &conn,
-            );
-            if id_opt.is_none() {
-                println!("Cant find player: {:?}", &rec.player.unwrap());
+            (Some(player), None) => match_player(player, &team, &Pos::Qb, &conn),
+            (Some(player), Some(position)) => match Pos::from_str(position) {
+                Ok(pos) => match_player(player, &team, &pos, &conn),
+                Err(_) => {
+                    report.skip(line, format!("Unknown position {}", position));
+                    continue;
+                }
+            },
+            (None, Some(_)) => {
+                report.skip(line, "Player name missing".to_string());
                 continue;
             }
-            id = id_opt.unwrap();
+        };
+        match found {
+            Ok(NameMatch::Found(id)) => {
+                let res = conn.execute(stats_in, params![id, week, season, pts]);
+                report.record(line, res.map(|_| ()).map_err(|e| e.to_string()));
+            }
+            Ok(NameMatch::Ambiguous(ambiguous)) => report.ambiguous.push(ambiguous),
+            Err(reason) => report.skip(line, reason),
+            Ok(NameMatch::NotFound) => report.unmatched.push(format!(
+                "{} {}",
+                rec.player.as_deref().unwrap_or("Defense"),
+                team.to_str()
+            )),
         }
-        let stats_in: &str = "INSERT INTO fan_pts (id, week, season, pts) 
-        VALUES (?1, ?2, ?3, ?4)";
-        conn.execute(stats_in, params![id, week, season, &rec.fantasy_pts])
-            .expect("Failed to insert Quarter Back into database");
     }
+    Ok(report)
+}
+
+/// Parses a site salary export. DraftKings Showdown lists everyone twice, the
+/// captain rows are dropped.
+pub fn read_salary_file(
+    contents: &str,
+    site: Site,
+    report: &mut ImportReport,
+) -> Vec<(usize, SalaryRecord)> {
+    let rows: Vec<(usize, Result<SalaryRecord, String>)> = match site {
+        Site::FanDuel => parse_rows::<FdSalaryRecord>(contents, report)
+            .into_iter()
+            .map(|(line, rec)| (line, rec.try_into()))
+            .collect(),
+        Site::DraftKings => parse_rows::<DkSalaryRecord>(contents, report)
+            .into_iter()
+            .filter(|(_, rec)| rec.roster_position != "CPT")
+            .map(|(line, rec)| (line, rec.try_into()))
+            .collect(),
+    };
+    let mut records: Vec<(usize, SalaryRecord)> = Vec::new();
+    for (line, rec) in rows {
+        match rec {
+            Ok(rec) => records.push((line, rec)),
+            Err(reason) => report.skip(line, reason),
+        }
+    }
+    report.skipped.sort_by_key(|(line, _)| *line);
+    records
+}
+
+/// Load the site's salary file into the slate table, creating players that
+/// aren't in the db yet. Projections loaded for the same slate take their
+/// salary and site id from here.
+pub fn load_in_salaries(
+    path: &str,
+    site: Site,
+    season: i16,
+    week: i8,
+    day: &Day,
+) -> Result<ImportReport, Box<dyn Error>> {
+    let contents: String = fs::read_to_string(path)?;
+    let mut report: ImportReport = ImportReport::default();
+    let rows: Vec<(usize, SalaryRecord)> = read_salary_file(&contents, site, &mut report);
+    let conn: Connection = Connection::open(DATABASE_FILE)?;
+    let slate_in: &str =
+        "INSERT INTO slate (id, season, week, day, site, site_id, salary, game, injury)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)";
+    let ownership_up: &str = "UPDATE ownership SET salary = ?1, site_id = ?2
+        WHERE id = ?3 AND season = ?4 AND week = ?5 AND day = ?6";
+    for (line, rec) in rows {
+        let id: i16 = match salary_player_id(&rec, &conn) {
+            Ok(id) => id,
+            Err(reason) => {
+                report.skip(line, reason);
+                continue;
+            }
+        };
+        let res = conn
+            .execute(
+                slate_in,
+                params![
+                    id,
+                    season,
+                    week,
+                    day.to_str(),
+                    site.to_str(),
+                    &rec.site_id,
+                    rec.salary,
+                    &rec.game,
+                    &rec.injury
+                ],
+            )
+            .and_then(|_| {
+                conn.execute(
+                    ownership_up,
+                    params![rec.salary, &rec.site_id, id, season, week, day.to_str()],
+                )
+            })
+            // So the salary feature scores on the salary the cap is checked against
+            .and_then(|_| {
+                conn.execute(
+                    &format!(
+                        "UPDATE {} SET salary = ?1 WHERE id = ?2 AND season = ?3 AND week = ?4 AND day = ?5",
+                        rec.pos.get_proj_table()
+                    ),
+                    params![rec.salary, id, season, week, day.to_str()],
+                )
+            });
+        report.record(line, res.map(|_| ()).map_err(|e| e.to_string()));
+    }
+    Ok(report)
+}
+
+// Defenses go by team since the sites and projections name them differently
+fn salary_player_id(rec: &SalaryRecord, conn: &Connection) -> Result<i16, String> {
+    if rec.pos != Pos::D {
+        return get_player_id_create_if_missing(&rec.name, &rec.team, &rec.pos, conn);
+    }
+    if let Some(id) = get_def_id(&rec.team, conn)? {
+        return Ok(id);
+    }
+    let name: String = TEAM_NAME_TO_ABV
+        .iter()
+        .find(|(_, abv)| **abv == rec.team.to_str())
+        .map(|(name, _)| name.to_string())
+        .unwrap_or(rec.name.clone());
+    get_player_id_create_if_missing(&name, &rec.team, &rec.pos, conn)
 }
 
 /// Load in any flex projects for Monday, Thu
-pub fn load_in_anyflex(path: &str, season: i16, week: i8, day: &Day) {
-    let contents: String = fs::read_to_string(path).expect("Failed to read in file");
-    let mut reader: csv::Reader<&[u8]> = csv::ReaderBuilder::new()
-        .has_headers(true)
-        .flexible(true)
-        .from_reader(contents.as_bytes());
-    let conn: Connection = Connection::open(DATABASE_FILE).unwrap();
-    for res in reader.deserialize() {
-        let rec: ProjRecord = res.unwrap();
-        let pos: Pos = Pos::from_string_ref(rec.pos.as_ref().unwrap()).unwrap();
-        match pos {
-            Pos::Qb => store_qb_proj(&rec, season, week, day, &conn),
-            Pos::D => store_dst_proj(&rec, season, week, day, &conn),
-            Pos::Rb => store_rb_proj(&rec, season, week, day, &conn),
-            Pos::Te => store_rec_proj(&rec, season, week, day, &conn),
-            Pos::Wr => store_rec_proj(&rec, season, week, day, &conn),
-            Pos::K => store_kick_proj(&rec, season, week, day, &conn),
+pub fn load_in_anyflex(
+    path: &str,
+    site: Site,
+    season: i16,
+    week: i8,
+    day: &Day,
+) -> Result<ImportReport, Box<dyn Error>> {
+    let contents: String = fs::read_to_string(path)?;
+    let mut report: ImportReport = ImportReport::default();
+    let rows: Vec<(usize, ProjRecord)> = parse_rows(&contents, &mut report);
+    let conn: Connection = Connection::open(DATABASE_FILE)?;
+    for (line, rec) in rows {
+        match rec.pos.as_deref().map(Pos::from_str) {
+            Some(Ok(pos)) => {
+                report.record(line, store_proj(&rec, &pos, site, season, week, day, &conn))
+            }
+            _ => report.skip(line, format!("Unknown position {:?}", rec.pos)),
         }
     }
+    Ok(report)
+}
+
+fn store_proj(
+    rec: &ProjRecord,
+    pos: &Pos,
+    site: Site,
+    season: i16,
+    week: i8,
+    day: &Day,
+    conn: &Connection,
+) -> Result<(), String> {
+    match pos {
+        Pos::Qb => store_qb_proj(rec, site, season, week, day, conn),
+        Pos::D => store_dst_proj(rec, site, season, week, day, conn),
+        Pos::Rb => store_rb_proj(rec, site, season, week, day, conn),
+        Pos::Te => store_rec_proj(rec, site, season, week, day, conn),
+        Pos::Wr => store_rec_proj(rec, site, season, week, day, conn),
+        Pos::K => store_kick_proj(rec, site, season, week, day, conn),
+    }
+}
+
+/// Resolves the player, creating them if needed, and stores the ownership row.
+/// Returns the salary the proj row should carry, see `store_ownership`.
+fn store_player(
+    rec: &ProjRecord,
+    site: Site,
+    season: i16,
+    week: i8,
+    day: &Day,
+    conn: &Connection,
+) -> Result<(Pos, i16, i32), String> {
+    let pos: Pos = rec
+        .pos
+        .as_deref()
+        .and_then(|p| Pos::from_str(p).ok())
+        .ok_or(format!("Unknown position {:?}", rec.pos))?;
+    let team: Team = parse_team(&rec.team)?;
+    parse_team(&rec.opp)?;
+    let id: i16 = get_player_id_create_if_missing(&rec.player, &team, &pos, conn)?;
+    let salary: i32 =
+        store_ownership(rec, id, site, season, week, day, conn).map_err(|e| e.to_string())?;
+    store_game_env(rec, season, week, conn)?;
+    store_player_share(rec, id, season, week, conn).map_err(|e| e.to_string())?;
+    Ok((pos, id, salary))
+}
+
+/// Every player row carries their game's lines and weather, the last one wins.
+/// Teams are stored by their `Team` abbreviation so the feed's spellings match.
+pub fn store_game_env(
+    rec: &ProjRecord,
+    season: i16,
+    week: i8,
+    conn: &Connection,
+) -> Result<(), String> {
+    let team: Team = parse_team(&rec.team)?;
+    let opp: Team = parse_team(&rec.opp)?;
+    let game_env_in: &str =
+        "INSERT INTO game_env (season, week, team, opp, spread, total, team_total,
+            opp_total, temperature, wind_speed, precip_per)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)";
+    conn.execute(
+        game_env_in,
+        params![
+            season,
+            week,
+            team.to_str(),
+            opp.to_str(),
+            rec.vegas_spread,
+            rec.vegas_total,
+            rec.vegas_team_total,
+            rec.vegas_opp_total,
+            rec.tempature,
+            rec.wind_speed,
+            rec.precip_per
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+pub fn store_player_share(
+    rec: &ProjRecord,
+    id: i16,
+    season: i16,
+    week: i8,
+    conn: &Connection,
+) -> Result<(), rusqlite::Error> {
+    let share_in: &str = "INSERT INTO player_share (id, season, week, imp_pts_proj, snaps_share,
+            rec_tgt_share, rec_td_tgt_share, rec_yds_share, rush_td_share, rush_yds_share, pass_to_rb_per,
+            proj_rush_yds, yds_per_carry, yds_per_tgt, yds_per_rec, avg_long_rec_yds)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)";
+    conn.execute(
+        share_in,
+        params![
+            id,
+            season,
+            week,
+            rec.imp_pts_proj,
+            rec.snaps_share,
+            rec.rec_tgt_share,
+            rec.rec_td_tgt_share,
+            rec.rec_yds_share,
+            rec.rush_td_share,
+            rec.rush_yds_share,
+            rec.pass_to_rb_per,
+            rec.proj_rush_yds,
+            rec.yds_per_carry,
+            rec.yds_per_tgt,
+            rec.yds_per_rec,
+            rec.avg_long_rec_yds
+        ],
+    )?;
+    Ok(())
 }
 
-fn store_qb_proj(rec: &ProjRecord, season: i16, week: i8, day: &Day, conn: &Connection) {
-    let pos: Pos = Pos::from_str(&rec.pos.as_ref().expect("Pos missing")).unwrap();
-    let id: i16 =
-        get_player_id_create_if_missing(&rec.player, &Team::from_str(&rec.team), &pos, conn);
-    store_ownership(&rec, id, season, week, day);
+fn store_qb_proj(
+    rec: &ProjRecord,
+    site: Site,
+    season: i16,
+    week: i8,
+    day: &Day,
+    conn: &Connection,
+) -> Result<(), String> {
+    let (_, id, salary) = store_player(rec, site, season, week, day, conn)?;
     let qb_in: &str =
         "INSERT INTO qb_proj (id, season, week, name, team, opp, pts_proj, cieling_proj, floor_proj, pts_plus_minus_proj, 
             pts_sal_proj, vegas_total, avg_pass_atts, avg_pass_comps, avg_pass_yds, avg_pass_tds, avg_rush_atts,
             avg_long_pass_yds, pass_to_wr_per, pass_to_te_per, wind_speed, salary, own_proj, rating, red_zone_op_pg,
-            vegas_team_total, month_consistency, yds_per_pass_att, day, avg_rush_yds) 
+            vegas_team_total, month_consistency, yds_per_pass_att, day, avg_rush_yds, exposure_min, exposure_max) 
         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, 
-                ?23, ?24, ?25, ?26, ?27, ?28, ?29, ?30)";
+                ?23, ?24, ?25, ?26, ?27, ?28, ?29, ?30, ?31, ?32)";
     conn.execute(
         qb_in,
         params![
@@ -305,7 +812,7 @@ fn store_qb_proj(rec: &ProjRecord, season: i16, week: i8, day: &Day, conn: &Conn
             rec.pass_to_wr_per,
             rec.pass_to_te_per,
             rec.wind_speed,
-            rec.salary,
+            salary,
             rec.own_proj,
             rec.rating,
             rec.red_zone_opp_pg,
@@ -313,23 +820,30 @@ fn store_qb_proj(rec: &ProjRecord, season: i16, week: i8, day: &Day, conn: &Conn
             rec.month_consistency,
             rec.yds_per_pass_att,
             day.to_str(),
-            rec.avg_rush_yds
+            rec.avg_rush_yds,
+            rec.exposure_min,
+            rec.exposure_max
         ],
     )
-    .expect("Failed to insert Quarter Back into database");
+    .map_err(|e| format!("Failed to insert Quarter Back: {}", e))?;
+    Ok(())
 }
 
-fn store_rb_proj(rec: &ProjRecord, season: i16, week: i8, day: &Day, conn: &Connection) {
-    let pos: Pos = Pos::from_str(&rec.pos.as_ref().expect("Pos not set")).unwrap();
-    let id: i16 =
-        get_player_id_create_if_missing(&rec.player, &Team::from_str(&rec.team), &pos, conn);
-    store_ownership(&rec, id, season, week, day);
+fn store_rb_proj(
+    rec: &ProjRecord,
+    site: Site,
+    season: i16,
+    week: i8,
+    day: &Day,
+    conn: &Connection,
+) -> Result<(), String> {
+    let (_, id, salary) = store_player(rec, site, season, week, day, conn)?;
     let rb_in: &str =
         "INSERT INTO rb_proj (id, season, week, name, team, opp, pts_proj, cieling_proj, floor_proj, pts_plus_minus_proj,
             pts_sal_proj, vegas_total, rush_yds_share, avg_atts, avg_td, avg_rush_yds, avg_rec_tgts, salary, own_proj,
-            rating, snaps_per, year_consistency, vegas_team_total, month_consistency, day) 
+            rating, snaps_per, year_consistency, vegas_team_total, month_consistency, day, exposure_min, exposure_max) 
         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12,?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, 
-                ?22, ?23, ?24, ?25)";
+                ?22, ?23, ?24, ?25, ?26, ?27)";
 
     conn.execute(
         rb_in,
@@ -351,32 +865,39 @@ fn store_rb_proj(rec: &ProjRecord, season: i16, week: i8, day: &Day, conn: &Conn
             rec.avg_rush_td,
             rec.avg_rush_yds,
             rec.avg_tgts,
-            rec.salary,
+            salary,
             rec.own_proj,
             rec.rating,
             rec.snaps_share,
             rec.year_consistency,
             rec.vegas_team_total,
             rec.month_consistency,
-            day.to_str()
+            day.to_str(),
+            rec.exposure_min,
+            rec.exposure_max
         ],
     )
-    .expect("Failed to insert Rb into database");
+    .map_err(|e| format!("Failed to insert Rb: {}", e))?;
+    Ok(())
 }
 
-fn store_rec_proj(rec: &ProjRecord, season: i16, week: i8, day: &Day, conn: &Connection) {
-    let pos: Pos = Pos::from_str(&rec.pos.as_ref().expect("Pos missing")).unwrap();
-    let id: i16 =
-        get_player_id_create_if_missing(&rec.player, &Team::from_str(&rec.team), &pos, conn);
-    store_ownership(&rec, id, season, week, day);
+fn store_rec_proj(
+    rec: &ProjRecord,
+    site: Site,
+    season: i16,
+    week: i8,
+    day: &Day,
+    conn: &Connection,
+) -> Result<(), String> {
+    let (pos, id, salary) = store_player(rec, site, season, week, day, conn)?;
     let table: &str = if pos == Pos::Wr { "wr_proj" } else { "te_proj" };
     let rec_in: String = format!(
         "INSERT INTO {} (id, season, week, name, team, opp, pts_proj, cieling_proj, floor_proj, pts_plus_minus_proj, 
             pts_sal_proj, vegas_total, avg_recp, avg_tgts, avg_td, avg_rec_yds, avg_rush_yds, red_zone_op_pg, 
             rec_tgt_share, salary, own_proj, rating, year_consistency, year_upside, vegas_team_total, 
-            month_consistency, month_upside, day) 
+            month_consistency, month_upside, day, exposure_min, exposure_max) 
         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, 
-                ?23, ?24, ?25, ?26, ?27, ?28)",
+                ?23, ?24, ?25, ?26, ?27, ?28, ?29, ?30)",
         table
     );
     conn.execute(
@@ -401,7 +922,7 @@ fn store_rec_proj(rec: &ProjRecord, season: i16, week: i8, day: &Day, conn: &Con
             rec.avg_rush_yds,
             rec.red_zone_opp_pg,
             rec.rec_tgt_share,
-            rec.salary,
+            salary,
             rec.own_proj,
             rec.rating,
             rec.year_consistency,
@@ -409,23 +930,30 @@ fn store_rec_proj(rec: &ProjRecord, season: i16, week: i8, day: &Day, conn: &Con
             rec.vegas_team_total,
             rec.month_consistency,
             rec.month_upside,
-            day.to_str()
+            day.to_str(),
+            rec.exposure_min,
+            rec.exposure_max
         ],
     )
-    .expect("Failed to insert Wide Reciever into database");
+    .map_err(|e| format!("Failed to insert Wide Reciever: {}", e))?;
+    Ok(())
 }
 
-fn store_kick_proj(rec: &ProjRecord, season: i16, week: i8, day: &Day, conn: &Connection) {
-    let pos: Pos = Pos::from_str(&rec.pos.as_ref().expect("Pos missing")).unwrap();
-    let id: i16 =
-        get_player_id_create_if_missing(&rec.player, &Team::from_str(&rec.team), &pos, conn);
-    store_ownership(&rec, id, season, week, day);
+fn store_kick_proj(
+    rec: &ProjRecord,
+    site: Site,
+    season: i16,
+    week: i8,
+    day: &Day,
+    conn: &Connection,
+) -> Result<(), String> {
+    let (_, id, salary) = store_player(rec, site, season, week, day, conn)?;
     let dst_in: &str = "INSERT INTO kick_proj (id, season, week, name, team, opp, pts_proj, cieling_proj, floor_proj,
-         pts_plus_minus_proj, pts_sal_proj, vegas_total, salary, own_proj, rating, day) 
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)";
+         pts_plus_minus_proj, pts_sal_proj, vegas_total, salary, own_proj, rating, day, exposure_min, exposure_max) 
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18)";
     conn.execute(
         dst_in,
-        (
+        params![
             id,
             season,
             week,
@@ -438,25 +966,31 @@ fn store_kick_proj(rec: &ProjRecord, season: i16, week: i8, day: &Day, conn: &Co
             rec.pts_plus_minus_proj,
             rec.pts_sal_proj,
             rec.vegas_total,
-            rec.salary,
+            salary,
             rec.own_proj,
             rec.rating,
             day.to_str(),
-        ),
+            rec.exposure_min,
+            rec.exposure_max
+        ],
     )
-    .expect("Failed to insert Defense into database");
+    .map_err(|e| format!("Failed to insert Kicker: {}", e))?;
+    Ok(())
 }
 
-fn store_dst_proj(rec: &ProjRecord, season: i16, week: i8, day: &Day, conn: &Connection) {
-    let pos: Pos = Pos::from_str(&rec.pos.as_ref().expect("Pos missing")).unwrap();
-    println!("{}", &rec.player);
-    let id: i16 =
-        get_player_id_create_if_missing(&rec.player, &Team::from_str(&rec.team), &pos, conn);
-    store_ownership(&rec, id, season, week, day);
+fn store_dst_proj(
+    rec: &ProjRecord,
+    site: Site,
+    season: i16,
+    week: i8,
+    day: &Day,
+    conn: &Connection,
+) -> Result<(), String> {
+    let (_, id, salary) = store_player(rec, site, season, week, day, conn)?;
     let dst_in: &str = "INSERT INTO dst_proj (id, season, week, name, team, opp, pts_proj, cieling_proj, floor_proj, 
         pts_plus_minus_proj, pts_sal_proj, vegas_total, salary, own_proj, rating, vegas_opp_total, day, 
-        vegas_team_total) 
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18)";
+        vegas_team_total, exposure_min, exposure_max) 
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20)";
     conn.execute(
         dst_in,
         params![
@@ -472,23 +1006,55 @@ fn store_dst_proj(rec: &ProjRecord, season: i16, week: i8, day: &Day, conn: &Con
             rec.pts_plus_minus_proj,
             rec.pts_sal_proj,
             rec.vegas_total,
-            rec.salary,
+            salary,
             rec.own_proj,
             rec.rating,
             rec.vegas_opp_total,
             day.to_str(),
-            rec.vegas_team_total
+            rec.vegas_team_total,
+            rec.exposure_min,
+            rec.exposure_max
         ],
     )
-    .expect("Failed to insert Defense into database");
+    .map_err(|e| format!("Failed to insert Defense: {}", e))?;
+    Ok(())
 }
 
-/// Load ownership stats
-pub fn store_ownership(rec: &ProjRecord, id: i16, season: i16, week: i8, day: &Day) {
-    let conn: Connection = Connection::open(DATABASE_FILE).unwrap();
+/// Load ownership stats, returns the salary stored
+pub fn store_ownership(
+    rec: &ProjRecord,
+    id: i16,
+    site: Site,
+    season: i16,
+    week: i8,
+    day: &Day,
+    conn: &Connection,
+) -> Result<i32, rusqlite::Error> {
+    // The site salary file wins over the projection file
+    let select_slate: &str = "SELECT salary, site_id FROM slate
+        WHERE id = ?1 AND season = ?2 AND week = ?3 AND day = ?4 AND site = ?5";
+    let listed: Option<(i32, String)> = conn
+        .query_row(
+            select_slate,
+            (id, season, week, day.to_str(), site.to_str()),
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+    let (salary, site_id): (i32, Option<String>) = match listed {
+        Some((salary, site_id)) => {
+            if salary != rec.salary as i32 {
+                println!(
+                    "{} salary is {} in projections but {} on the site",
+                    rec.player, rec.salary, salary
+                );
+            }
+            (salary, Some(site_id))
+        }
+        None => (rec.salary as i32, rec.site_id.clone()),
+    };
     let ownership_in: &str =
-        "INSERT INTO ownership (id, season, week, day, name, team, opp, pos, salary, own_per) 
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)";
+        "INSERT INTO ownership (id, season, week, day, name, team, opp, pos, salary, own_per, site_id) 
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)";
 
     conn.execute(
         ownership_in,
@@ -501,33 +1067,199 @@ pub fn store_ownership(rec: &ProjRecord, id: i16, season: i16, week: i8, day: &D
             &rec.team,
             &rec.opp,
             &rec.pos,
-            rec.salary,
+            salary,
             rec.own_proj,
+            site_id,
         ),
-    )
-    .expect("Failed to insert Ownership into database");
+    )?;
+    Ok(salary)
 }
 
 // Create player Id Record
-pub fn load_player_id(player: &Player, conn: &Connection) -> i16 {
+pub fn load_player_id(player: &Player, conn: &Connection) -> Result<i16, String> {
     let player_in: &str = "INSERT INTO player (name, team , pos) VALUES (?1, ?2, ?3)";
     conn.execute(
         player_in,
         (
             &player.name,
             &player.team.to_str(),
-            player.pos.to_str().expect("Failed to convert Pos to Str"),
+            player.pos.to_str().unwrap(),
         ),
     )
-    .expect("Failed to insert Player into database");
-    return get_player_id(&player.name, &player.team, &player.pos, conn)
-        .expect("Just loaded player but cannot find him.");
+    .map_err(|e| format!("Couldn't add player {}: {}", player.name, e))?;
+    get_player_id(&player.name, &player.team, &player.pos, conn)?
+        .ok_or(format!("Added player {} but can't find them", player.name))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_fan_pts_site_scoring() {
+        let contents: &str = "player,team,position,fantasyPts,receptions,recYds,recTds,fumblesLost
+WR0 KCman,KC,WR,,8,110,1,1
+WR1 KCman,KC,WR,12.5,,,,
+WR2 KCman,KC,WR,,,,,";
+        let mut report: ImportReport = ImportReport::default();
+        let rows: Vec<(usize, FantasyStats)> = parse_rows(contents, &mut report);
+        assert_eq!(rows[0].1.points(Site::FanDuel), Ok(19.0));
+        assert_eq!(rows[0].1.points(Site::DraftKings), Ok(27.0));
+        assert_eq!(rows[1].1.points(Site::DraftKings), Ok(12.5));
+        assert!(rows[2].1.points(Site::FanDuel).is_err());
+    }
+
+    #[test]
+    fn test_read_salary_file() {
+        let fanduel: &str = "Id,Position,First Name,Nickname,Last Name,FPPG,Played,Salary,Game,Team,Opponent,Injury Indicator,Injury Details
+91142-55010,QB,Patrick,Patrick Mahomes,Mahomes,24.1,3,9000,KC@LV,KC,LV,,
+91142-61234,D,Las Vegas,Las Vegas Raiders,Raiders,6.2,3,3200,KC@LV,LV,KC,Q,Illness";
+        let mut report: ImportReport = ImportReport::default();
+        let records: Vec<SalaryRecord> = read_salary_file(fanduel, Site::FanDuel, &mut report)
+            .into_iter()
+            .map(|(_, rec)| rec)
+            .collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].site_id, "91142-55010");
+        assert_eq!(records[0].injury, None);
+        assert_eq!(records[1].pos, Pos::D);
+        assert_eq!(records[1].injury, Some("Q".to_string()));
+
+        let draftkings: &str = "Position,Name + ID,Name,ID,Roster Position,Salary,Game Info,TeamAbbrev,AvgPointsPerGame
+QB,Patrick Mahomes (28493021),Patrick Mahomes,28493021,CPT,16800,KC@LV 10/01/2023 04:25PM ET,KC,24.1
+QB,Patrick Mahomes (28493001),Patrick Mahomes,28493001,FLEX,11200,KC@LV 10/01/2023 04:25PM ET,KC,24.1
+DST,Raiders (28493050),Raiders,28493050,FLEX,4000,KC@LV 10/01/2023 04:25PM ET,LV,6.2";
+        let records: Vec<SalaryRecord> =
+            read_salary_file(draftkings, Site::DraftKings, &mut report)
+                .into_iter()
+                .map(|(_, rec)| rec)
+                .collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].salary, 11200);
+        assert!(records[0].opp == Team::Lv && records[1].opp == Team::Kc);
+        assert_eq!(records[1].game, "KC@LV");
+        assert!(report.skipped.is_empty());
+    }
+
+    #[test]
+    fn test_skipped_rows() {
+        let salaries: &str = "Id,Position,Nickname,Salary,Game,Team,Opponent
+91142-1,QB,Patrick Mahomes,9000,KC@LV,KC,LV
+91142-2,QB,Nobody,9000,KC@XYZ,XYZ,KC
+91142-3,QB,Somebody,lots,KC@LV,LV,KC
+91142-4,P,Punter,4000,KC@LV,LV,KC";
+        let mut report: ImportReport = ImportReport::default();
+        let records: Vec<(usize, SalaryRecord)> =
+            read_salary_file(salaries, Site::FanDuel, &mut report);
+        assert_eq!(records.len(), 1);
+        let lines: Vec<usize> = report.skipped.iter().map(|(line, _)| *line).collect();
+        assert_eq!(lines, vec![3, 4, 5]);
+        assert_eq!(report.skipped[0].1, "Unknown team XYZ");
+        report.record(2, Ok(()));
+        assert!(report.to_string().starts_with("Inserted 1 rows, skipped 3"));
+    }
+
+    #[test]
+    fn test_player_lookup_errors() {
+        // No player table, every lookup fails instead of panicking
+        let conn: Connection = Connection::open_in_memory().unwrap();
+        let name: String = String::from("Nobody Atall");
+        assert!(get_player_id_create_if_missing(&name, &Team::Kc, &Pos::Qb, &conn).is_err());
+        assert!(match_player(&name, &Team::Kc, &Pos::Qb, &conn).is_err());
+        assert!(get_def_id(&Team::Kc, &conn).is_err());
+    }
+
+    #[test]
+    fn test_store_game_env() {
+        let conn: Connection = Connection::open_in_memory().unwrap();
+        crate::tables::migrate(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO player VALUES (7, 'Kenneth Walker', 'SEA', 'RB')",
+            (),
+        )
+        .unwrap();
+        let rec: ProjRecord = ProjRecord {
+            team: "SEA".to_string(),
+            opp: "ARI".to_string(),
+            vegas_spread: -6.5,
+            vegas_total: 47.5,
+            vegas_team_total: 27.0,
+            tempature: 38.0,
+            precip_per: 60.0,
+            rush_td_share: 0.45,
+            yds_per_carry: 4.8,
+            ..ProjRecord::default()
+        };
+        store_game_env(&rec, 2023, 4, &conn).unwrap();
+        store_player_share(&rec, 7, 2023, 4, &conn).unwrap();
+        let env: GameEnv = query_game_env(&Team::Sea, 4, 2023, &conn).unwrap();
+        assert_eq!(env.opp, Team::Ari);
+        assert_eq!(env.spread, -6.5);
+        assert_eq!(env.precip_per, 60.0);
+        let share: PlayerShare = query_player_share(7, 4, 2023, &conn).unwrap();
+        assert_eq!(share.rush_td_share, 0.45);
+        assert_eq!(share.yds_per_carry, 4.8);
+        assert!(query_player_share(8, 4, 2023, &conn).is_none());
+    }
+
+    #[test]
+    fn test_site_salary_in_proj() {
+        let conn: Connection = Connection::open_in_memory().unwrap();
+        crate::tables::migrate(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO player VALUES (7, 'Josh Allen', 'BUF', 'QB')",
+            (),
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO slate (id, season, week, day, site, site_id, salary, game)
+            VALUES (7, 1999, 4, 'sun', 'fanduel', '91142-7', 9000, 'BUF@MIA'),
+                (7, 1999, 4, 'sun', 'draftkings', '3001', 7000, 'BUF@MIA')",
+            (),
+        )
+        .unwrap();
+        let rec: ProjRecord = ProjRecord {
+            player: "Josh Allen".to_string(),
+            pos: Some("QB".to_string()),
+            team: "BUF".to_string(),
+            opp: "MIA".to_string(),
+            salary: 8500,
+            ..ProjRecord::default()
+        };
+        store_proj(&rec, &Pos::Qb, Site::FanDuel, 1999, 4, &Day::Sun, &conn).unwrap();
+        let salary = |table: &str| -> i32 {
+            conn.query_row(
+                &format!("SELECT salary FROM {} WHERE id = 7", table),
+                (),
+                |row| row.get(0),
+            )
+            .unwrap()
+        };
+        assert_eq!(salary("ownership"), 9000);
+        assert_eq!(salary("qb_proj"), 9000);
+    }
+
+    #[test]
+    fn test_store_game_env_alias() {
+        let conn: Connection = Connection::open_in_memory().unwrap();
+        crate::tables::migrate(&conn).unwrap();
+        let rec: ProjRecord = ProjRecord {
+            team: "BLT".to_string(),
+            opp: "@CLV".to_string(),
+            wind_speed: 18.0,
+            ..ProjRecord::default()
+        };
+        store_game_env(&rec, 1999, 4, &conn).unwrap();
+        let env: GameEnv = query_game_env(&Team::Bal, 4, 1999, &conn).unwrap();
+        assert_eq!(env.opp, Team::Cle);
+        assert_eq!(env.wind_speed, 18.0);
+        let bad: ProjRecord = ProjRecord {
+            team: "XYZ".to_string(),
+            ..rec
+        };
+        assert!(store_game_env(&bad, 1999, 4, &conn).is_err());
+    }
+
     #[test]
     fn test_get_player_id() {
         let conn: Connection = Connection::open(DATABASE_FILE).unwrap();
@@ -537,6 +1269,7 @@ mod tests {
             &Pos::Wr,
             &conn,
         )
+        .unwrap()
         .unwrap();
         assert_eq!(id, 154)
     }
@@ -0,0 +1,317 @@
+use std::error::Error;
+use std::sync::Arc;
+
+use rusqlite::Connection;
+
+use crate::config::SlateConfig;
+use crate::get_lock_exclude_ids;
+use crate::get_slate;
+use crate::lineup::*;
+use crate::optimizer::{single_slot_pool, sort_by_score, SlotBounds, TopLineups};
+use crate::player::*;
+use crate::scorer::{load_projs, rank_lineups, Scorer, SlateScorer};
+use crate::DATABASE_FILE;
+
+/// Finds the top `exact_top_n` classic lineups without trying every combination.
+/// Same pool and same lineup score as `build_all_possible_lineups`.
+pub fn build_exact_lineups(
+    config: &SlateConfig,
+    scorer: &Arc<dyn Scorer>,
+) -> Result<Vec<Lineup>, Box<dyn Error>> {
+    let conn: Connection = Connection::open(DATABASE_FILE)?;
+    let (locked, _) = get_lock_exclude_ids(config, &conn)?;
+    let slate: Vec<LitePlayer> = get_slate(config, &conn)?;
+    let slate_scorer: SlateScorer =
+        SlateScorer::new(scorer.clone(), load_projs(&slate, config, &conn), false);
+    let players: Vec<LitePlayer> = slate_scorer.score_players(&slate);
+    let lineups: Vec<Lineup> = solve_exact(&players, config, &locked, &slate_scorer)
+        .into_iter()
+        .map(|l| l.build(config.week, config.season))
+        .collect::<Result<Vec<Lineup>, Box<dyn Error>>>()?;
+    Ok(rank_lineups(lineups, scorer.as_ref()))
+}
+
+/// Branch and bound over the player pool, best lineup first.
+/// Lineups are searched as player sets so each one shows up once, the flex is
+/// an extra player at one of the site's flex positions.
+pub fn solve_exact(
+    players: &[LitePlayer],
+    config: &SlateConfig,
+    locked: &[i16],
+    scorer: &SlateScorer,
+) -> Vec<LineupBuilder> {
+    let by_pos = |pos: Pos| -> Vec<LitePlayer> {
+        players.iter().filter(|p| p.pos == pos).cloned().collect()
+    };
+    let qbs: Vec<LitePlayer> = single_slot_pool(players, Pos::Qb, locked);
+    let defs: Vec<LitePlayer> = single_slot_pool(players, Pos::D, locked);
+    let rbs: Vec<LitePlayer> = by_pos(Pos::Rb);
+    let wrs: Vec<LitePlayer> = by_pos(Pos::Wr);
+
+    let mut top: TopLineups = TopLineups::new(config.exact_top_n);
+    for flex_pos in config.site.flex_positions() {
+        let count = |pos: Pos, base: usize| -> usize { base + (*flex_pos == pos) as usize };
+        let (te_count, rb_count, wr_count) =
+            (count(Pos::Te, 1), count(Pos::Rb, 2), count(Pos::Wr, 3));
+        // A locked TE only narrows the pool when there is one TE spot
+        let tes: Vec<LitePlayer> = match te_count {
+            1 => single_slot_pool(players, Pos::Te, locked),
+            _ => by_pos(Pos::Te),
+        };
+        let groups: Vec<Group> = vec![
+            Group::new(&qbs, 1),
+            Group::new(&defs, 1),
+            Group::new(&tes, te_count),
+            Group::new(&rbs, rb_count),
+            Group::new(&wrs, wr_count),
+        ];
+        let mut search: Search = Search::new(groups, config, locked, scorer, top);
+        // Bounds count the most the lineup term can add
+        search.search(0, 0, 0, 0, scorer.max_correlation());
+        top = search.top;
+    }
+    top.into_sorted()
+}
+
+/// `count` players picked from a pool sorted best score first
+struct Group {
+    pool: Vec<LitePlayer>,
+    count: usize,
+    bounds: SlotBounds,
+    // Bounds for a single player, used for the rest of a partly picked group
+    single: SlotBounds,
+}
+
+impl Group {
+    fn new(players: &[LitePlayer], count: usize) -> Group {
+        Group {
+            pool: sort_by_score(players.to_vec()),
+            count,
+            bounds: SlotBounds::new(players, count),
+            single: SlotBounds::new(players, 1),
+        }
+    }
+}
+
+struct Search<'a> {
+    groups: Vec<Group>,
+    config: &'a SlateConfig,
+    locked: &'a [i16],
+    scorer: &'a SlateScorer,
+    // Bounds for every group after the index
+    after: Vec<SlotBounds>,
+    chosen: Vec<LitePlayer>,
+    top: TopLineups,
+}
+
+impl<'a> Search<'a> {
+    fn new(
+        groups: Vec<Group>,
+        config: &'a SlateConfig,
+        locked: &'a [i16],
+        scorer: &'a SlateScorer,
+        top: TopLineups,
+    ) -> Search<'a> {
+        let after: Vec<SlotBounds> = (0..groups.len())
+            .map(|g| {
+                groups[g + 1..]
+                    .iter()
+                    .fold(SlotBounds::default(), |a, x| a.plus(x.bounds))
+            })
+            .collect();
+        Search {
+            groups,
+            config,
+            locked,
+            scorer,
+            after,
+            chosen: Vec::with_capacity(9),
+            top,
+        }
+    }
+
+    fn threshold(&self) -> Option<f32> {
+        self.top.threshold()
+    }
+
+    fn search(&mut self, group: usize, picked: usize, start: usize, salary: i32, score: f32) {
+        if group == self.groups.len() {
+            self.finish_lineup(salary);
+            return;
+        }
+        let count: usize = self.groups[group].count;
+        if picked == count {
+            self.search(group + 1, 0, 0, salary, score);
+            return;
+        }
+        let left: usize = count - picked;
+        let pool_len: usize = self.groups[group].pool.len();
+        if pool_len < start + left {
+            return;
+        }
+        let single: SlotBounds = self.groups[group].single;
+        let rest_count: i32 = (left - 1) as i32;
+        for i in start..=(pool_len - left) {
+            let player: LitePlayer = self.groups[group].pool[i];
+            // The rest of this group comes from later in the pool
+            let rest: SlotBounds = SlotBounds {
+                cheapest: single.cheapest * rest_count,
+                priciest: single.priciest * rest_count,
+                max_score: self.groups[group].pool[i + 1..i + left]
+                    .iter()
+                    .map(|p| p.score)
+                    .sum(),
+            }
+            .plus(self.after[group]);
+            // Pool is sorted so nothing later in it can do better
+            if !rest.can_beat(score + player.score, self.threshold()) {
+                break;
+            }
+            let new_salary: i32 = salary + player.salary as i32;
+            if !rest.salary_in_reach(new_salary, self.config) {
+                continue;
+            }
+            if player.pos == Pos::D && self.chosen.iter().any(|p| p.opp == player.team) {
+                continue;
+            }
+            self.chosen.push(player);
+            self.search(group, picked + 1, i + 1, new_salary, score + player.score);
+            self.chosen.pop();
+        }
+    }
+
+    fn finish_lineup(&mut self, salary: i32) {
+        if salary >= self.config.salary_cap() || salary <= self.config.min_sal() {
+            return;
+        }
+        let lineup: LineupBuilder = arrange_lineup(&self.chosen);
+        if !lineup.fits_own_brackets() || !lineup.contains_all(self.locked) {
+            return;
+        }
+        let score: f32 = self.scorer.lineup_score(&lineup);
+        if score <= 0.0 {
+            return;
+        }
+        self.top.push(score, lineup);
+    }
+}
+
+/// Chosen players are QB, DST, TEs then RBs and WRs. With three RBs one goes to
+/// the flex, picking a pair for RB1/RB2 that aren't playing each other.
+fn arrange_lineup(chosen: &[LitePlayer]) -> LineupBuilder {
+    let of_pos =
+        |pos: Pos| -> Vec<&LitePlayer> { chosen.iter().filter(|p| p.pos == pos).collect() };
+    let tes: Vec<&LitePlayer> = of_pos(Pos::Te);
+    let rbs: Vec<&LitePlayer> = of_pos(Pos::Rb);
+    let wrs: Vec<&LitePlayer> = of_pos(Pos::Wr);
+    let lineup: LineupBuilder = LineupBuilder::new()
+        .set_pos(&chosen[0], Slot::None)
+        .set_pos(&chosen[1], Slot::None)
+        .set_pos(tes[0], Slot::None);
+    let (rb1, rb2, flex) = if rbs.len() == 3 {
+        [
+            (rbs[0], rbs[1], rbs[2]),
+            (rbs[0], rbs[2], rbs[1]),
+            (rbs[1], rbs[2], rbs[0]),
+        ]
+        .into_iter()
+        .find(|(rb1, rb2, _)| rb1.opp != rb2.team)
+        .unwrap_or((rbs[0], rbs[1], rbs[2]))
+    } else if wrs.len() == 4 {
+        (rbs[0], rbs[1], wrs[3])
+    } else {
+        (rbs[0], rbs[1], tes[1])
+    };
+    lineup
+        .set_pos(rb1, Slot::First)
+        .set_pos(rb2, Slot::Second)
+        .set_pos(wrs[0], Slot::First)
+        .set_pos(wrs[1], Slot::Second)
+        .set_pos(wrs[2], Slot::Third)
+        .set_pos(flex, Slot::Flex)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimizer::tests::{test_config, test_pool, test_scorer};
+    use crate::optimizer::{unpruned_best, BOUND_SLACK};
+    use crate::site::Site;
+    use itertools::Itertools;
+
+    fn brute_force_best(players: &[LitePlayer], config: &SlateConfig) -> Option<f32> {
+        let best: f32 = unpruned_best(players, config, &[], &Arc::new(test_scorer()));
+        (best > 0.0).then_some(best)
+    }
+
+    #[test]
+    fn test_matches_brute_force() {
+        let players: Vec<LitePlayer> = test_pool();
+        let config: SlateConfig = test_config();
+        let lineups: Vec<LineupBuilder> = solve_exact(&players, &config, &[], &test_scorer());
+        assert!(!lineups.is_empty());
+        let best: f32 = brute_force_best(&players, &config).unwrap();
+        assert!((lineups[0].score() - best).abs() < BOUND_SLACK);
+    }
+
+    #[test]
+    fn test_sorted_unique_and_valid() {
+        let players: Vec<LitePlayer> = test_pool();
+        let config: SlateConfig = test_config();
+        let lineups: Vec<LineupBuilder> = solve_exact(&players, &config, &[], &test_scorer());
+        assert!(lineups.len() <= config.exact_top_n);
+        for pair in lineups.windows(2) {
+            assert!(pair[0].score() >= pair[1].score());
+        }
+        let sets: Vec<Vec<i16>> = lineups
+            .iter()
+            .map(|l| l.array_of_players().iter().map(|p| p.id).sorted().collect())
+            .collect();
+        assert_eq!(sets.iter().unique().count(), sets.len());
+        for lineup in &lineups {
+            assert!(
+                lineup.salary_used < config.salary_cap() && lineup.salary_used > config.min_sal()
+            );
+            assert!(lineup.fits_own_brackets());
+        }
+    }
+
+    #[test]
+    fn test_te_flex() {
+        let players: Vec<LitePlayer> = test_pool();
+        let config: SlateConfig = SlateConfig {
+            site: Site::DraftKings,
+            exact_top_n: 50,
+            ..test_config()
+        };
+        let lineups: Vec<LineupBuilder> = solve_exact(&players, &config, &[], &test_scorer());
+        let best: f32 = brute_force_best(&players, &config).unwrap();
+        assert!((lineups[0].score() - best).abs() < BOUND_SLACK);
+        let fanduel: Vec<LineupBuilder> = solve_exact(
+            &players,
+            &SlateConfig {
+                site: Site::FanDuel,
+                ..config.clone()
+            },
+            &[],
+            &test_scorer(),
+        );
+        assert!(fanduel.iter().all(|l| l.flex.unwrap().pos != Pos::Te));
+        assert!(lineups[0].score() >= fanduel[0].score());
+    }
+
+    #[test]
+    fn test_locked_player() {
+        let players: Vec<LitePlayer> = test_pool();
+        let config: SlateConfig = test_config();
+        let unlocked: Vec<LineupBuilder> = solve_exact(&players, &config, &[], &test_scorer());
+        let wr: i16 = players
+            .iter()
+            .filter(|p| p.pos == Pos::Wr)
+            .find(|p| !unlocked[0].contains_all(&[p.id]))
+            .unwrap()
+            .id;
+        let lineups: Vec<LineupBuilder> = solve_exact(&players, &config, &[wr], &test_scorer());
+        assert!(lineups.iter().all(|l| l.contains_all(&[wr])));
+    }
+}
@@ -0,0 +1,179 @@
+use rusqlite::{Connection, OptionalExtension};
+
+use crate::config::SlateConfig;
+use crate::lineup::explain_player;
+use crate::player::*;
+use crate::scoring::{scoring_model, Breakdown};
+
+/// A player's score on the slate and what went into it
+#[derive(Debug, Clone)]
+pub struct PlayerExplanation {
+    pub id: i16,
+    pub name: String,
+    pub pos: Pos,
+    pub breakdown: Breakdown,
+}
+
+/// Breakdowns for one player or a lineup's worth, with the stack bonus and
+/// the lineup rules `Lineup::score` applies when there's more than one
+#[derive(Debug, Clone)]
+pub struct Explanation {
+    pub players: Vec<PlayerExplanation>,
+    /// (QB id, pass catcher id, bonus)
+    pub stack: Option<(i16, i16, Breakdown)>,
+    pub rules: Vec<String>,
+    pub total: f32,
+}
+
+/// Explains the players' scores on the configured slate. WRs fill the
+/// lineup's WR slots in the order given, so list the flex WR last.
+pub fn explain(
+    ids: &[i16],
+    any_flex: bool,
+    config: &SlateConfig,
+    conn: &Connection,
+) -> Result<Explanation, String> {
+    let mut projs: Vec<Proj> = Vec::new();
+    let mut players: Vec<PlayerExplanation> = Vec::new();
+    for id in ids {
+        let pos: Pos = slate_pos(*id, config, conn)?;
+        let proj: Proj = query_proj_helper(*id, &pos, config.week, config.season, conn);
+        players.push(PlayerExplanation {
+            id: *id,
+            name: get_player_name(*id, conn),
+            pos,
+            breakdown: explain_player(&proj, any_flex),
+        });
+        projs.push(proj);
+    }
+    let mut total: f32 = players.iter().map(|p| p.breakdown.score).sum();
+    let mut stack: Option<(i16, i16, Breakdown)> = None;
+    let mut rules: Vec<String> = Vec::new();
+    if players.len() > 1 {
+        let qb: Option<&QbProj> = projs.iter().find_map(|p| match p {
+            Proj::QbProj(qb) => Some(qb),
+            _ => None,
+        });
+        let wrs: Vec<&RecProj> = projs
+            .iter()
+            .filter_map(|p| match p {
+                Proj::RecProj(rec) if rec.pos == Pos::Wr => Some(rec),
+                _ => None,
+            })
+            .take(3)
+            .collect();
+        if let Some(qb) = qb {
+            stack = scoring_model()
+                .stack
+                .explain(&wrs, qb)
+                .map(|(wr_id, breakdown)| (qb.id, wr_id, breakdown));
+            let def: Option<&DefProj> = projs.iter().find_map(|p| match p {
+                Proj::DefProj(def) => Some(def),
+                _ => None,
+            });
+            if let Some(def) = def.filter(|def| def.team == qb.opp) {
+                rules.push(format!(
+                    "QB faces the {} defense: zeroed",
+                    def.team.to_str()
+                ));
+            }
+        }
+        total += stack.as_ref().map_or(0.0, |(_, _, b)| b.score);
+        let rbs: Vec<&RbProj> = projs
+            .iter()
+            .filter_map(|p| match p {
+                Proj::RbProj(rb) => Some(rb),
+                _ => None,
+            })
+            .take(2)
+            .collect();
+        if rbs.len() == 2 && rbs[0].opp == rbs[1].team {
+            rules.push("RBs face each other: zeroed".to_string());
+        }
+        if !rules.is_empty() {
+            total = 0.0;
+        }
+    }
+    Ok(Explanation {
+        players,
+        stack,
+        rules,
+        total,
+    })
+}
+
+/// The player's position on the slate, an error when they aren't on it
+fn slate_pos(id: i16, config: &SlateConfig, conn: &Connection) -> Result<Pos, String> {
+    let pos: Option<String> = conn
+        .query_row(
+            "SELECT pos FROM ownership WHERE id = ?1 AND season = ?2 AND week = ?3 AND day = ?4",
+            (id, config.season, config.week, config.day.to_str()),
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    let pos: String = pos.ok_or(format!(
+        "Player {} isn't on the {} week {} {} slate",
+        id,
+        config.season,
+        config.week,
+        config.day.to_str()
+    ))?;
+    Pos::from_string(pos.clone()).map_err(|_| format!("Not a position: {}", pos))
+}
+
+impl Explanation {
+    pub fn report(&self) -> String {
+        let mut report: String = String::new();
+        for player in &self.players {
+            report.push_str(&format!(
+                "{} {} ({}) Score: {}\n",
+                player.pos.to_str().unwrap(),
+                player.name,
+                player.id,
+                player.breakdown.score
+            ));
+            report.push_str(&breakdown_report(&player.breakdown));
+        }
+        if let Some((qb_id, wr_id, breakdown)) = &self.stack {
+            report.push_str(&format!(
+                "Stack {} with {} Score: {}\n",
+                self.name(*qb_id),
+                self.name(*wr_id),
+                breakdown.score
+            ));
+            report.push_str(&breakdown_report(breakdown));
+        }
+        for rule in &self.rules {
+            report.push_str(&format!("Rule: {}\n", rule));
+        }
+        if self.players.len() > 1 {
+            report.push_str(&format!("Total: {}\n", self.total));
+        }
+        report
+    }
+
+    fn name(&self, id: i16) -> &str {
+        self.players
+            .iter()
+            .find(|p| p.id == id)
+            .map_or("", |p| p.name.as_str())
+    }
+}
+
+fn breakdown_report(breakdown: &Breakdown) -> String {
+    let mut report: String = format!(
+        "    {:<24}{:>10}{:>12}{:>9}{:>14}\n",
+        "feature", "raw", "normalized", "weight", "contribution"
+    );
+    for feature in &breakdown.features {
+        report.push_str(&format!(
+            "    {:<24}{:>10.2}{:>12.3}{:>9.3}{:>14.4}\n",
+            feature.name, feature.raw, feature.normalized, feature.weight, feature.contribution
+        ));
+    }
+    for rule in &breakdown.rules {
+        report.push_str(&format!("    Rule: {}\n", rule));
+    }
+    report
+}
@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use crate::lineup::{IslandLineup, Lineup};
+use crate::site::Site;
+
+/// Classic lineups in the site's bulk upload layout
+pub fn write_classic_csv(
+    path: &str,
+    site: Site,
+    lineups: &[Lineup],
+    site_ids: &HashMap<i16, String>,
+) -> Result<(), Box<dyn Error>> {
+    let rows: Vec<Vec<i16>> = lineups.iter().map(|l| l.get_id_array().to_vec()).collect();
+    write_bulk_csv(path, &site.roster_slots(), &rows, site_ids)
+}
+
+/// Single game lineups in the site's bulk upload layout, captain first
+pub fn write_island_csv(
+    path: &str,
+    site: Site,
+    lineups: &[IslandLineup],
+    site_ids: &HashMap<i16, String>,
+) -> Result<(), Box<dyn Error>> {
+    let rows: Vec<Vec<i16>> = lineups
+        .iter()
+        .map(|l| l.get_as_arr().iter().map(|p| p.get_id()).collect())
+        .collect();
+    write_bulk_csv(path, &site.island_format().roster_slots(), &rows, site_ids)
+}
+
+/// One row per lineup of site player ids under the slot headers. Fails before
+/// writing anything if a player has no site id since the upload would be rejected.
+pub fn write_bulk_csv(
+    path: &str,
+    header: &[&str],
+    rows: &[Vec<i16>],
+    site_ids: &HashMap<i16, String>,
+) -> Result<(), Box<dyn Error>> {
+    let mut missing: Vec<i16> = rows
+        .iter()
+        .flatten()
+        .filter(|id| !site_ids.contains_key(id))
+        .cloned()
+        .collect();
+    missing.sort();
+    missing.dedup();
+    if !missing.is_empty() {
+        return Err(format!("No site id loaded for players {:?}", missing).into());
+    }
+    if let Some(dir) = Path::new(path).parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut writer: csv::Writer<fs::File> = csv::Writer::from_path(path)?;
+    writer.write_record(header)?;
+    for row in rows {
+        writer.write_record(row.iter().map(|id| &site_ids[id]))?;
+    }
+    writer.flush()?;
+    println!("Writing {} lineups to {}", rows.len(), path);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_bulk_csv() {
+        let path: String = std::env::temp_dir()
+            .join("dfstimizer-export-test.csv")
+            .to_string_lossy()
+            .to_string();
+        let site_ids: HashMap<i16, String> = HashMap::from([
+            (1, "91142-55010".to_string()),
+            (2, "91142-61234".to_string()),
+        ]);
+        let header: Vec<&str> = vec!["CPT", "FLEX"];
+        write_bulk_csv(&path, &header, &[vec![1, 2], vec![2, 1]], &site_ids).unwrap();
+        let contents: String = fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            contents,
+            "CPT,FLEX\n91142-55010,91142-61234\n91142-61234,91142-55010\n"
+        );
+        assert!(write_bulk_csv(&path, &header, &[vec![1, 3]], &site_ids).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
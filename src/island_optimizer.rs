@@ -1,3 +1,4 @@
+use std::error::Error;
 use std::sync::Arc;
 
 use futures::channel::mpsc;
@@ -7,20 +8,19 @@ use futures::future::join_all;
 use futures::StreamExt;
 use rusqlite::Connection;
 
+use crate::config::SlateConfig;
+use crate::get_lock_exclude_ids;
 use crate::get_slate;
 use crate::lineup::*;
 use crate::player::*;
+use crate::scorer::Scorer;
+use crate::site::IslandFormat;
 use crate::DATABASE_FILE;
-use crate::GAME_DAY;
-use crate::MIN_SAL;
-use crate::SALARY_CAP;
-use crate::SEASON;
-use crate::WEEK;
 use itertools::Itertools;
 // use std::sync::Rc;
 
-fn get_mvp_ids(players: Vec<LitePlayer>) -> Vec<Arc<i16>> {
-    let mvp_pos: &[Pos; 1] = &[Pos::Qb];
+fn get_mvp_ids(players: Vec<LitePlayer>, format: IslandFormat) -> Vec<Arc<i16>> {
+    let mvp_pos: &[Pos] = format.captain_positions();
     players
         .into_iter()
         .filter(|p| mvp_pos.contains(&p.pos))
@@ -28,29 +28,43 @@ fn get_mvp_ids(players: Vec<LitePlayer>) -> Vec<Arc<i16>> {
         .collect::<Vec<Arc<i16>>>()
 }
 
-pub fn build_island_lineups(week: i8, season: i16) -> Vec<IslandLineup> {
+/// FanDuel MVP or DraftKings Showdown lineups depending on the config's site
+pub fn build_island_lineups(
+    config: &SlateConfig,
+    scorer: &Arc<dyn Scorer>,
+) -> Result<Vec<IslandLineup>, Box<dyn Error>> {
+    let config: &SlateConfig = &config.island();
+    let format: IslandFormat = config.site.island_format();
     let pool: ThreadPool = ThreadPool::new().unwrap();
     let mut finished_lineups: Vec<IslandLineup> = Vec::new();
-    let conn: Connection = Connection::open(DATABASE_FILE).unwrap();
-    let players: Vec<LitePlayer> = get_slate(week, season, &GAME_DAY, &conn);
-    let ids: Vec<Arc<i16>> = get_mvp_ids(players);
+    let conn: Connection = Connection::open(DATABASE_FILE)?;
+    let (locked, _) = get_lock_exclude_ids(config, &conn)?;
+    let players: Vec<LitePlayer> = get_slate(config, &conn)?;
+    let ids: Vec<Arc<i16>> = get_mvp_ids(players.clone(), format);
     let mut futures: Vec<_> = Vec::new();
     for id in ids {
         let (tx, rx) = mpsc::unbounded::<IslandLineup>();
+        let config: SlateConfig = config.clone();
+        let locked: Vec<i16> = locked.clone();
+        let scorer: Arc<dyn Scorer> = scorer.clone();
+        let thread_players: Vec<LitePlayer> = players.clone();
         let future = async {
             let fut_tx_result = async move {
-                let conn: Connection = Connection::open(DATABASE_FILE).unwrap();
-                let mut mvp_lineup: IslandLB = IslandLB::new();
-                let thread_players: Vec<LitePlayer> =
-                    get_slate(week, season, &GAME_DAY, &conn);
+                let mut mvp_lineup: IslandLB = IslandLB::new(format);
                 for player in &thread_players {
                     if player.id == *id {
                         mvp_lineup = mvp_lineup.set_slot(player, Slot::Mvp);
                     }
                 }
-                build_and_score_combos(&mvp_lineup, &thread_players)
-                    .into_iter()
-                    .for_each(|l| tx.unbounded_send(l).expect("Failed to send result"));
+                build_and_score_combos(
+                    &mvp_lineup,
+                    &thread_players,
+                    &config,
+                    &locked,
+                    scorer.as_ref(),
+                )
+                .into_iter()
+                .for_each(|l| tx.unbounded_send(l).expect("Failed to send result"));
             };
 
             pool.spawn_ok(fut_tx_result);
@@ -66,10 +80,16 @@ pub fn build_island_lineups(week: i8, season: i16) -> Vec<IslandLineup> {
         finished_lineups.extend(future);
     }
     finished_lineups.sort_by(|a, b: &IslandLineup| b.score.partial_cmp(&a.score).unwrap());
-    finished_lineups
+    Ok(finished_lineups)
 }
 
-fn build_and_score_combos(mvp_lineup: &IslandLB, players: &Vec<LitePlayer>) -> Vec<IslandLineup> {
+fn build_and_score_combos(
+    mvp_lineup: &IslandLB,
+    players: &Vec<LitePlayer>,
+    config: &SlateConfig,
+    locked: &[i16],
+    scorer: &dyn Scorer,
+) -> Vec<IslandLineup> {
     let amount: usize = 20;
     let conn: Connection = Connection::open(DATABASE_FILE).expect("Couldn't Open DB File");
     let mut best_lineups: Vec<IslandLineup> = Vec::new();
@@ -78,18 +98,21 @@ fn build_and_score_combos(mvp_lineup: &IslandLB, players: &Vec<LitePlayer>) -> V
     for combo in players
         .iter()
         .filter(|p| p.id != mvp_lineup.mvp.as_ref().unwrap().id)
-        .combinations(4)
+        .combinations(mvp_lineup.format.flex_count())
     {
-        let island_lb: IslandLB = mvp_lineup
-            .clone()
-            .set_slot(combo[0], Slot::First)
-            .set_slot(combo[1], Slot::Second)
-            .set_slot(combo[2], Slot::Third)
-            .set_slot(combo[3], Slot::Fourth);
-        if island_lb.salary_used > SALARY_CAP || island_lb.salary_used < MIN_SAL {
+        let island_lb: IslandLB = combo
+            .iter()
+            .enumerate()
+            .fold(mvp_lineup.clone(), |lineup, (i, player)| {
+                lineup.set_slot(player, Slot::int_to_slot(i as i8 + 1))
+            });
+        if island_lb.salary_used > config.salary_cap() || island_lb.salary_used < config.min_sal() {
             continue;
         }
-        let new_lineup: IslandLineup = island_lb.build(WEEK, SEASON, &conn);
+        if !island_lb.contains_all(locked) {
+            continue;
+        }
+        let new_lineup: IslandLineup = island_lb.build(config.week, config.season, &conn, scorer);
         let score: f32 = new_lineup.score;
         if best_lineups.len() == amount && sorted == false {
             best_lineups.sort_by(|a, b: &IslandLineup| b.score.partial_cmp(&a.score).unwrap());
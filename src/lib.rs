@@ -1,102 +1,50 @@
 use std::{cmp::min, sync::RwLock};
 
+use config::SlateConfig;
 use itertools::Itertools;
 use lazy_static::lazy_static;
 use lineup::{dst_score, qb_score, rb_score, score_kicker, te_score, wr_stud_score, LineupBuilder};
 use num_bigint::{BigUint, ToBigUint};
 use rusqlite::{CachedStatement, Connection};
+use serde::{Deserialize, Serialize};
 
 use crate::player::*;
+use crate::site::Site;
 
+pub mod checkpoint;
+pub mod config;
 pub mod data_loader;
+pub mod exact_optimizer;
+pub mod explain;
+pub mod export;
 pub mod island_optimizer;
 pub mod lineup;
+pub mod normalize;
 pub mod optimizer;
 pub mod player;
+pub mod portfolio;
+pub mod scorer;
+pub mod scoring;
+pub mod site;
 pub mod tables;
+pub mod tuning;
+pub mod weather;
 
 pub const DATABASE_FILE: &str = "./dfs_nfl.db3";
-pub const SEASON: i16 = 2023;
-pub const WEEK: i8 = 4;
-pub const GAME_DAY: Day = Day::Sun;
 
 pub const OWNERSHIP_CUTOFF_PER: f32 = 0.10;
 
 pub const FILTER_TOP_QB: i8 = 0;
 pub const FILTER_TOP_RB: i8 = 0;
 
-pub const SALARY_CAP: i32 = 50000;
-pub const MIN_SAL: i32 = 49500;
-
-// pub const WR_COUNT: i8 = 40;
-// pub const QB_COUNT: i8 = 12;
-// pub const TE_COUNT: i8 = 14;
-// pub const RB_COUNT: i8 = 25;
-// pub const D_COUNT: i8 = 14;
-pub const WR_COUNT: i8 = 25;
-pub const QB_COUNT: i8 = 12;
-pub const TE_COUNT: i8 = 12;
-pub const RB_COUNT: i8 = 12;
-pub const D_COUNT: i8 = 12;
-
+// Season, week, day, salary and pool sizes live in config::SlateConfig
 lazy_static! {
+    pub static ref SLATE_CONFIG: RwLock<SlateConfig> = RwLock::new(SlateConfig::default());
     pub static ref SLATE: RwLock<Vec<LitePlayer>> = RwLock::new(Vec::new());
-    // QB Stats
-    pub static ref QB_AVG_RUSH_YDS: (f32, f32) = get_max_min(SEASON, WEEK, "avg_rush_yds", Pos::Qb);
-    pub static ref QB_AVG_RZ_OP: (f32, f32) =  get_max_min(SEASON, WEEK, "red_zone_op_pg", Pos::Qb);
-    pub static ref QB_WR_PASS_PER: (f32, f32) = get_max_min(SEASON, WEEK, "pass_to_wr_per", Pos::Qb);
-    pub static ref QB_PTS_PLUS_MINUS: (f32, f32) = get_max_min(SEASON, WEEK , "pts_plus_minus_proj", Pos::Qb);
-    pub static ref QB_CIELING: (f32, f32) = get_max_min(SEASON, WEEK, "cieling_proj", Pos::Qb);
-    pub static ref QB_OPP_DEF: (f32, f32) = get_def_max_min(&Pos::Qb);
-    pub static ref QB_AVG_TD: (f32, f32) = get_max_min(SEASON, WEEK, "avg_pass_tds", Pos::Qb);
-    pub static ref QB_INVERSE_SAL: (f32, f32) = get_inverse_max_min(SEASON, WEEK, "salary", &Pos::Qb);
-    pub static ref QB_PTS_SAL: (f32, f32) = get_max_min(SEASON, WEEK, "pts_sal_proj", Pos::Qb);
-
-    // RB Stats
-    pub static ref RB_ATTS: (f32, f32) = get_max_min(SEASON, WEEK, "avg_atts", Pos::Rb);
-    pub static ref RB_AVG_TD: (f32, f32) = get_max_min(SEASON, WEEK, "avg_td", Pos::Rb);
-    pub static ref RB_AVG_REC_TGTS: (f32, f32) = get_max_min(SEASON, WEEK, "avg_rec_tgts", Pos::Rb);
-    pub static ref RB_CEILING: (f32, f32) = get_max_min(SEASON, WEEK, "cieling_proj", Pos::Rb);
-    pub static ref RB_OPP_DEF: (f32, f32) = get_def_max_min(&Pos::Rb);
-    pub static ref RB_INVERSE_SAL: (f32, f32) = get_inverse_max_min(SEASON, WEEK, "salary", &Pos::Rb);
-    pub static ref RB_PTS_SAL: (f32, f32) = get_max_min(SEASON, WEEK, "pts_sal_proj", Pos::Rb);
-
-    // WR Stats
-    pub static ref WR_TGT_SHARE: (f32, f32) = get_max_min(SEASON, WEEK, "rec_tgt_share", Pos::Wr);
-    pub static ref WR_RED_ZONE: (f32, f32) = get_max_min(SEASON, WEEK, "red_zone_op_pg", Pos::Wr);
-    pub static ref WR_MONTH_UPSIDE: (f32, f32) = get_max_min(SEASON, WEEK, "month_upside", Pos::Wr);
-    pub static ref WR_CIELING: (f32, f32) = get_max_min(SEASON, WEEK, "cieling_proj", Pos::Wr);
-    pub static ref WR_OPP_DEF: (f32, f32) = get_def_max_min(&Pos::Wr);
-    pub static ref WR_AVG_TD: (f32, f32) = get_max_min(SEASON, WEEK, "avg_td", Pos::Wr);
-    pub static ref WR_PTS_SAL: (f32, f32) = get_max_min(SEASON, WEEK, "pts_sal_proj", Pos::Wr);
-    // pub static ref WR_MEDIAN_SAL: (f32, f32) = get_field_median(SEASON, WEEK, field, table, limit)
-
-    // TE Stats
-    pub static ref TE_REC_TGT: (f32, f32) = get_max_min(SEASON, WEEK, "rec_tgt_share", Pos::Te);
-    pub static ref TE_RED_ZONE: (f32, f32) = get_max_min(SEASON, WEEK, "red_zone_op_pg", Pos::Te);
-    pub static ref TE_OPP_DEF: (f32, f32) = get_def_max_min(&Pos::Te);
-    pub static ref TE_CIELING: (f32, f32) = get_max_min(SEASON, WEEK, "cieling_proj", Pos::Te);
-    pub static ref TE_AVG_TD: (f32, f32)  = get_max_min(SEASON, WEEK, "avg_td", Pos::Te);
-    pub static ref TE_UPSIDE: (f32, f32)  = get_max_min(SEASON, WEEK, "month_upside", Pos::Te);
-    pub static ref TE_INVERSE_SAL: (f32, f32) = get_inverse_max_min(SEASON, WEEK, "salary", &Pos::Te);
-    pub static ref TE_PTS_SAL: (f32, f32) = get_max_min(SEASON, WEEK, "pts_sal_proj", Pos::Te);
-
-    // Any Flex
-    pub static ref ALL_PTS_MAX_MIN: (f32, f32) = get_max_min_all(SEASON, WEEK, "pts_proj");
-    pub static ref ALL_FLOOR_MAX_MIN: (f32, f32) = get_max_min_all(SEASON, WEEK, "floor_proj");
-    pub static ref ALL_CIELING_MAX_MIN: (f32, f32) = get_max_min_all(SEASON, WEEK, "cieling_proj");
-    pub static ref ALL_PTS_SAL_MAX_MIN: (f32, f32) = get_max_min_all(SEASON, WEEK, "pts_sal_proj");
-    pub static ref ALL_PTS_PLUS_MINS_MAX_MIN: (f32, f32) = get_max_min_all(SEASON, WEEK, "pts_plus_minus_proj");
-
-    // DST Stats
-    pub static ref DST_RATING: (f32, f32) = get_max_min(SEASON, WEEK, "rating", Pos::D);
-    pub static ref DST_VEGAS_OPP_TOTAL: (f32, f32) = get_inverse_max_min(SEASON, WEEK, "vegas_opp_total", &Pos::D);
-    pub static ref DST_PTS_PLUS_MINUS: (f32, f32) = get_max_min(SEASON, WEEK, "pts_plus_minus_proj", Pos::D);
-
-    pub static ref ALL_TEAM_TOTAL: (f32, f32) = get_max_min_all(SEASON, WEEK, "vegas_team_total");
-    pub static ref ALL_VEGAS_TOTAL: (f32, f32) = get_max_min_all(SEASON, WEEK, "vegas_total");
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Day {
     Mon,
     Thu,
@@ -119,10 +67,19 @@ impl Day {
             &_ => panic!("Not a day when games are playedll"),
         }
     }
+
+    pub fn try_from_str(day: &str) -> Option<Day> {
+        match day.to_lowercase().as_str() {
+            "mon" => Some(Day::Mon),
+            "thu" => Some(Day::Thu),
+            "sun" => Some(Day::Sun),
+            _ => None,
+        }
+    }
 }
 
 /// Returns tuple of (max: f32,min: f32)
-fn get_max_min(season: i16, week: i8, field: &str, pos: Pos) -> (f32, f32) {
+pub(crate) fn get_max_min(config: &SlateConfig, field: &str, pos: Pos) -> (f32, f32) {
     let conn: Connection = Connection::open(DATABASE_FILE).unwrap();
     let mut max_statement: rusqlite::CachedStatement<'_> = conn
         .prepare_cached(
@@ -145,12 +102,9 @@ fn get_max_min(season: i16, week: i8, field: &str, pos: Pos) -> (f32, f32) {
         )
         .expect("Couldn't prepare statement..");
 
-    let max: f32 = max_statement
-        .query_row((week, season, GAME_DAY.to_str()), |r| r.get(0))
-        .unwrap();
-    let mut min: f32 = min_statement
-        .query_row((week, season, GAME_DAY.to_str()), |r| r.get(0))
-        .unwrap();
+    let params = (config.week, config.season, config.day.to_str());
+    let max: f32 = max_statement.query_row(params, |r| r.get(0)).unwrap();
+    let mut min: f32 = min_statement.query_row(params, |r| r.get(0)).unwrap();
 
     if min <= 0.0 {
         min = min - 0.2;
@@ -160,17 +114,28 @@ fn get_max_min(season: i16, week: i8, field: &str, pos: Pos) -> (f32, f32) {
     (max, min)
 }
 
-fn get_def_max_min(pos: &Pos) -> (f32, f32) {
+/// Max over the same week `query_def_vs_pos` reads for the slate
+pub(crate) fn get_def_max_min(config: &SlateConfig, pos: &Pos) -> (f32, f32) {
     let conn: Connection = Connection::open(DATABASE_FILE).unwrap();
     let mut max_statement: rusqlite::CachedStatement<'_> = conn
-        .prepare_cached(format!("SELECT MAX(pts_given_pg) FROM {}", pos.get_def_table()).as_str())
+        .prepare_cached(
+            format!(
+                "SELECT MAX(pts_given_pg) FROM {table} WHERE season = ?1 AND week = (
+                    SELECT MAX(week) FROM {table} WHERE season = ?1 AND week <= ?2)",
+                table = pos.get_def_table()
+            )
+            .as_str(),
+        )
         .expect("Couldn't prepare statement..");
-    let max: f32 = max_statement.query_row((), |r| r.get(0)).unwrap();
-    (max, 0.0)
+    // No numbers by the week leaves no range, every defense scores the same
+    let max: Option<f32> = max_statement
+        .query_row((config.season, config.week), |r| r.get(0))
+        .unwrap();
+    (max.unwrap_or(0.0), 0.0)
 }
 
 /// Returns the inverse of the score, remove after cummulative
-fn get_inverse_max_min(season: i16, week: i8, field: &str, pos: &Pos) -> (f32, f32) {
+pub(crate) fn get_inverse_max_min(config: &SlateConfig, field: &str, pos: &Pos) -> (f32, f32) {
     let conn: Connection = Connection::open(DATABASE_FILE).unwrap();
     let mut max_statement: rusqlite::CachedStatement<'_> = conn
         .prepare_cached(
@@ -184,11 +149,55 @@ fn get_inverse_max_min(season: i16, week: i8, field: &str, pos: &Pos) -> (f32, f
         .expect("Couldn't prepare statement..");
 
     let max: f32 = max_statement
-        .query_row((week, season, &GAME_DAY.to_str()), |r| r.get(0))
+        .query_row((config.week, config.season, config.day.to_str()), |r| {
+            r.get(0)
+        })
         .unwrap();
     (0.0, -1.0 * max)
 }
 
+/// Every value of a column for the slate's players
+pub(crate) fn get_slate_values(config: &SlateConfig, field: &str, pos: &Pos) -> Vec<f32> {
+    let conn: Connection = Connection::open(DATABASE_FILE).unwrap();
+    let mut statement: rusqlite::CachedStatement<'_> = conn
+        .prepare_cached(
+            format!(
+                "SELECT {} FROM {} WHERE week = ?1 AND season = ?2 AND day = ?3",
+                field,
+                pos.get_proj_table()
+            )
+            .as_str(),
+        )
+        .expect("Couldn't prepare statement..");
+    statement
+        .query_map((config.week, config.season, config.day.to_str()), |r| {
+            r.get::<_, Option<f32>>(0)
+        })
+        .unwrap()
+        .filter_map(|v| v.unwrap())
+        .collect()
+}
+
+/// Points given up to the position, same week as `get_def_max_min`
+pub(crate) fn get_def_values(config: &SlateConfig, pos: &Pos) -> Vec<f32> {
+    let conn: Connection = Connection::open(DATABASE_FILE).unwrap();
+    let mut statement: rusqlite::CachedStatement<'_> = conn
+        .prepare_cached(
+            format!(
+                "SELECT pts_given_pg FROM {table} WHERE season = ?1 AND week = (
+                    SELECT MAX(week) FROM {table} WHERE season = ?1 AND week <= ?2)",
+                table = pos.get_def_table()
+            )
+            .as_str(),
+        )
+        .expect("Couldn't prepare statement..");
+    statement
+        .query_map((config.season, config.week), |r| r.get(0))
+        .unwrap()
+        .map(|v| v.unwrap())
+        .collect()
+}
+
 // Avoid the clone by passing a mutable reference
 fn get_median(vec: &mut Vec<f32>) -> f32 {
     if vec.is_empty() {
@@ -227,12 +236,12 @@ fn get_field_median(season: i16, week: i8, field: &str, table: &str, limit: i8)
     get_median(&mut values)
 }
 
-fn get_max_min_all(season: i16, week: i8, field: &str) -> (f32, f32) {
+pub(crate) fn get_max_min_all(config: &SlateConfig, field: &str) -> (f32, f32) {
     let tables: [Pos; 5] = [Pos::D, Pos::Qb, Pos::Rb, Pos::Te, Pos::Wr];
     let mut max: f32 = 0.0;
     let mut min: f32 = 200.0;
     for table in tables {
-        let max_min: (f32, f32) = get_max_min(season, week, field, table);
+        let max_min: (f32, f32) = get_max_min(config, field, table);
         if max_min.0 > max {
             max = max_min.0
         }
@@ -245,26 +254,45 @@ fn get_max_min_all(season: i16, week: i8, field: &str) -> (f32, f32) {
 
 // These Ids should be cached in an option.
 // TODO Cache slate after calculation
-pub fn get_slate(week: i8, season: i16, day: &Day, conn: &Connection) -> Vec<LitePlayer> {
+pub fn get_slate(config: &SlateConfig, conn: &Connection) -> Result<Vec<LitePlayer>, String> {
     if SLATE.read().unwrap().len() > 0 {
-        return SLATE.read().unwrap().clone();
+        return Ok(SLATE.read().unwrap().clone());
     }
+    let ids: Vec<i16> =
+        get_active_players(config.season, config.week, &config.day, config.site, conn);
+    if ids.len() == 0 {
+        return Err(format!(
+            "No players found for {} week {} {}",
+            config.season,
+            config.week,
+            config.day.to_str()
+        ));
+    }
+    let (locked, excluded) = get_lock_exclude_ids(config, conn)?;
+    let active: Vec<LitePlayer> = get_players_by_ids(config.week, config.season, &ids);
     let mut players: Vec<LitePlayer> = Vec::new();
-    let top_qb: Vec<LitePlayer> =
-        get_top_players_by_pos(season, week, &Pos::Qb, QB_COUNT, day, conn);
-    let top_rb: Vec<LitePlayer> =
-        get_top_players_by_pos(season, week, &Pos::Rb, RB_COUNT, day, conn);
-    let top_wr: Vec<LitePlayer> =
-        get_top_players_by_pos(season, week, &Pos::Wr, WR_COUNT, day, conn);
-    let top_te: Vec<LitePlayer> =
-        get_top_players_by_pos(season, week, &Pos::Te, TE_COUNT, day, conn);
-    let top_d: Vec<LitePlayer> = get_top_players_by_pos(season, week, &Pos::D, D_COUNT, day, conn);
-    let top_ids: [Vec<LitePlayer>; 5] = [top_qb, top_rb, top_d, top_te, top_wr];
-    for ids in top_ids {
-        players.extend(ids)
+    for pos in [Pos::Qb, Pos::Rb, Pos::D, Pos::Te, Pos::Wr, Pos::K] {
+        players.extend(get_top_players_by_pos(
+            config, &pos, &active, &locked, &excluded,
+        ))
     }
     SLATE.write().unwrap().extend(players.clone());
-    players
+    Ok(players)
+}
+
+/// Drops the cached slate and projections before working on another week
+pub fn clear_slate_caches() {
+    SLATE.write().unwrap().clear();
+    clear_proj_caches();
+}
+
+/// `LineupBuilder::build` reads projections from the caches, this fills them for
+/// players this process hasn't looked up yet, like the ones in merged shard files
+pub fn prime_proj_caches(week: i8, season: i16, ids: &[i16]) {
+    let conn: Connection = Connection::open(DATABASE_FILE).unwrap();
+    for id in ids {
+        get_player_by_id(week, *id, season, false, &conn);
+    }
 }
 
 pub fn get_players_by_ids(week: i8, season: i16, ids: &[i16]) -> Vec<LitePlayer> {
@@ -276,12 +304,26 @@ pub fn get_players_by_ids(week: i8, season: i16, ids: &[i16]) -> Vec<LitePlayer>
     players
 }
 
-pub fn get_active_players(season: i16, week: i8, day: &Day, conn: &Connection) -> Vec<i16> {
+/// Players with projections, limited to the site's salary file when one is loaded
+/// and leaving out anyone it lists as out
+pub fn get_active_players(
+    season: i16,
+    week: i8,
+    day: &Day,
+    site: Site,
+    conn: &Connection,
+) -> Vec<i16> {
     let mut query: CachedStatement<'_> = conn
-        .prepare_cached("SELECT id FROM ownership WHERE week = ?1 AND season = ?2 AND day = ?3")
+        .prepare_cached(
+            "SELECT id FROM ownership WHERE week = ?1 AND season = ?2 AND day = ?3
+            AND (NOT EXISTS (SELECT 1 FROM slate
+                    WHERE week = ?1 AND season = ?2 AND day = ?3 AND site = ?4)
+                OR id IN (SELECT id FROM slate WHERE week = ?1 AND season = ?2 AND day = ?3
+                    AND site = ?4 AND IFNULL(injury, '') NOT IN ('O', 'IR')))",
+        )
         .unwrap();
     let ids: Vec<i16> = query
-        .query_map((week, season, day.to_str()), |r| r.get(0))
+        .query_map((week, season, day.to_str(), site.to_str()), |r| r.get(0))
         .unwrap()
         .into_iter()
         .map(|p| p.unwrap())
@@ -289,26 +331,38 @@ pub fn get_active_players(season: i16, week: i8, day: &Day, conn: &Connection) -
     return ids;
 }
 
-/// Takes all players for week/day and filters using our scoring
+/// Resolved lock and exclude lists from the config
+pub fn get_lock_exclude_ids(
+    config: &SlateConfig,
+    conn: &Connection,
+) -> Result<(Vec<i16>, Vec<i16>), String> {
+    let locked: Vec<i16> = resolve_player_refs(&config.lock, conn)?;
+    let excluded: Vec<i16> = resolve_player_refs(&config.exclude, conn)?;
+    if let Some(id) = locked.iter().find(|id| excluded.contains(id)) {
+        return Err(format!("Player {} is both locked and excluded", id));
+    }
+    Ok((locked, excluded))
+}
+
+/// Takes the slate's active players at `pos` and filters using our scoring.
+/// Excluded players are dropped and locked players always make the cut.
 pub fn get_top_players_by_pos(
-    season: i16,
-    week: i8,
+    config: &SlateConfig,
     pos: &Pos,
-    count: i8,
-    day: &Day,
-    conn: &Connection,
+    active: &[LitePlayer],
+    locked: &[i16],
+    excluded: &[i16],
 ) -> Vec<LitePlayer> {
-    let ids: Vec<i16> = get_active_players(season, week, day, conn);
-    if ids.len() == 0 {
-        panic!("No players found for pos")
-    }
-    let mut players: Vec<LitePlayer> = get_players_by_ids(week, season, &ids)
-        .into_iter()
-        .filter(|p| &p.pos == pos)
+    let mut players: Vec<LitePlayer> = active
+        .iter()
+        .filter(|p| &p.pos == pos && !excluded.contains(&p.id))
+        .cloned()
         .collect_vec();
-    let take: usize = min(count as usize, players.len());
+    let take: usize = min(config.pool_size(pos) as usize, players.len());
     players.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap());
-    players[0..take].to_vec()
+    let mut top: Vec<LitePlayer> = players[0..take].to_vec();
+    top.extend(players[take..].iter().filter(|p| locked.contains(&p.id)));
+    top
 }
 
 pub fn mean(data: &[f32]) -> Option<f32> {
@@ -444,6 +498,11 @@ mod tests {
 
     #[test]
     fn test_max_min_all() {
-        println!("{:?}", get_max_min_all(2023, 1, "floor_proj"));
+        let config: SlateConfig = SlateConfig {
+            season: 2023,
+            week: 1,
+            ..SlateConfig::default()
+        };
+        println!("{:?}", get_max_min_all(&config, "floor_proj"));
     }
 }
@@ -1,19 +1,16 @@
 use std::cmp::max;
+use std::sync::Arc;
 use std::vec;
 
 use rand::Rng;
 use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
 
-use crate::{
-    player::*, return_if_field_exits, ALL_CIELING_MAX_MIN, ALL_FLOOR_MAX_MIN, ALL_PTS_MAX_MIN,
-    ALL_PTS_PLUS_MINS_MAX_MIN, ALL_PTS_SAL_MAX_MIN, ALL_TEAM_TOTAL, DATABASE_FILE,
-    DST_PTS_PLUS_MINUS, DST_VEGAS_OPP_TOTAL, QB_AVG_RUSH_YDS, QB_AVG_RZ_OP, QB_AVG_TD, QB_CIELING,
-    QB_INVERSE_SAL, QB_OPP_DEF, QB_PTS_PLUS_MINUS, QB_PTS_SAL, RB_ATTS, RB_AVG_REC_TGTS,
-    RB_CEILING, RB_INVERSE_SAL, RB_OPP_DEF, RB_PTS_SAL, SALARY_CAP, TE_AVG_TD, TE_CIELING,
-    TE_INVERSE_SAL, TE_OPP_DEF, TE_PTS_SAL, TE_UPSIDE, WR_AVG_TD, WR_CIELING, WR_MONTH_UPSIDE,
-    WR_OPP_DEF, WR_PTS_SAL, WR_RED_ZONE, WR_TGT_SHARE,
-};
-use crate::{RB_AVG_TD, TE_REC_TGT};
+use crate::normalize::min_max;
+use crate::scorer::Scorer;
+use crate::scoring::{scoring_model, Breakdown, ScoringModel};
+use crate::site::IslandFormat;
+use crate::{player::*, return_if_field_exits};
 
 // first name is min, next number is max
 pub const OWN_COUNT_RANGE_3: OwnBracket = OwnBracket {
@@ -55,6 +52,7 @@ pub enum Slot {
     Second,
     Third,
     Fourth,
+    Fifth,
     None,
     Flex,
 }
@@ -72,6 +70,7 @@ impl Slot {
             2 => Slot::Second,
             3 => Slot::Third,
             4 => Slot::Fourth,
+            5 => Slot::Fifth,
             _ => panic!("Not a slot"),
         }
     }
@@ -105,91 +104,27 @@ pub fn score_player(
     }
 }
 
-// TODO Maybe else if score on TD or Yds
-// TODO Scorning functions
 // TODO performance could precaclulate score for each player and store in cache..
+// Weights come from the installed scoring model, see scoring.rs
 pub fn rb_score(rbs: &[&RbProj], any_flex: bool, _: bool) -> f32 {
-    let mut score: f32 = 0.0;
-    rbs.iter().for_each(|rb| {
-        let mut inside_score: f32 = 0.0;
-        inside_score += get_normalized_score(rb.opp_def_pts_given, *RB_OPP_DEF) * 0.5;
-        inside_score += get_normalized_score(rb.avg_att, *RB_ATTS) * 0.5;
-        inside_score += get_normalized_score(rb.avg_rec_tgts, *RB_AVG_REC_TGTS) * 0.5;
-        inside_score += get_normalized_score(rb.avg_td, *RB_AVG_TD) * 1.0;
-        inside_score += get_normalized_score(rb.salary as f32 * -1.0, *RB_INVERSE_SAL) * 3.0;
-        if any_flex {
-            inside_score += 2.0;
-            inside_score += get_normalized_score(rb.cieling_proj, *ALL_CIELING_MAX_MIN) * 2.0;
-            // lower score
-        } else {
-            inside_score += get_normalized_score(rb.vegas_team_total, *ALL_TEAM_TOTAL) * 2.0;
-            // inside_score += get_normalized_score(rb.pts_sal_proj, *RB_PTS_SAL) * 1.0;
-            inside_score += get_normalized_score(rb.cieling_proj, *ALL_CIELING_MAX_MIN) * 3.0;
-        }
-        if rb.own_proj < 5.5 {
-            inside_score = 0.0
-        }
-
-        score += get_normalized_score(inside_score, (9.25, 0.0));
-    });
-
-    score
+    let model: Arc<ScoringModel> = scoring_model();
+    rbs.iter().map(|rb| model.rb.score(*rb, any_flex)).sum()
 }
 
 pub fn qb_score(qb: &QbProj, any_flex: bool) -> f32 {
-    let mut score: f32 = 0.0;
-    // score += get_normalized_score(qb.opp_def_pts_given, *QB_OPP_DEF) * 1.0;
-    score += get_normalized_score(qb.red_zone_op_pg, *QB_AVG_RZ_OP) * 1.00;
-    score += get_normalized_score(qb.avg_pass_tds, *QB_AVG_TD) * 1.00;
-    score += get_normalized_score(qb.avg_rush_yards, *QB_AVG_RUSH_YDS) * 0.5;
-    score += get_normalized_score(qb.salary as f32 * -1.0, *QB_INVERSE_SAL) * 1.2;
-    // rush yds
-    if any_flex {
-        // score += get_normalized_score(qb.cieling_proj, *ALL_CIELING_MAX_MIN) * 3.5;
-        score += 2.5
-    } else {
-        score += get_normalized_score(qb.vegas_team_total, *ALL_TEAM_TOTAL) * 2.0;
-        score += get_normalized_score(qb.cieling_proj, *QB_CIELING) * 2.0;
-        // score += get_normalized_score(qb.pts_sal_proj, *QB_PTS_SAL) * 1.0;
-    }
-    if qb.own_proj < 2.5 {
-        score = 0.0
-    }
-
-    let new_score: f32 = get_normalized_score(score, (8.25, 0.0));
-    new_score
+    scoring_model().qb.score(qb, any_flex)
 }
 
 // Most expensive scoring
 pub fn wr_stud_score(wr: &RecProj, any_flex: bool) -> f32 {
-    let mut score: f32 = 0.0;
-    score += get_normalized_score(wr.opp_def_pts_given, *WR_OPP_DEF) * 0.75;
-    score += get_normalized_score(wr.rec_tgt_share, *WR_TGT_SHARE) * 0.5;
-    score += get_normalized_score(wr.avg_td, *WR_AVG_TD) * 1.5;
-    score += get_normalized_score(wr.red_zone_op_pg, *WR_RED_ZONE) * 0.75;
-    if any_flex {
-        // score += get_normalized_score(wr.cieling_proj, *ALL_CIELING_MAX_MIN) * 2.5;
-        score += 2.5;
-    } else {
-        score += get_normalized_score(wr.vegas_team_total, *ALL_TEAM_TOTAL) * 2.5;
-        score += get_normalized_score(wr.pts_sal_proj, *WR_PTS_SAL) * 2.5;
-        score += get_normalized_score(wr.cieling_proj, *WR_CIELING) * 3.5;
-    }
-    score += get_normalized_score(wr.month_upside, *WR_MONTH_UPSIDE) * 0.5;
-    if wr.own_proj < 8.0 {
-        score += 0.05
-    }
-
-    if wr.own_proj < 3.5 {
-        score = 0.0
-    }
-    get_normalized_score(score, (11.35, 0.0)) + 0.05
+    scoring_model().wr.score(wr, any_flex)
 }
 
 fn flex_score(flex: &FlexProj) -> f32 {
     match flex.pos {
         Pos::Wr => return wr_stud_score(flex.rec_proj.as_ref().unwrap(), false),
         Pos::Rb => return rb_score(&[flex.rb_proj.as_ref().unwrap()], false, true),
+        Pos::Te => return te_score(flex.rec_proj.as_ref().unwrap(), false),
         _ => {
             panic!("Wrong Flex Pos..");
         }
@@ -198,151 +133,136 @@ fn flex_score(flex: &FlexProj) -> f32 {
 
 // Only included in AnyFlex
 pub fn score_kicker(proj: &KickProj) -> f32 {
-    let pts_score: f32 = get_normalized_score(proj.pts_plus_minus_proj, *ALL_PTS_MAX_MIN) * 1.0;
-    get_normalized_score(pts_score, (1.0, 0.0))
+    scoring_model().k.score(proj, true)
 }
 
 pub fn te_score(te: &RecProj, any_flex: bool) -> f32 {
-    let mut score: f32 = 0.0;
-    // score += get_normalized_score(te.opp_def_pts_given, *TE_OPP_DEF) * 0.25;
-    // score += get_normalized_score(te.rec_tgt_share, *TE_REC_TGT) * 1.5;
-    score += get_normalized_score(te.avg_td, *TE_AVG_TD) * 1.0;
-    score += get_normalized_score(te.month_upside, *TE_UPSIDE) * 0.50;
-    score += get_normalized_score(-1.0 * te.salary as f32, *TE_INVERSE_SAL) * 0.5;
-    if any_flex {
-        // score += get_normalized_score(te.pts_sal_proj, *ALL_PTS_MAX_MIN) * 2.0;
-        score += 2.0;
-    } else {
-        score += get_normalized_score(te.vegas_team_total, *ALL_TEAM_TOTAL) * 1.0;
-        score += get_normalized_score(te.pts_sal_proj, *TE_PTS_SAL) * 2.0;
-    }
-    if te.own_proj < 5.0 {
-        return 0.0;
-    }
-
-    let score = get_normalized_score(score, (8.0, 0.0));
-    score
+    scoring_model().te.score(te, any_flex)
 }
 
-// Points
 pub fn dst_score(def: &DefProj, any_flex: bool) -> f32 {
-    let mut score: f32 = 0.0;
-    if def.own_proj < 1.0 {
-        return 0.0;
-    }
-
-    score += get_normalized_score(def.vegas_opp_total * -1.0, *DST_VEGAS_OPP_TOTAL) * 1.0;
-    if any_flex {
-        score += get_normalized_score(def.pts_plus_minus_proj, *ALL_PTS_PLUS_MINS_MAX_MIN) * 1.0;
-    } else {
-        score += get_normalized_score(def.pts_plus_minus_proj, *DST_PTS_PLUS_MINUS) * 1.0;
-    }
-    get_normalized_score(score, (3.0, 0.0))
+    scoring_model().dst.score(def, any_flex)
 }
 
 pub fn score_stacking(wrs: &[&RecProj], qb: &QbProj) -> f32 {
-    let mut score: f32 = 0.0;
-    for wr in wrs {
-        if wr.team == qb.team {
-            let bonus = 0.3 + (get_normalized_score(wr.rec_tgt_share, *WR_TGT_SHARE) * 0.50);
-            if bonus > score {
-                score = bonus;
-            }
-        }
+    scoring_model().stack.score(wrs, qb)
+}
+
+/// What went into a player's score, kickers are always any flex like `score_kicker`
+pub fn explain_player(proj: &Proj, any_flex: bool) -> Breakdown {
+    let model: Arc<ScoringModel> = scoring_model();
+    match proj {
+        Proj::QbProj(qb) => model.qb.explain(qb, any_flex),
+        Proj::RbProj(rb) => model.rb.explain(rb, any_flex),
+        Proj::RecProj(rec) if rec.pos == Pos::Te => model.te.explain(rec, any_flex),
+        Proj::RecProj(rec) => model.wr.explain(rec, any_flex),
+        Proj::DefProj(def) => model.dst.explain(def, any_flex),
+        Proj::KickProj(k) => model.k.explain(k, true),
     }
-    return score;
 }
 
-/// Takes tuple of max: f32, min: f32
+/// Takes tuple of max: f32, min: f32, values outside it are clamped to 0-1
 pub fn get_normalized_score(value: f32, max_min: (f32, f32)) -> f32 {
-    if value > max_min.0 {
-        panic!("Value is greater than max: {}", value)
-    }
-    if value < max_min.1 {
-        panic!("Value is less than min: {}", value)
-    }
-    (value - max_min.1) / (max_min.0 - max_min.1)
+    min_max(value, max_min)
 }
 
+/// Single game lineup, `mvp` is the captain. `fifth` is only used by Showdown.
 #[derive(Clone)]
 pub struct IslandLB {
+    pub format: IslandFormat,
     pub mvp: Option<LitePlayer>,
     pub first: Option<LitePlayer>,
     pub second: Option<LitePlayer>,
     pub third: Option<LitePlayer>,
     pub fourth: Option<LitePlayer>,
+    pub fifth: Option<LitePlayer>,
     pub salary_used: i32,
 }
 
 impl IslandLB {
-    pub fn new() -> IslandLB {
+    pub fn new(format: IslandFormat) -> IslandLB {
         IslandLB {
+            format,
             mvp: None,
             first: None,
             second: None,
             third: None,
             fourth: None,
+            fifth: None,
             salary_used: 0,
         }
     }
 
     pub fn set_slot(mut self, lite_player: &LitePlayer, slot: Slot) -> IslandLB {
+        let mut salary: i32 = lite_player.salary as i32;
         match slot {
-            Slot::Mvp => self.mvp = Some(return_if_field_exits(self.mvp, lite_player)),
+            Slot::Mvp => {
+                self.mvp = Some(return_if_field_exits(self.mvp, lite_player));
+                salary = self.format.captain_salary(salary);
+            }
             Slot::First => self.first = Some(return_if_field_exits(self.first, lite_player)),
             Slot::Second => self.second = Some(return_if_field_exits(self.second, lite_player)),
             Slot::Third => self.third = Some(return_if_field_exits(self.third, lite_player)),
             Slot::Fourth => self.fourth = Some(return_if_field_exits(self.fourth, lite_player)),
+            Slot::Fifth if self.format == IslandFormat::Showdown => {
+                self.fifth = Some(return_if_field_exits(self.fifth, lite_player))
+            }
             _ => panic!("Not a valid Island Slot"),
         }
-        self.salary_used += lite_player.salary as i32;
+        self.salary_used += salary;
         self
     }
 
-    fn get_proj_score(proj: &Proj) -> f32 {
-        let score: f32 = match proj {
-            Proj::QbProj(qb_proj) => qb_score(qb_proj, true),
-            Proj::RecProj(rec_proj) => match rec_proj.pos {
-                Pos::Wr => wr_stud_score(rec_proj, true),
-                Pos::Te => te_score(rec_proj, true),
-                _ => panic!("Rec Proj had wrong POS."),
-            },
-            Proj::RbProj(rb_proj) => rb_score(&[&rb_proj], true, false),
-            Proj::DefProj(def_proj) => dst_score(def_proj, true),
-            Proj::KickProj(kick_proj) => score_kicker(kick_proj),
-        };
-        score
-    }
-
-    pub fn score(mvp_proj: &Proj, projs: &[&Proj; 4]) -> f32 {
+    pub fn score(mvp_proj: &Proj, projs: &[&Proj], scorer: &dyn Scorer) -> f32 {
         let mut total_score: f32 = 0.0;
-        let mut players: Vec<&Proj> = Vec::new();
-        players.push(mvp_proj);
-        players.extend(projs);
 
-        projs.into_iter().for_each(|p: &&Proj| {
-            total_score += Self::get_proj_score(p);
+        projs.iter().for_each(|p: &&Proj| {
+            total_score += scorer.player(p, true);
         });
-        total_score += Self::get_proj_score(mvp_proj) * 1.5;
+        total_score += scorer.player(mvp_proj, true) * IslandFormat::CAPTAIN_POINTS;
         // TODO score stacking
         total_score
     }
 
-    pub fn build(self, week: i8, season: i16, conn: &Connection) -> IslandLineup {
+    pub fn contains_all(&self, ids: &[i16]) -> bool {
+        let slots: [Option<LitePlayer>; 6] = [
+            self.mvp,
+            self.first,
+            self.second,
+            self.third,
+            self.fourth,
+            self.fifth,
+        ];
+        ids.iter()
+            .all(|id| slots.iter().flatten().any(|p| p.id == *id))
+    }
+
+    pub fn build(
+        self,
+        week: i8,
+        season: i16,
+        conn: &Connection,
+        scorer: &dyn Scorer,
+    ) -> IslandLineup {
         let mvp_proj: Proj = query_proj(self.mvp.as_ref(), week, season, conn);
         let first: Proj = query_proj(self.first.as_ref(), week, season, conn);
         let second: Proj = query_proj(self.second.as_ref(), week, season, conn);
         let third: Proj = query_proj(self.third.as_ref(), week, season, conn);
         let fourth: Proj = query_proj(self.fourth.as_ref(), week, season, conn);
+        let fifth: Option<Proj> = self.fifth.map(|p| query_proj(Some(&p), week, season, conn));
 
-        let score: f32 = Self::score(&mvp_proj, &[&first, &second, &third, &fourth]);
+        let mut flex: Vec<&Proj> = vec![&first, &second, &third, &fourth];
+        flex.extend(fifth.as_ref());
+        let score: f32 = Self::score(&mvp_proj, &flex, scorer);
 
         IslandLineup {
+            format: self.format,
             mvp: mvp_proj,
             first: first,
             second: second,
             third: third,
             fourth: fourth,
+            fifth,
             salary_used: self.salary_used,
             score: score,
         }
@@ -351,47 +271,47 @@ impl IslandLB {
 
 #[derive(Debug, Clone)]
 pub struct IslandLineup {
+    pub format: IslandFormat,
     pub mvp: Proj,
     pub first: Proj,
     pub second: Proj,
     pub third: Proj,
     pub fourth: Proj,
+    pub fifth: Option<Proj>,
     pub salary_used: i32,
     pub score: f32,
 }
 
 impl IslandLineup {
     pub fn lineup_str(&self, conn: &Connection) -> String {
-        format!(
-            "Sal: {}, Score: {}\nMVP: {}: {} {}\n{}: {} {}\n{}: {} {}\n{}: {} {}\n{}: {} {}\n\n",
+        let mut lineup: String = format!(
+            "Sal: {}, Score: {}\n{}: ",
             self.salary_used,
             self.score,
-            self.mvp.get_name(conn),
-            self.mvp.get_pos().to_str().expect(""),
-            self.mvp.get_own(),
-            self.first.get_name(conn),
-            self.first.get_pos().to_str().expect(""),
-            self.first.get_own(),
-            self.second.get_name(conn),
-            self.second.get_pos().to_str().expect(""),
-            self.second.get_own(),
-            self.third.get_name(conn),
-            self.third.get_pos().to_str().expect(""),
-            self.third.get_own(),
-            self.fourth.get_name(conn),
-            self.fourth.get_pos().to_str().expect(""),
-            self.fourth.get_own()
-        )
+            self.format.captain_label()
+        );
+        for proj in self.get_as_arr() {
+            lineup.push_str(&format!(
+                "{}: {} {}\n",
+                proj.get_name(conn),
+                proj.get_pos().to_str().expect(""),
+                proj.get_own()
+            ));
+        }
+        lineup.push('\n');
+        lineup
     }
 
-    pub fn get_as_arr(&self) -> [&Proj; 5] {
-        [
+    pub fn get_as_arr(&self) -> Vec<&Proj> {
+        let mut projs: Vec<&Proj> = vec![
             &self.mvp,
             &self.first,
             &self.second,
             &self.third,
             &self.fourth,
-        ]
+        ];
+        projs.extend(self.fifth.as_ref());
+        projs
     }
 }
 
@@ -442,9 +362,30 @@ pub struct Lineup {
     pub flex: FlexProj,
     pub def: DefProj,
     pub salary_used: i32,
+    /// What the optimizer's scorer gave the lineup, see `rank_lineups`
+    pub scorer_total: f32,
 }
 
 impl Lineup {
+    /// Every player, the flex after the TE
+    pub fn projs(&self) -> Vec<Proj> {
+        let flex: Proj = match self.flex.pos {
+            Pos::Rb => Proj::RbProj(self.flex.rb_proj.expect("Stored rb under wrong pos")),
+            _ => Proj::RecProj(self.flex.rec_proj.expect("Stored rec under wrong pos")),
+        };
+        vec![
+            Proj::QbProj(self.qb),
+            Proj::RbProj(self.rb1),
+            Proj::RbProj(self.rb2),
+            Proj::RecProj(self.wr1),
+            Proj::RecProj(self.wr2),
+            Proj::RecProj(self.wr3),
+            Proj::RecProj(self.te),
+            flex,
+            Proj::DefProj(self.def),
+        ]
+    }
+
     // TODO transfer to lite player need to add team
     pub fn score(&self) -> f32 {
         let scores: Vec<f32> = vec![
@@ -483,7 +424,7 @@ impl Lineup {
 
     pub fn get_id_array(&self) -> [i16; 9] {
         let flex_own: i16 = match self.flex.pos {
-            Pos::Wr => {
+            Pos::Wr | Pos::Te => {
                 self.flex
                     .rec_proj
                     .as_ref()
@@ -516,7 +457,7 @@ impl Lineup {
 
     pub fn get_ownership_arr(&self) -> [f32; 9] {
         let flex_own: f32 = match self.flex.pos {
-            Pos::Wr => {
+            Pos::Wr | Pos::Te => {
                 self.flex
                     .rec_proj
                     .as_ref()
@@ -559,7 +500,7 @@ impl Lineup {
         format!(
             "\nSalary: {} Score: {} Cum Own: {}\n{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}\n",
             self.salary_used,
-            self.score(),
+            self.scorer_total,
             self.get_cum_ownership(),
             format!(
                 "QB: {} Team: {} Score: {} Own: {}",
@@ -632,13 +573,20 @@ impl Lineup {
                     flex_score(&self.flex),
                     self.flex.rb_proj.as_ref().expect("").own_proj
                 ),
+                Pos::Te => format!(
+                    "FTE: {} Team: {} Score: {} Own: {}",
+                    get_player_name(self.flex.rec_proj.as_ref().expect("").id, conn),
+                    self.flex.rec_proj.as_ref().expect("").team.to_str(),
+                    flex_score(&self.flex),
+                    self.flex.rec_proj.as_ref().expect("").own_proj
+                ),
                 _ => panic!("Wrong POS For Flex"),
             }
         )
     }
 }
 
-#[derive(Clone, Debug, Copy)]
+#[derive(Clone, Debug, Copy, Serialize, Deserialize)]
 pub struct LineupBuilder {
     pub qb: Option<LitePlayer>,
     pub rb1: Option<LitePlayer>,
@@ -746,6 +694,15 @@ impl LineupBuilder {
         }
     }
 
+    /// Locked players check, works on partially built lineups
+    pub fn contains_all(&self, ids: &[i16]) -> bool {
+        let slots: [Option<LitePlayer>; 9] = [
+            self.qb, self.rb1, self.rb2, self.wr1, self.wr2, self.wr3, self.te, self.flex, self.def,
+        ];
+        ids.iter()
+            .all(|id| slots.iter().flatten().any(|p| p.id == *id))
+    }
+
     pub fn array_of_players(&self) -> [LitePlayer; 9] {
         [
             self.qb.expect("Line up missing qb"),
@@ -760,9 +717,9 @@ impl LineupBuilder {
         ]
     }
 
-    pub fn get_salary_spent_score(&self) -> f32 {
+    pub fn get_salary_spent_score(&self, salary_cap: i32) -> f32 {
         let spent: f32 = self.total_amount_spent() as f32;
-        (spent - 0.0) / (SALARY_CAP as f32 - 0.0)
+        (spent - 0.0) / (salary_cap as f32 - 0.0)
     }
 
     pub fn total_amount_spent(&self) -> i32 {
@@ -785,7 +742,10 @@ impl LineupBuilder {
                 Slot::Flex => self.flex = Some(return_if_field_exits(self.flex, &lp)),
                 _ => panic!("Bad WR Slot"),
             },
-            Pos::Te => self.te = Some(return_if_field_exits(self.te, &lp)),
+            Pos::Te => match slot {
+                Slot::Flex => self.flex = Some(return_if_field_exits(self.flex, &lp)),
+                _ => self.te = Some(return_if_field_exits(self.te, &lp)),
+            },
             Pos::D => self.def = Some(return_if_field_exits(self.def, &lp)),
             Pos::K => panic!("No kicker in regular optimizer."),
         }
@@ -795,9 +755,10 @@ impl LineupBuilder {
 
     // Will pull actual data from Sqlite
     pub fn build(self, week: i8, season: i16) -> Result<Lineup, Box<dyn std::error::Error>> {
-        let flex: FlexProj = if self.flex.as_ref().unwrap().pos == Pos::Wr {
+        let flex_pos: Pos = self.flex.as_ref().unwrap().pos;
+        let flex: FlexProj = if flex_pos == Pos::Wr || flex_pos == Pos::Te {
             FlexProj {
-                pos: Pos::Wr,
+                pos: flex_pos,
                 rec_proj: Some(get_rec_from_cache(self.flex.unwrap().id)),
                 rb_proj: None,
             }
@@ -829,13 +790,15 @@ impl LineupBuilder {
             flex,
             def,
             salary_used: self.salary_used,
+            scorer_total: 0.0,
         })
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::WR_CIELING;
+    use crate::scoring::{slate_stats, Range};
+    use crate::DATABASE_FILE;
 
     use super::*;
 
@@ -845,9 +808,9 @@ mod tests {
         // Def vs_rb will change this every time.
         if let Some(mut rb) = query_rb_proj(129, 1, 2023, &conn) {
             // rb.vegas_total = ALL_VEGAS_TOTAL.0;
-            rb.avg_att = RB_ATTS.0;
-            rb.avg_rec_tgts = RB_AVG_REC_TGTS.0;
-            rb.pts_plus_minus_proj = RB_CEILING.0;
+            rb.avg_att = slate_stats(Pos::Rb, Range::Pos, "avg_atts").bounds.0;
+            rb.avg_rec_tgts = slate_stats(Pos::Rb, Range::Pos, "avg_rec_tgts").bounds.0;
+            rb.pts_plus_minus_proj = slate_stats(Pos::Rb, Range::Pos, "cieling_proj").bounds.0;
             println!("High Score {}", rb_score(&[&rb], false, false));
         }
     }
@@ -858,9 +821,9 @@ mod tests {
         // Def vs_rb will change this every time.
         if let Some(mut wr) = query_rec_proj(1, 1, 2023, &Pos::Wr, &conn) {
             // wr.vegas_total = ALL_VEGAS_TOTAL.0;
-            wr.rec_tgt_share = WR_TGT_SHARE.0;
-            wr.red_zone_op_pg = WR_RED_ZONE.0;
-            wr.cieling_proj = WR_CIELING.0;
+            wr.rec_tgt_share = slate_stats(Pos::Wr, Range::Pos, "rec_tgt_share").bounds.0;
+            wr.red_zone_op_pg = slate_stats(Pos::Wr, Range::Pos, "red_zone_op_pg").bounds.0;
+            wr.cieling_proj = slate_stats(Pos::Wr, Range::Pos, "cieling_proj").bounds.0;
             println!("High Score {}", wr_stud_score(&wr, false));
         }
     }
@@ -871,8 +834,8 @@ mod tests {
     //     // Def vs_rb will change this every time.
     //     if let Some(mut te) = query_rec_proj(30, 1, 2023, &Pos::Te, &conn) {
     //         // te.vegas_total = ALL_VEGAS_TOTAL.0;
-    //         te.rec_tgt_share = TE_REC_TGT.0;
-    //         te.pts_sal_proj = TE_PTS_SAL.0;
+    //         te.rec_tgt_share = slate_stats(Pos::Te, Range::Pos, "rec_tgt_share").bounds.0;
+    //         te.pts_sal_proj = slate_stats(Pos::Te, Range::Pos, "pts_sal_proj").bounds.0;
     //         println!("High Score {}", te_score(&te, false));
     //     }
     // }
@@ -882,11 +845,13 @@ mod tests {
         let conn = Connection::open(DATABASE_FILE).unwrap();
         if let Some(mut dst) = query_def_proj(15, 1, 2023, &conn) {
             dst.vegas_opp_total = 14.0;
-            dst.pts_plus_minus_proj = DST_PTS_PLUS_MINUS.1;
+            dst.pts_plus_minus_proj = slate_stats(Pos::D, Range::Pos, "pts_plus_minus_proj")
+                .bounds
+                .1;
             println!(
                 "High Score {} {:?}",
                 dst_score(&dst, false),
-                *DST_VEGAS_OPP_TOTAL
+                slate_stats(Pos::D, Range::Inverse, "vegas_opp_total").bounds
             );
         }
     }
@@ -896,17 +861,20 @@ mod tests {
         if let Some(mut qb) = query_qb_proj(26, 1, 2023, &conn) {
             // qb.vegas_total = ALL_VEGAS_TOTAL.0;
             // qb.avg_rush_atts = QB_RUSH_ATT.0;
-            qb.red_zone_op_pg = QB_AVG_RZ_OP.0;
-            qb.cieling_proj = QB_CIELING.0;
-            qb.pts_sal_proj = QB_PTS_PLUS_MINUS.0;
+            qb.red_zone_op_pg = slate_stats(Pos::Qb, Range::Pos, "red_zone_op_pg").bounds.0;
+            qb.cieling_proj = slate_stats(Pos::Qb, Range::Pos, "cieling_proj").bounds.0;
+            qb.pts_sal_proj = slate_stats(Pos::Qb, Range::Pos, "pts_plus_minus_proj")
+                .bounds
+                .0;
             println!("High Score {}", qb_score(&qb, false));
         }
     }
 
     #[test]
     fn test_inverse_salary() {
-        let sal1 = get_normalized_score(7200.0 * -1.0, *RB_INVERSE_SAL);
-        let sal2 = get_normalized_score(4500.0 * -1.0, *RB_INVERSE_SAL);
+        let salary: (f32, f32) = slate_stats(Pos::Rb, Range::Inverse, "salary").bounds;
+        let sal1 = get_normalized_score(7200.0 * -1.0, salary);
+        let sal2 = get_normalized_score(4500.0 * -1.0, salary);
         println!("{} {}", sal1, sal2);
     }
 
@@ -921,6 +889,26 @@ mod tests {
         assert!(score_stacking(&[&off_team, &off_team, &off_team], &qb) == 0.0);
     }
 
+    #[test]
+    fn test_showdown_captain_salary() {
+        let player: LitePlayer = LitePlayer::test();
+        let kicker: LitePlayer = LitePlayer {
+            id: player.id + 1,
+            pos: Pos::K,
+            ..player
+        };
+        let lineup: IslandLB = IslandLB::new(IslandFormat::Showdown)
+            .set_slot(&kicker, Slot::Mvp)
+            .set_slot(&player, Slot::Fifth);
+        assert_eq!(
+            lineup.salary_used,
+            player.salary as i32 * 3 / 2 + player.salary as i32
+        );
+        assert!(lineup.contains_all(&[player.id, kicker.id]));
+        let mvp: IslandLB = IslandLB::new(IslandFormat::Mvp).set_slot(&player, Slot::Mvp);
+        assert_eq!(mvp.salary_used, player.salary as i32);
+    }
+
     #[test]
     fn test_own_arr() {
         let own = [0, 1, 2, 3, 4, 5, 6, 7, 8];
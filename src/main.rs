@@ -1,27 +1,107 @@
-use csv::Error;
+use dfstimizer::checkpoint::merge_lineups;
+use dfstimizer::config::flag_value;
+use dfstimizer::config::parse_flag;
+use dfstimizer::config::split_list;
+use dfstimizer::config::SlateConfig;
+use dfstimizer::config::Solver;
 use dfstimizer::data_loader::load_in_anyflex;
 use dfstimizer::data_loader::load_in_def_vs_pos;
 use dfstimizer::data_loader::load_in_fan_pts;
 use dfstimizer::data_loader::load_in_proj;
+use dfstimizer::data_loader::load_in_salaries;
+use dfstimizer::data_loader::ImportReport;
+use dfstimizer::exact_optimizer::build_exact_lineups;
+use dfstimizer::exact_optimizer::solve_exact;
+use dfstimizer::explain::explain;
+use dfstimizer::explain::Explanation;
+use dfstimizer::export::write_classic_csv;
+use dfstimizer::export::write_island_csv;
+use dfstimizer::get_lock_exclude_ids;
 use dfstimizer::get_slate;
+use dfstimizer::island_optimizer::build_island_lineups;
 use dfstimizer::lineup::*;
 use dfstimizer::optimizer::*;
 use dfstimizer::player::*;
+use dfstimizer::portfolio::build_portfolio;
+use dfstimizer::portfolio::Exposure;
+use dfstimizer::scorer::{load_projs, Scorer, SlateScorer};
+use dfstimizer::scoring::scoring_model;
+use dfstimizer::scoring::ScoringModel;
+use dfstimizer::tables::check_schema;
 use dfstimizer::tables::init_tables;
 use dfstimizer::total_comb;
-use dfstimizer::Day;
+use dfstimizer::tuning::{tune, tuning_slates, Objective, TuneOptions, TuneSlate, Tuned};
 use dfstimizer::DATABASE_FILE;
-use dfstimizer::GAME_DAY;
-use dfstimizer::SEASON;
-use dfstimizer::WEEK;
 use rusqlite::Connection;
 
-use std::cmp::min;
 use std::collections::HashMap;
+use std::env;
+use std::error::Error;
+use std::fs;
 use std::fs::File;
 use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
 use std::time::Instant;
 
+const USAGE: &str = "Usage: dfstimizer <command> [flags]
+
+Commands:
+    init-db                             Create the sqlite tables
+    migrate                             Upgrade an existing database to the current schema,
+                                        the other commands refuse to run until it's done
+    load proj --file <csv> --pos <pos>  Load a projection file for one position, a
+                                        site_id column is kept for upload files
+    load anyflex --file <csv>           Load an any-flex (single game) projection file
+    load def-vs-pos --file <csv> --pos <pos>
+                                        Load defense vs position points given going
+                                        into --week, kept per week for backtests
+    load fan-pts --file <csv>           Load actual fantasy points, rows without
+                                        fantasyPts are scored from passYds, passTds,
+                                        interceptions, rushYds, rushTds, receptions,
+                                        recYds, recTds and fumblesLost by --site rules
+    load salaries --file <csv>          Load the site's salary export, once loaded it
+                                        sets the player pool, salaries and site ids
+    optimize classic [--out <file>] [--csv <file>] [--verify]
+                                        Build classic lineups, --verify also tries every
+                                        lineup without pruning and checks the best matches
+                                        the exact solver's (slow)
+    optimize island [--out <file>] [--csv <file>]
+                                        Build single game lineups, FanDuel MVP or
+                                        DraftKings Showdown depending on --site
+    alias add --name <name> --id <id>   Save another spelling of a player's name, for
+                                        names the fan-pts load reports as ambiguous
+    merge --files <a.jsonl,b.jsonl,...> [--out <file>] [--csv <file>]
+                                        Combine brute force shard results into lineups
+    backtest [--threshold <pts>]        Build classic lineups and score them with actual points
+    report                              Print the player pool and iteration count
+    explain --players <ids or Name-TEAM,...> [--any-flex]
+                                        Show each feature, weight and rule behind the
+                                        players' scores, more than one is scored as a
+                                        lineup with its stack bonus (list the flex WR last)
+    write-model [--out <file>]          Write the scoring model (--model or the built in
+                                        one) as JSON to edit and load back with --model
+    tune [--objective <correlation|top-lineups>] [--iterations <n>] [--threshold <pts>]
+         [--seed <n>] [--out <file>]    Search the weights of the scoring model (--model or
+                                        the built in one) over every week with fan-pts loaded
+                                        and write the best one, top-lineups runs the solver
+                                        each try and counts lineups over --threshold
+
+    --csv writes the portfolio in the site's bulk upload format using site ids
+
+Slate flags (all commands):
+    --config <json> --site <fanduel|draftkings> --season <year> --week <week> --day <mon|thu|sun>
+    --salary-cap <sal> --min-sal <sal> (the site's cap and cap - 500 when unset)
+    --qb-count <n> --rb-count <n> --wr-count <n> --te-count <n> --d-count <n>
+    --k-count <n> (single game only, both teams' kickers when unset)
+    --lineups <n> --min-unique <n> --solver <exact|brute> --exact-top-n <n>
+    --combo-top-k <n> --checkpoint <file> --shard <i/n> --model <json>
+    --scorer <heuristic|projection|sim>
+    --lock <ids or Name-TEAM,...> --exclude <ids or Name-TEAM,...>
+
+Weather adjustments are set under \"weather\" in the --config file, e.g.
+    {\"weather\": {\"wind_threshold\": 12.0, \"wind_penalty\": 0.03}} or {\"weather\": {\"enabled\": false}}";
+
 fn count_player_type(players: &Vec<LitePlayer>, pos: Pos) -> i32 {
     let mut count: i32 = 0;
     for player in players {
@@ -32,103 +112,6 @@ fn count_player_type(players: &Vec<LitePlayer>, pos: Pos) -> i32 {
     count
 }
 
-#[allow(dead_code)]
-fn load_in_stats() {
-    init_tables();
-    // load_in_anyflex("flex/sun-night-2.csv", 2023, 22, &Day::Sun);
-    // load_in_anyflex("flex/flex-1.csv", 2023, 1, &Day::Thu);
-    // load_in_anyflex("flex/flex-3-thu.csv", 2023, 3, &Day::Thu);
-    // load_in_anyflex("flex/monday-1.csv", 2023, 1, &Day::Mon);
-    load_in_proj("sun-proj/d-1.csv", 2023, 1, &Pos::D, &Day::Sun);
-    load_in_proj("sun-proj/qb-1.csv", 2023, 1, &Pos::Qb, &Day::Sun);
-    load_in_proj("sun-proj/rb-1.csv", 2023, 1, &Pos::Rb, &Day::Sun);
-    load_in_proj("sun-proj/te-1.csv", 2023, 1, &Pos::Te, &Day::Sun);
-    load_in_proj("sun-proj/wr-1.csv", 2023, 1, &Pos::Wr, &Day::Sun);
-    load_in_proj("sun-proj/d-2.csv", 2023, 2, &Pos::D, &Day::Sun);
-    load_in_proj("sun-proj/qb-2.csv", 2023, 2, &Pos::Qb, &Day::Sun);
-    load_in_proj("sun-proj/rb-2.csv", 2023, 2, &Pos::Rb, &Day::Sun);
-    load_in_proj("sun-proj/te-2.csv", 2023, 2, &Pos::Te, &Day::Sun);
-    load_in_proj("sun-proj/wr-2.csv", 2023, 2, &Pos::Wr, &Day::Sun);
-    load_in_proj("sun-proj/d-3.csv", 2023, 3, &Pos::D, &Day::Sun);
-    load_in_proj("sun-proj/qb-3.csv", 2023, 3, &Pos::Qb, &Day::Sun);
-    load_in_proj("sun-proj/rb-3.csv", 2023, 3, &Pos::Rb, &Day::Sun);
-    load_in_proj("sun-proj/te-3.csv", 2023, 3, &Pos::Te, &Day::Sun);
-    load_in_proj("sun-proj/wr-3.csv", 2023, 3, &Pos::Wr, &Day::Sun);
-    load_in_proj("sun-proj/d-4.csv", 2023, 4, &Pos::D, &Day::Sun);
-    load_in_proj("sun-proj/qb-4.csv", 2023, 4, &Pos::Qb, &Day::Sun);
-    load_in_proj("sun-proj/rb-4.csv", 2023, 4, &Pos::Rb, &Day::Sun);
-    load_in_proj("sun-proj/te-4.csv", 2023, 4, &Pos::Te, &Day::Sun);
-    load_in_proj("sun-proj/wr-4.csv", 2023, 4, &Pos::Wr, &Day::Sun);
-    load_in_def_vs_pos("def/def-vs-qb.csv", "def_vs_qb");
-    load_in_def_vs_pos("def/def-vs-rb.csv", "def_vs_rb");
-    load_in_def_vs_pos("def/def-vs-te.csv", "def_vs_te");
-    load_in_def_vs_pos("def/def-vs-wr.csv", "def_vs_wr");
-    load_in_fan_pts("fantasy_points/dst-3-stats.csv", 2023, 3);
-    load_in_fan_pts("fantasy_points/qb-3-stats.csv", 2023, 3);
-    load_in_fan_pts("fantasy_points/rec-rb-3-stats.csv", 2023, 3);
-    load_in_fan_pts("fantasy_points/dst-2-stats.csv", 2023, 2);
-    load_in_fan_pts("fantasy_points/qb-2-stats.csv", 2023, 2);
-    load_in_fan_pts("fantasy_points/rec-rush-2-stats.csv", 2023, 2);
-}
-
-#[allow(dead_code)]
-fn parse_lineups(lineups: Vec<Lineup>) -> Option<Vec<Lineup>> {
-    let mut qb_lineups: HashMap<i16, Vec<Lineup>> = HashMap::new();
-    let mut best_lines: Vec<Lineup> = Vec::new();
-    let amount_of_qb_per = 20;
-    lineups.into_iter().for_each(|l| {
-        let qb_id: i16 = l.qb.id;
-        if qb_lineups.get(&qb_id).is_some() {
-            if !qb_lineups.get(&qb_id).unwrap().contains(&l) {
-                qb_lineups.get_mut(&qb_id).unwrap().push(l.clone());
-            }
-        } else {
-            qb_lineups.insert(qb_id, vec![l]);
-        }
-    });
-    for k in qb_lineups.keys() {
-        let lu = qb_lineups.get(k).expect("");
-        let mut clone_lu = lu.clone();
-        clone_lu.sort_by(|a, b: &Lineup| b.score().partial_cmp(&a.score()).unwrap());
-        let max_index = min(clone_lu.len(), amount_of_qb_per);
-        clone_lu[0..max_index]
-            .iter()
-            .for_each(|l| best_lines.push(l.clone()));
-    }
-    best_lines.sort_by(|a, b: &Lineup| b.score().partial_cmp(&a.score()).unwrap());
-    Some(best_lines)
-}
-
-// keep conn for ease of swapping
-#[allow(dead_code)]
-fn parse_island_lineups(lineups: Vec<IslandLineup>) -> Option<Vec<IslandLineup>> {
-    let mut qb_lineups: HashMap<i16, Vec<IslandLineup>> = HashMap::new();
-    let mut best_lines: Vec<IslandLineup> = Vec::new();
-    let amount_of_qb_per = 15;
-    lineups.into_iter().for_each(|l| {
-        let qb_id: i16 = l.mvp.get_id();
-        if qb_lineups.get(&qb_id).is_some() {
-            if !qb_lineups.get(&qb_id).unwrap().contains(&l) {
-                qb_lineups.get_mut(&qb_id).unwrap().push(l.clone());
-            }
-        } else {
-            qb_lineups.insert(qb_id, vec![l]);
-        }
-    });
-    for k in qb_lineups.keys() {
-        let lu = qb_lineups.get(k).expect("");
-        let mut clone_lu = lu.clone();
-        clone_lu
-            .sort_by(|a: &IslandLineup, b: &IslandLineup| b.score.partial_cmp(&a.score).unwrap());
-        let max_index = min(clone_lu.len(), amount_of_qb_per);
-        clone_lu[0..max_index]
-            .iter()
-            .for_each(|l| best_lines.push(l.clone()));
-    }
-    best_lines.sort_by(|a: &IslandLineup, b: &IslandLineup| b.score.partial_cmp(&a.score).unwrap());
-    Some(best_lines)
-}
-
 fn historic_lineups_scores(
     lineups: &Vec<Lineup>,
     week: i8,
@@ -156,14 +139,335 @@ fn historic_lineups_scores(
 // TODO Create Cache per thread..
 // TODO look into rayon parrell processing
 // TODO Score RB salary used and QB
-// TODO Back score lineups see how many are scoring over 200, possible iterate scoring weights
 // TODO create an immutable hashmap instead of using RWLcok
 
-fn main() -> Result<(), Error> {
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if let Err(e) = run(&args) {
+        eprintln!("{}\n\n{}", e, USAGE);
+        std::process::exit(1);
+    }
+}
+
+fn run(args: &[String]) -> Result<(), Box<dyn Error>> {
     let start: Instant = Instant::now();
-    let conn = Connection::open(DATABASE_FILE).unwrap();
-    // load_in_stats();
-    let players: Vec<LitePlayer> = get_slate(WEEK, SEASON, &GAME_DAY, &conn);
+    let config: SlateConfig = SlateConfig::from_args(args)?;
+    config.install();
+    if let Some(path) = &config.model {
+        ScoringModel::from_file(path)?.install();
+    }
+    let command: Option<&str> = args.first().map(|c| c.as_str());
+    let sub_command: Option<&str> = args.get(1).map(|c| c.as_str());
+    let creates_schema: bool = matches!(
+        command,
+        None | Some("init-db")
+            | Some("migrate")
+            | Some("write-model")
+            | Some("help")
+            | Some("--help")
+    );
+    if !creates_schema {
+        check_schema(&Connection::open(DATABASE_FILE)?)?;
+    }
+    match (command, sub_command) {
+        (Some("init-db"), _) | (Some("migrate"), _) => init_tables()?,
+        (Some("load"), Some(kind)) => load(kind, args, &config)?,
+        (Some("optimize"), Some("classic")) => {
+            check_lock_exclude(&config)?;
+            optimize_classic(args, &config)?
+        }
+        (Some("optimize"), Some("island")) => {
+            check_lock_exclude(&config)?;
+            optimize_island(args, &config)?
+        }
+        (Some("backtest"), _) => {
+            check_lock_exclude(&config)?;
+            backtest(args, &config)?
+        }
+        (Some("merge"), _) => merge(args, &config)?,
+        (Some("alias"), Some("add")) => alias_add(args)?,
+        (Some("report"), _) => report(&config)?,
+        (Some("explain"), _) => explain_players(args, &config)?,
+        (Some("write-model"), _) => write_model(args)?,
+        (Some("tune"), _) => tune_model(args, &config)?,
+        (Some("help"), _) | (Some("--help"), _) => println!("{}", USAGE),
+        _ => return Err(format!("Unknown command: {}", args.join(" ")).into()),
+    }
+    println!("Elapsed Time: {:?}", start.elapsed());
+    Ok(())
+}
+
+fn required_flag<'a>(args: &'a [String], flag: &str) -> Result<&'a str, String> {
+    flag_value(args, flag).ok_or(format!("Missing required flag {}", flag))
+}
+
+fn pos_flag(args: &[String]) -> Result<Pos, String> {
+    let pos: &str = required_flag(args, "--pos")?;
+    Pos::from_str(pos).map_err(|_| format!("Not a position: {}", pos))
+}
+
+// The optimizer threads panic on a bad list so catch it up front
+fn check_lock_exclude(config: &SlateConfig) -> Result<(), Box<dyn Error>> {
+    let conn: Connection = Connection::open(DATABASE_FILE)?;
+    let (locked, excluded) = get_lock_exclude_ids(config, &conn)?;
+    if !locked.is_empty() || !excluded.is_empty() {
+        println!("Locked: {:?} Excluded: {:?}", locked, excluded);
+    }
+    Ok(())
+}
+
+// Matchups without def vs pos numbers score like an average defense
+fn warn_missing_def_vs_pos(config: &SlateConfig, conn: &Connection) {
+    let missing: Vec<String> = missing_def_vs_pos(config.season, config.week, &config.day, conn);
+    if !missing.is_empty() {
+        println!(
+            "No def vs pos numbers by week {} for {}, using the league average",
+            config.week,
+            missing.join(", ")
+        );
+    }
+}
+
+fn load(kind: &str, args: &[String], config: &SlateConfig) -> Result<(), Box<dyn Error>> {
+    let file: &str = required_flag(args, "--file")?;
+    let report: ImportReport = match kind {
+        "proj" => load_in_proj(
+            file,
+            config.site,
+            config.season,
+            config.week,
+            &pos_flag(args)?,
+            &config.day,
+        ),
+        "anyflex" => load_in_anyflex(file, config.site, config.season, config.week, &config.day),
+        "def-vs-pos" => load_in_def_vs_pos(
+            file,
+            pos_flag(args)?.get_def_table(),
+            config.season,
+            config.week,
+        ),
+        "fan-pts" => load_in_fan_pts(file, config.season, config.week, config.site),
+        "salaries" => load_in_salaries(file, config.site, config.season, config.week, &config.day),
+        _ => return Err(format!("Unknown load type: {}", kind).into()),
+    }?;
+    println!("{}", report);
+    Ok(())
+}
+
+fn write_model(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let path: &str = flag_value(args, "--out").unwrap_or("scoring-model.json");
+    scoring_model().write_file(path)?;
+    println!("Wrote scoring model to {}", path);
+    Ok(())
+}
+
+fn tune_model(args: &[String], config: &SlateConfig) -> Result<(), Box<dyn Error>> {
+    let mut options: TuneOptions = TuneOptions::default();
+    if let Some(objective) = flag_value(args, "--objective") {
+        options.objective =
+            Objective::try_from_str(objective).ok_or(format!("Not an objective: {}", objective))?;
+    }
+    if let Some(iterations) = flag_value(args, "--iterations") {
+        options.iterations = parse_flag("--iterations", iterations)?;
+    }
+    if let Some(threshold) = flag_value(args, "--threshold") {
+        options.threshold = parse_flag("--threshold", threshold)?;
+    }
+    if let Some(seed) = flag_value(args, "--seed") {
+        options.seed = parse_flag("--seed", seed)?;
+    }
+    let path: &str = flag_value(args, "--out").unwrap_or("tuned-model.json");
+    let conn: Connection = Connection::open(DATABASE_FILE)?;
+    let slates: Vec<TuneSlate> = tuning_slates(config, &conn);
+    if slates.is_empty() {
+        return Err("No weeks with both projections and fan-pts loaded".into());
+    }
+    println!("Tuning over {} weeks", slates.len());
+    let tuned: Tuned = tune(&scoring_model(), &slates, &options, config)?;
+    tuned.model.write_file(path)?;
+    println!(
+        "Objective {} -> {}, wrote the model to {}",
+        tuned.start, tuned.best, path
+    );
+    Ok(())
+}
+
+fn alias_add(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let name: &str = required_flag(args, "--name")?;
+    let id: i16 = parse_flag("--id", required_flag(args, "--id")?)?;
+    let conn: Connection = Connection::open(DATABASE_FILE)?;
+    add_player_alias(name, id, &conn)?;
+    println!("{} now matches player {}", name, id);
+    Ok(())
+}
+
+fn explain_players(args: &[String], config: &SlateConfig) -> Result<(), Box<dyn Error>> {
+    let refs: Vec<String> = split_list(required_flag(args, "--players")?);
+    let any_flex: bool = args.iter().any(|a| a == "--any-flex");
+    let conn: Connection = Connection::open(DATABASE_FILE)?;
+    warn_missing_def_vs_pos(config, &conn);
+    let ids: Vec<i16> = resolve_player_refs(&refs, &conn)?;
+    let explanation: Explanation = explain(&ids, any_flex, config, &conn)?;
+    print!("{}", explanation.report());
+    Ok(())
+}
+
+fn create_out_file(args: &[String], default_path: String) -> Result<File, Box<dyn Error>> {
+    let path: String = flag_value(args, "--out")
+        .map(|p| p.to_owned())
+        .unwrap_or(default_path);
+    if let Some(dir) = Path::new(&path).parent() {
+        fs::create_dir_all(dir)?;
+    }
+    println!("Writing lineups to {}", path);
+    Ok(File::create(path)?)
+}
+
+fn build_classic_lineups(config: &SlateConfig) -> Result<Vec<Lineup>, Box<dyn Error>> {
+    let scorer: Arc<dyn Scorer> = config.scorer.build();
+    match config.solver {
+        Solver::Exact => build_exact_lineups(config, &scorer),
+        Solver::Brute => build_all_possible_lineups(config, &scorer),
+    }
+}
+
+// Holds the exact solver to trying every lineup, compares on the search score
+// both optimize, not Lineup::score
+fn verify_solvers(config: &SlateConfig) -> Result<(), Box<dyn Error>> {
+    let conn: Connection = Connection::open(DATABASE_FILE)?;
+    let slate: Vec<LitePlayer> = get_slate(config, &conn)?;
+    let slate_scorer: Arc<SlateScorer> = Arc::new(SlateScorer::new(
+        config.scorer.build(),
+        load_projs(&slate, config, &conn),
+        false,
+    ));
+    let players: Vec<LitePlayer> = slate_scorer.score_players(&slate);
+    let (locked, _) = get_lock_exclude_ids(config, &conn)?;
+    let exact: f32 = solve_exact(&players, config, &locked, &slate_scorer)
+        .first()
+        .map(|l| slate_scorer.lineup_score(l))
+        .unwrap_or(0.0);
+    let unpruned: f32 = unpruned_best(&players, config, &locked, &slate_scorer);
+    println!("Best score exact: {} every lineup: {}", exact, unpruned);
+    if (exact - unpruned).abs() > 0.001 {
+        return Err(format!(
+            "Solvers disagree, exact {} every lineup {}",
+            exact, unpruned
+        )
+        .into());
+    }
+    Ok(())
+}
+
+fn optimize_classic(args: &[String], config: &SlateConfig) -> Result<(), Box<dyn Error>> {
+    report(config)?;
+    if args.iter().any(|a| a == "--verify") {
+        verify_solvers(config)?;
+    }
+    if let Some(shard) = config.shard {
+        if config.solver != Solver::Brute {
+            return Err("--shard only works with --solver brute".into());
+        }
+        build_all_possible_lineups(config, &config.scorer.build())?;
+        println!(
+            "Shard {}/{} saved to {}, combine the shards with merge",
+            shard.index,
+            shard.count,
+            config.checkpoint_path().unwrap()
+        );
+        return Ok(());
+    }
+    let lineups: Vec<Lineup> = build_classic_lineups(config)?;
+    write_classic_portfolio(args, config, lineups)
+}
+
+fn write_classic_portfolio(
+    args: &[String],
+    config: &SlateConfig,
+    lineups: Vec<Lineup>,
+) -> Result<(), Box<dyn Error>> {
+    let conn: Connection = Connection::open(DATABASE_FILE)?;
+    let exposures: HashMap<i16, Exposure> =
+        get_exposures(config.season, config.week, &config.day, &conn);
+    let mut file: File = create_out_file(
+        args,
+        format!(
+            "lineups/lineups-{}-{}.txt",
+            config.week,
+            config.day.to_str()
+        ),
+    )?;
+    let portfolio: Vec<Lineup> =
+        build_portfolio(lineups, config.lineup_count, config.min_unique, &exposures);
+    for lineup in &portfolio {
+        file.write_all(lineup.lineup_str(&conn).as_bytes())?;
+    }
+    if let Some(path) = flag_value(args, "--csv") {
+        let site_ids: HashMap<i16, String> =
+            get_site_ids(config.season, config.week, &config.day, config.site, &conn);
+        write_classic_csv(path, config.site, &portfolio, &site_ids)?;
+    }
+    Ok(())
+}
+
+// Shard files only store players so the slate gets reloaded by id
+fn merge(args: &[String], config: &SlateConfig) -> Result<(), Box<dyn Error>> {
+    let files: Vec<String> = split_list(required_flag(args, "--files")?);
+    let (config, lineups) = merge_lineups(&files, config)?;
+    println!(
+        "Merged {} lineups from {} files",
+        lineups.len(),
+        files.len()
+    );
+    write_classic_portfolio(args, &config, lineups)
+}
+
+fn optimize_island(args: &[String], config: &SlateConfig) -> Result<(), Box<dyn Error>> {
+    let conn: Connection = Connection::open(DATABASE_FILE)?;
+    let lineups: Vec<IslandLineup> = build_island_lineups(config, &config.scorer.build())?;
+    let exposures: HashMap<i16, Exposure> =
+        get_exposures(config.season, config.week, &config.day, &conn);
+    let mut file: File = create_out_file(
+        args,
+        format!("lineups/island-{}-{}.txt", config.week, config.day.to_str()),
+    )?;
+    let portfolio: Vec<IslandLineup> =
+        build_portfolio(lineups, config.lineup_count, config.min_unique, &exposures);
+    for lineup in &portfolio {
+        file.write_all(lineup.lineup_str(&conn).as_bytes())?;
+    }
+    if let Some(path) = flag_value(args, "--csv") {
+        let site_ids: HashMap<i16, String> =
+            get_site_ids(config.season, config.week, &config.day, config.site, &conn);
+        write_island_csv(path, config.site, &portfolio, &site_ids)?;
+    }
+    Ok(())
+}
+
+fn backtest(args: &[String], config: &SlateConfig) -> Result<(), Box<dyn Error>> {
+    let threshold: f32 = match flag_value(args, "--threshold") {
+        Some(t) => t
+            .parse()
+            .map_err(|_| format!("Bad value for --threshold: {}", t))?,
+        None => 160.0,
+    };
+    let conn: Connection = Connection::open(DATABASE_FILE)?;
+    warn_missing_def_vs_pos(config, &conn);
+    let mut lineups: Vec<Lineup> = build_classic_lineups(config)?;
+    lineups.sort_by(|a, b: &Lineup| b.scorer_total.total_cmp(&a.scorer_total));
+    println!(
+        "Lineups over {}: {} total {:?}",
+        threshold,
+        historic_lineups_scores(&lineups, config.week, config.season, threshold, &conn),
+        &lineups.len()
+    );
+    Ok(())
+}
+
+fn report(config: &SlateConfig) -> Result<(), Box<dyn Error>> {
+    let conn: Connection = Connection::open(DATABASE_FILE)?;
+    warn_missing_def_vs_pos(config, &conn);
+    let players: Vec<LitePlayer> = get_slate(config, &conn)?;
     let qb: u32 = count_player_type(&players, Pos::Qb) as u32;
     let wr_count: u32 = count_player_type(&players, Pos::Wr) as u32;
     let wr: u32 = total_comb(wr_count.try_into().unwrap(), 3);
@@ -171,32 +475,24 @@ fn main() -> Result<(), Error> {
     let rb: u32 = total_comb(rb_count.try_into().unwrap(), 2);
     let te: u32 = count_player_type(&players, Pos::Te) as u32;
     let d: u32 = count_player_type(&players, Pos::D) as u32;
-    let flex: u32 = wr_count + rb_count;
+    let flex: u32 = config
+        .site
+        .flex_positions()
+        .iter()
+        .map(|pos| count_player_type(&players, *pos) as u32)
+        .sum();
     let total: u128 = qb as u128 * wr as u128 * rb as u128 * te as u128 * d as u128 * flex as u128;
-    // println!("Total Players: {}", players.len());
+    println!(
+        "Slate {} week {} {}",
+        config.season,
+        config.week,
+        config.day.to_str()
+    );
+    println!(
+        "QB: {} RB: {} WR: {} TE: {} DST: {}",
+        qb, rb_count, wr_count, te, d
+    );
     println!("Max Iterations: {}", total);
-    println!("WR Combos: {}", total_comb(wr_count as usize, 3));
-    // TODO load in def for sunday
-    let mut lineups: Vec<Lineup> = build_all_possible_lineups(WEEK, SEASON);
-    // let lineups: Vec<IslandLineup> = build_island_lineups(WEEK, SEASON);
-
-    let mut file = File::create(format!(
-        "lineups/lineups-{}-{}.txt",
-        WEEK,
-        &GAME_DAY.to_str()
-    ))
-    .unwrap();
-    lineups.sort_by(|a, b: &Lineup| b.score().partial_cmp(&a.score()).unwrap());
-    // println!(
-    //     "Lineups over 200: {} total {:?}",
-    //     historic_lineups_scores(&lineups, WEEK, SEASON, 160.0, &conn),
-    //     &lineups.len()
-    // );
-
-    for lineup in parse_lineups(lineups).unwrap() {
-        file.write_all(lineup.lineup_str(&conn).as_bytes())?;
-    }
-
-    println!("Elapsed Time: {:?}", start.elapsed());
+    println!("WR Combos: {}", wr);
     Ok(())
 }
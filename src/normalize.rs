@@ -0,0 +1,101 @@
+use serde::{Deserialize, Serialize};
+
+/// How a feature's value is put on a 0-1 scale against the slate
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Normalize {
+    /// Between the slate's (max, min), clamped
+    #[default]
+    MinMax,
+    /// Standard deviations from the slate mean, -3 to 3 mapped to 0-1
+    ZScore,
+    /// Share of the slate at or under the value
+    Percentile,
+}
+
+/// Standard deviations at either end of the z-score scale
+const Z_RANGE: f32 = 3.0;
+
+/// One column's values over a slate
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SlateStats {
+    /// (max, min) for min-max, padded the way `get_max_min` always has
+    pub bounds: (f32, f32),
+    pub mean: f32,
+    pub std_dev: f32,
+    // Sorted for percentiles
+    values: Vec<f32>,
+}
+
+impl SlateStats {
+    pub fn new(bounds: (f32, f32), mut values: Vec<f32>) -> SlateStats {
+        values.retain(|v| v.is_finite());
+        values.sort_by(f32::total_cmp);
+        let count: f32 = values.len().max(1) as f32;
+        let mean: f32 = values.iter().sum::<f32>() / count;
+        let variance: f32 = values.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / count;
+        SlateStats {
+            bounds,
+            mean,
+            std_dev: variance.sqrt(),
+            values,
+        }
+    }
+
+    pub fn normalize(&self, value: f32, method: Normalize) -> f32 {
+        match method {
+            Normalize::MinMax => min_max(value, self.bounds),
+            Normalize::ZScore => {
+                if self.std_dev == 0.0 {
+                    return 0.5;
+                }
+                let z: f32 = ((value - self.mean) / self.std_dev).clamp(-Z_RANGE, Z_RANGE);
+                (z + Z_RANGE) / (2.0 * Z_RANGE)
+            }
+            Normalize::Percentile => {
+                if self.values.is_empty() {
+                    return 0.0;
+                }
+                let at_or_under: usize = self.values.partition_point(|v| *v <= value);
+                at_or_under as f32 / self.values.len() as f32
+            }
+        }
+    }
+}
+
+/// Value between (max, min) clamped to 0-1, 0 when there's no range
+pub fn min_max(value: f32, max_min: (f32, f32)) -> f32 {
+    let (max, min) = max_min;
+    if max <= min || value.is_nan() {
+        return 0.0;
+    }
+    ((value - min) / (max - min)).clamp(0.0, 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_min_max_clamps() {
+        assert_eq!(min_max(5.0, (10.0, 0.0)), 0.5);
+        assert_eq!(min_max(12.0, (10.0, 0.0)), 1.0);
+        assert_eq!(min_max(-3.0, (10.0, 0.0)), 0.0);
+        assert_eq!(min_max(3.0, (2.0, 2.0)), 0.0);
+    }
+
+    #[test]
+    fn test_slate_stats() {
+        let stats: SlateStats = SlateStats::new((8.0, 0.0), vec![8.0, 2.0, 4.0, 6.0, f32::NAN]);
+        assert_eq!(stats.mean, 5.0);
+        assert_eq!(stats.normalize(4.0, Normalize::MinMax), 0.5);
+        assert_eq!(stats.normalize(5.0, Normalize::ZScore), 0.5);
+        assert_eq!(stats.normalize(100.0, Normalize::ZScore), 1.0);
+        assert_eq!(stats.normalize(4.0, Normalize::Percentile), 0.5);
+        assert_eq!(stats.normalize(1.0, Normalize::Percentile), 0.0);
+        assert_eq!(stats.normalize(9.0, Normalize::Percentile), 1.0);
+
+        let flat: SlateStats = SlateStats::new((3.0, 0.0), vec![3.0, 3.0]);
+        assert_eq!(flat.normalize(7.0, Normalize::ZScore), 0.5);
+    }
+}
@@ -1,241 +1,697 @@
 use futures::channel::mpsc;
+use futures::channel::mpsc::UnboundedSender;
 use futures::executor;
 use futures::executor::ThreadPool;
-use futures::future::join_all;
 use futures::StreamExt;
 use itertools::Itertools;
 use rusqlite::Connection;
 
+use crate::checkpoint::{Checkpoint, CheckpointHeader, ComboResult};
+use crate::config::SlateConfig;
+use crate::get_lock_exclude_ids;
 use crate::get_slate;
-use crate::get_top_players_by_pos;
 use crate::lineup::*;
 use crate::player::*;
+use crate::scorer::{load_projs, rank_lineups, Scorer, SlateScorer};
 use crate::DATABASE_FILE;
-use crate::GAME_DAY;
-use crate::MIN_SAL;
-use crate::SALARY_CAP;
-use crate::WR_COUNT;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
 use std::collections::HashMap;
+use std::error::Error;
+use std::sync::Arc;
 use std::time::Instant;
 
-pub fn build_all_possible_lineups(week: i8, season: i16) -> Vec<Lineup> {
+/// Brute force over every WR combination on the thread pool, or just this
+/// machine's shard of them. Finished combinations go through one collector that
+/// saves them to the checkpoint file, so a restarted run only does the
+/// combinations that are left.
+pub fn build_all_possible_lineups(
+    config: &SlateConfig,
+    scorer: &Arc<dyn Scorer>,
+) -> Result<Vec<Lineup>, Box<dyn Error>> {
     let pool: ThreadPool = ThreadPool::new().unwrap();
-    let conn: Connection = Connection::open(DATABASE_FILE).unwrap();
-    let mut finished_lineups: Vec<Lineup> = Vec::new();
-    let wr_ids: Vec<i16> =
-        get_top_players_by_pos(season, week, &Pos::Wr, WR_COUNT, &GAME_DAY, &conn)
-            .into_iter()
-            .map(|wr| wr.id)
-            .collect_vec();
+    let conn: Connection = Connection::open(DATABASE_FILE)?;
+    let (locked, excluded) = get_lock_exclude_ids(config, &conn)?;
+    let players: Vec<LitePlayer> = get_slate(config, &conn)?;
+    let wr_ids: Vec<i16> = players
+        .iter()
+        .filter(|p| p.pos == Pos::Wr)
+        .map(|wr| wr.id)
+        .collect_vec();
+    let mut checkpoint: Checkpoint = match config.checkpoint_path() {
+        Some(path) => Checkpoint::open(
+            &path,
+            &CheckpointHeader::new(config, &players, &locked, &excluded),
+        )?,
+        None => Checkpoint::default(),
+    };
     println!("Cooking up LINEUPS!! {} WRs", wr_ids.len());
-    let mut futures: Vec<_> = Vec::new();
-    for wr_id in wr_ids.into_iter().combinations(3) {
-        let (tx, rx) = mpsc::unbounded::<Lineup>();
-        let future = async {
-            let fut_tx_result = async move {
-                let start: Instant = Instant::now();
-                let conn: Connection = Connection::open(DATABASE_FILE).unwrap();
-                let thread_players: Vec<LitePlayer> = get_slate(week, season, &GAME_DAY, &conn);
-                drop(conn);
-                let mut qb_lineups: Vec<LineupBuilder> = Vec::new();
-                thread_players
-                    .iter()
-                    .filter(|player: &&LitePlayer| player.pos == Pos::Qb)
-                    .for_each(|qb: &LitePlayer| {
-                        let lineup_builder: LineupBuilder = LineupBuilder::new();
-                        qb_lineups.push(lineup_builder.set_pos(&qb, Slot::None))
-                    });
-                let wr_lineups: Vec<LineupBuilder> =
-                    add_wrs_to_lineups(wr_id, &thread_players, qb_lineups);
-                let rbs_lineups: Vec<LineupBuilder> = add_rbs_to_lineups(
-                    &thread_players
-                        .clone()
-                        .into_iter()
-                        .filter(|p| p.pos == Pos::Rb)
-                        .collect_vec(),
-                    wr_lineups,
-                );
-                let te_lineups: Vec<LineupBuilder> = add_te_to_lineups(
-                    &thread_players
-                        .clone()
-                        .into_iter()
-                        .filter(|p| p.pos == Pos::Te)
-                        .collect_vec(),
-                    rbs_lineups,
-                );
-                let dst_lineups: Vec<LineupBuilder> = add_dst_to_lineups(
-                    &thread_players
-                        .clone()
-                        .into_iter()
-                        .filter(|p| p.pos == Pos::D)
-                        .collect_vec(),
-                    te_lineups,
-                );
-                // let filterd_lineups: Vec<LineupBuilder> = filter_low_salary_cap(dst_lineups, 39500);
-                let flex_pos: [Pos; 2] = [Pos::Wr, Pos::Rb];
-                let lineup: Option<LineupBuilder> = add_flex_find_top_num(
-                    &thread_players
-                        .into_iter()
-                        .filter(|p| flex_pos.contains(&p.pos))
-                        .collect_vec(),
-                    dst_lineups,
-                    week,
-                    season,
-                );
-                if lineup.is_some() {
-                    tx.unbounded_send(lineup.unwrap().build(week, season).expect(""))
-                        .expect("Failed to send lineup")
-                }
-                println!("Finished Thread {:?}", start.elapsed());
-            };
-            pool.spawn_ok(fut_tx_result);
-
-            let future = rx.collect::<Vec<Lineup>>();
-            future.await
+    let (tx, mut rx) = mpsc::unbounded::<ComboResult>();
+    for (combo, wr_id) in wr_ids.into_iter().combinations(3).enumerate() {
+        let mut wr_key: [i16; 3] = [wr_id[0], wr_id[1], wr_id[2]];
+        wr_key.sort();
+        if checkpoint.is_done(&wr_key) || config.shard.is_some_and(|s| !s.owns(combo)) {
+            continue;
+        }
+        let tx: UnboundedSender<ComboResult> = tx.clone();
+        let config: SlateConfig = config.clone();
+        let locked: Vec<i16> = locked.clone();
+        let scorer: Arc<dyn Scorer> = scorer.clone();
+        let slate: Vec<LitePlayer> = players.clone();
+        let fut_tx_result = async move {
+            let start: Instant = Instant::now();
+            let conn: Connection = Connection::open(DATABASE_FILE).unwrap();
+            let scorer: SlateScorer =
+                SlateScorer::new(scorer, load_projs(&slate, &config, &conn), false);
+            drop(conn);
+            let thread_players: Vec<LitePlayer> = scorer.score_players(&slate);
+            let p_lookup: HashMap<i16, &LitePlayer> =
+                LitePlayer::player_lookup_map(&thread_players);
+            let wrs: Vec<LitePlayer> = wr_id
+                .iter()
+                .map(|id| **p_lookup.get(id).expect("Player missing"))
+                .collect();
+            let lineups: Vec<LineupBuilder> =
+                top_lineups_for_wrs(&wrs, &thread_players, &config, &locked, &scorer);
+            tx.unbounded_send(ComboResult {
+                wrs: wr_key,
+                lineups,
+            })
+            .expect("Failed to send lineups");
+            println!("Finished Thread {:?}", start.elapsed());
         };
-        futures.push(future);
+        pool.spawn_ok(fut_tx_result);
     }
-    let futures_join = join_all(futures);
-    let done_futures = executor::block_on(futures_join);
-    for future in done_futures {
-        finished_lineups.extend(future);
+    drop(tx);
+    executor::block_on(async {
+        while let Some(result) = rx.next().await {
+            checkpoint.record(result)?;
+        }
+        Ok::<(), Box<dyn Error>>(())
+    })?;
+    let finished_lineups: Vec<Lineup> = checkpoint
+        .into_lineups()
+        .into_iter()
+        .map(|l| l.build(config.week, config.season))
+        .collect::<Result<_, _>>()?;
+    Ok(rank_lineups(finished_lineups, scorer.as_ref()))
+}
+
+/// Best search score over every lineup, no bounds or pools narrowed by locks,
+/// 0 when nothing fits. Far slower than the searches, it's the independent
+/// check `--verify` and the tests hold them to. Each WR combination runs on
+/// the thread pool.
+pub fn unpruned_best(
+    players: &[LitePlayer],
+    config: &SlateConfig,
+    locked: &[i16],
+    scorer: &Arc<SlateScorer>,
+) -> f32 {
+    let pool: ThreadPool = ThreadPool::new().unwrap();
+    let wrs: Vec<LitePlayer> = players
+        .iter()
+        .filter(|p| p.pos == Pos::Wr)
+        .cloned()
+        .collect();
+    let (tx, rx) = mpsc::unbounded::<f32>();
+    for combo in wrs.into_iter().combinations(3) {
+        let tx: UnboundedSender<f32> = tx.clone();
+        let players: Vec<LitePlayer> = players.to_vec();
+        let config: SlateConfig = config.clone();
+        let locked: Vec<i16> = locked.to_vec();
+        let scorer: Arc<SlateScorer> = scorer.clone();
+        pool.spawn_ok(async move {
+            let best: f32 = unpruned_best_for_wrs(&combo, &players, &config, &locked, &scorer);
+            tx.unbounded_send(best).expect("Failed to send score");
+        });
     }
-    finished_lineups.sort_by(|a, b: &Lineup| b.score().partial_cmp(&a.score()).unwrap());
-    finished_lineups
+    drop(tx);
+    executor::block_on(rx.fold(0.0, |a: f32, b: f32| async move { a.max(b) }))
 }
 
-pub fn filter_low_salary_cap(
-    mut lineups: Vec<LineupBuilder>,
-    filter_cap: i32,
-) -> Vec<LineupBuilder> {
-    lineups.retain(|l| l.salary_used > filter_cap);
-    lineups
+/// Every lineup around three WRs, see `unpruned_best`
+pub fn unpruned_best_for_wrs(
+    wrs: &[LitePlayer],
+    players: &[LitePlayer],
+    config: &SlateConfig,
+    locked: &[i16],
+    scorer: &SlateScorer,
+) -> f32 {
+    let by_pos =
+        |pos: Pos| -> Vec<&LitePlayer> { players.iter().filter(|p| p.pos == pos).collect() };
+    let flex: Vec<&LitePlayer> = players
+        .iter()
+        .filter(|p| config.site.flex_positions().contains(&p.pos))
+        .filter(|p| !wrs.iter().any(|wr| wr.id == p.id))
+        .collect();
+    let lineup: LineupBuilder = LineupBuilder::new()
+        .set_pos(&wrs[0], Slot::First)
+        .set_pos(&wrs[1], Slot::Second)
+        .set_pos(&wrs[2], Slot::Third);
+    let mut best: f32 = 0.0;
+    for qb in by_pos(Pos::Qb) {
+        let lineup: LineupBuilder = lineup.set_pos(qb, Slot::None);
+        for rbs in by_pos(Pos::Rb).into_iter().combinations(2) {
+            let lineup: LineupBuilder = lineup
+                .set_pos(rbs[0], Slot::First)
+                .set_pos(rbs[1], Slot::Second);
+            for te in by_pos(Pos::Te) {
+                let lineup: LineupBuilder = lineup.set_pos(te, Slot::None);
+                for def in by_pos(Pos::D) {
+                    let lineup: LineupBuilder = lineup.set_pos(def, Slot::None);
+                    for flex in &flex {
+                        if [rbs[0].id, rbs[1].id, te.id].contains(&flex.id) {
+                            continue;
+                        }
+                        let lineup: LineupBuilder = lineup.set_pos(flex, Slot::Flex);
+                        if lineup.salary_used < config.salary_cap()
+                            && lineup.salary_used > config.min_sal()
+                            && lineup.fits_own_brackets()
+                            && lineup.contains_all(locked)
+                        {
+                            best = best.max(scorer.lineup_score(&lineup));
+                        }
+                    }
+                }
+            }
+        }
+    }
+    best
 }
 
-// Needs to barrow players so it can be passed to the rest of the functions
-pub fn add_wrs_to_lineups(
-    player_ids: Vec<i16>,
-    players: &Vec<LitePlayer>,
-    lineups: Vec<LineupBuilder>,
-) -> Vec<LineupBuilder> {
-    let mut new_lineups: Vec<LineupBuilder> = Vec::new();
-    let p_lookup: HashMap<i16, &LitePlayer> = LitePlayer::player_lookup_map(players);
-    for lineup in &lineups {
-        new_lineups.push(
-            lineup
-                .clone()
-                .set_pos(
-                    *p_lookup.get(&player_ids[0]).expect("Player missing"),
-                    Slot::First,
-                )
-                .set_pos(
-                    *p_lookup.get(&player_ids[1]).expect("Missing Player"),
-                    Slot::Second,
-                )
-                .set_pos(
-                    *p_lookup.get(&player_ids[2]).expect("Missing Player"),
-                    Slot::Third,
-                ),
-        )
+/// Players for a position with one roster spot, only the locked ones when there are any
+pub fn single_slot_pool(players: &[LitePlayer], pos: Pos, locked: &[i16]) -> Vec<LitePlayer> {
+    let pool: Vec<LitePlayer> = players.iter().filter(|p| p.pos == pos).cloned().collect();
+    let locked_pool: Vec<LitePlayer> = pool
+        .iter()
+        .filter(|p| locked.contains(&p.id))
+        .cloned()
+        .collect();
+    if locked_pool.is_empty() {
+        pool
+    } else {
+        locked_pool
     }
-    new_lineups
 }
 
-pub fn add_rbs_to_lineups(
-    rbs: &Vec<LitePlayer>,
-    lineups: Vec<LineupBuilder>,
-) -> Vec<LineupBuilder> {
-    let mut new_lineups: Vec<LineupBuilder> = Vec::new();
-    let p_lookup: HashMap<i16, &LitePlayer> = LitePlayer::player_lookup_map(&rbs);
-    for lineup in lineups {
-        for combo in rbs.iter().combinations(2) {
-            new_lineups.push(
-                lineup
-                    .clone()
-                    .set_pos(
-                        *p_lookup.get(&combo[0].id).expect("Player missing"),
-                        Slot::First,
-                    )
-                    .set_pos(
-                        *p_lookup.get(&combo[1].id).expect("Player Missing"),
-                        Slot::Second,
-                    ),
-            );
+/// Best score first so a loop can stop once the bound drops under the best lineup
+pub fn sort_by_score(mut players: Vec<LitePlayer>) -> Vec<LitePlayer> {
+    players.sort_by(|a, b| b.score.total_cmp(&a.score));
+    players
+}
+
+// Scores get summed in a different order at the end than in the bounds
+pub const BOUND_SLACK: f32 = 0.001;
+
+/// Salary range and best score of the slots a partial lineup still has to fill
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SlotBounds {
+    pub cheapest: i32,
+    pub priciest: i32,
+    pub max_score: f32,
+}
+
+impl SlotBounds {
+    /// Bounds for filling `count` slots from the pool
+    pub fn new(pool: &[LitePlayer], count: usize) -> SlotBounds {
+        let salaries: Vec<i32> = pool.iter().map(|p| p.salary as i32).sorted().collect();
+        SlotBounds {
+            cheapest: salaries.iter().take(count).sum(),
+            priciest: salaries.iter().rev().take(count).sum(),
+            max_score: sort_by_score(pool.to_vec())
+                .iter()
+                .take(count)
+                .map(|p| p.score)
+                .sum(),
+        }
+    }
+
+    pub fn plus(self, other: SlotBounds) -> SlotBounds {
+        SlotBounds {
+            cheapest: self.cheapest + other.cheapest,
+            priciest: self.priciest + other.priciest,
+            max_score: self.max_score + other.max_score,
+        }
+    }
+
+    /// Some way of filling the slots ends up over min_sal and under the cap
+    pub fn salary_in_reach(&self, salary: i32, config: &SlateConfig) -> bool {
+        salary + self.cheapest < config.salary_cap() && salary + self.priciest > config.min_sal()
+    }
+
+    /// Filling the slots could still score over `best`
+    pub fn can_beat(&self, score: f32, best: Option<f32>) -> bool {
+        match best {
+            Some(best) => score + self.max_score + BOUND_SLACK > best,
+            None => true,
         }
     }
-    new_lineups
 }
 
-pub fn add_te_to_lineups(tes: &Vec<LitePlayer>, lineups: Vec<LineupBuilder>) -> Vec<LineupBuilder> {
-    let mut lineups_with_te: Vec<LineupBuilder> = Vec::with_capacity(lineups.len());
-    for lineup in &lineups {
-        tes.iter().for_each(|te| {
-            lineups_with_te.push(lineup.clone().set_pos(&te, Slot::None));
-        })
+/// Ordered worst first so the heap can drop the lowest score
+struct ScoredLineup {
+    score: f32,
+    // Sorted so the same players in different slots match
+    ids: [i16; 9],
+    lineup: LineupBuilder,
+}
+
+impl PartialEq for ScoredLineup {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
     }
-    lineups_with_te
 }
 
-pub fn add_flex_find_top_num(
-    players: &Vec<LitePlayer>,
-    lineups: Vec<LineupBuilder>,
-    week: i8,
-    season: i16,
-) -> Option<LineupBuilder> {
-    let mut best_lineup: Option<LineupBuilder> = None;
-    let mut highest_score: f32 = 0.0;
-    for lineup in &lineups {
-        let exiting_players: [&i16; 5] = [
-            &lineup.rb1.as_ref().unwrap().id,
-            &lineup.rb2.as_ref().unwrap().id,
-            &lineup.wr1.as_ref().unwrap().id,
-            &lineup.wr2.as_ref().unwrap().id,
-            &lineup.wr3.as_ref().unwrap().id,
-        ];
+impl Eq for ScoredLineup {}
+
+impl PartialOrd for ScoredLineup {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ScoredLineup {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.score.total_cmp(&self.score)
+    }
+}
+
+/// The best `limit` lineups seen, each set of players kept once
+pub struct TopLineups {
+    limit: usize,
+    heap: BinaryHeap<ScoredLineup>,
+}
+
+impl TopLineups {
+    pub fn new(limit: usize) -> TopLineups {
+        TopLineups {
+            limit,
+            heap: BinaryHeap::with_capacity(limit + 1),
+        }
+    }
+
+    /// Score a lineup has to beat to get in, None until it's full
+    pub fn threshold(&self) -> Option<f32> {
+        if self.heap.len() < self.limit {
+            return None;
+        }
+        self.heap.peek().map(|l| l.score)
+    }
+
+    pub fn push(&mut self, score: f32, lineup: LineupBuilder) {
+        if self.limit == 0 || self.threshold().is_some_and(|t| score <= t) {
+            return;
+        }
+        let mut ids: [i16; 9] = lineup.array_of_players().map(|p| p.id);
+        ids.sort();
+        if self.heap.iter().any(|l| l.ids == ids) {
+            return;
+        }
+        if self.heap.len() == self.limit {
+            self.heap.pop();
+        }
+        self.heap.push(ScoredLineup { score, ids, lineup });
+    }
+
+    /// Best lineup first
+    pub fn into_sorted(self) -> Vec<LineupBuilder> {
+        self.heap
+            .into_sorted_vec()
+            .into_iter()
+            .map(|l| l.lineup)
+            .collect()
+    }
+}
+
+/// Pools for one WR combo, each sorted best score first
+struct ComboPools<'a> {
+    config: &'a SlateConfig,
+    locked: &'a [i16],
+    scorer: &'a SlateScorer,
+    qbs: Vec<LitePlayer>,
+    rbs: Vec<LitePlayer>,
+    tes: Vec<LitePlayer>,
+    defs: Vec<LitePlayer>,
+    flex: Vec<LitePlayer>,
+    // What is left to fill after each step
+    after_qb: SlotBounds,
+    after_rbs: SlotBounds,
+    after_te: SlotBounds,
+    after_def: SlotBounds,
+}
+
+struct ComboSearch<'a> {
+    pools: &'a ComboPools<'a>,
+    top: TopLineups,
+}
+
+/// Best `combo_top_k` lineups around three WRs, best first. Walks QB, RBs, TE,
+/// DST then flex without building every partial lineup, skipping branches that
+/// can't fit the salary range or beat the worst lineup kept so far.
+pub fn top_lineups_for_wrs(
+    wrs: &[LitePlayer],
+    players: &[LitePlayer],
+    config: &SlateConfig,
+    locked: &[i16],
+    scorer: &SlateScorer,
+) -> Vec<LineupBuilder> {
+    let by_pos = |pos: Pos| -> Vec<LitePlayer> {
+        players.iter().filter(|p| p.pos == pos).cloned().collect()
+    };
+    let flex: Vec<LitePlayer> = sort_by_score(
         players
             .iter()
-            .filter(|p| !exiting_players.contains(&&p.id))
-            .filter(|p| {
-                (p.salary as i32 + lineup.salary_used) < SALARY_CAP
-                    && (p.salary as i32 + lineup.salary_used) > MIN_SAL
-            })
-            .for_each(|flex| {
-                let finished_lineup = lineup.set_pos(&flex, Slot::Flex);
-                if finished_lineup.fits_own_brackets() {
-                    let score: f32 = finished_lineup.score();
-                    if best_lineup.is_none() {
-                        best_lineup = Some(finished_lineup);
-                        highest_score = score;
-                    } else if score > highest_score {
-                        highest_score = score;
-                        best_lineup = Some(finished_lineup)
-                    }
+            .filter(|p| config.site.flex_positions().contains(&p.pos))
+            .filter(|p| !wrs.iter().any(|wr| wr.id == p.id))
+            .cloned()
+            .collect(),
+    );
+    let qbs: Vec<LitePlayer> = sort_by_score(single_slot_pool(players, Pos::Qb, locked));
+    let rbs: Vec<LitePlayer> = sort_by_score(by_pos(Pos::Rb));
+    let tes: Vec<LitePlayer> = sort_by_score(single_slot_pool(players, Pos::Te, locked));
+    let defs: Vec<LitePlayer> = sort_by_score(single_slot_pool(players, Pos::D, locked));
+    let after_def: SlotBounds = SlotBounds::new(&flex, 1);
+    let after_te: SlotBounds = SlotBounds::new(&defs, 1).plus(after_def);
+    let after_rbs: SlotBounds = SlotBounds::new(&tes, 1).plus(after_te);
+    let after_qb: SlotBounds = SlotBounds::new(&rbs, 2).plus(after_rbs);
+    let pools: ComboPools = ComboPools {
+        config,
+        locked,
+        scorer,
+        qbs,
+        rbs,
+        tes,
+        defs,
+        flex,
+        after_qb,
+        after_rbs,
+        after_te,
+        after_def,
+    };
+    let lineup: LineupBuilder = LineupBuilder::new()
+        .set_pos(&wrs[0], Slot::First)
+        .set_pos(&wrs[1], Slot::Second)
+        .set_pos(&wrs[2], Slot::Third);
+    let mut search: ComboSearch = ComboSearch {
+        pools: &pools,
+        top: TopLineups::new(config.combo_top_k),
+    };
+    // Bounds count the most the lineup term can add
+    let score: f32 = wrs.iter().map(|wr| wr.score).sum::<f32>() + scorer.max_correlation();
+    search.add_qb(lineup, score);
+    search.top.into_sorted()
+}
+
+impl<'a> ComboSearch<'a> {
+    fn best_score(&self) -> Option<f32> {
+        self.top.threshold()
+    }
+
+    fn add_qb(&mut self, lineup: LineupBuilder, score: f32) {
+        let pools: &ComboPools = self.pools;
+        for qb in &pools.qbs {
+            if !pools.after_qb.can_beat(score + qb.score, self.best_score()) {
+                break;
+            }
+            let lineup: LineupBuilder = lineup.set_pos(qb, Slot::None);
+            if pools
+                .after_qb
+                .salary_in_reach(lineup.salary_used, pools.config)
+            {
+                self.add_rbs(lineup, score + qb.score);
+            }
+        }
+    }
+
+    fn add_rbs(&mut self, lineup: LineupBuilder, score: f32) {
+        let pools: &ComboPools = self.pools;
+        for (i, rb1) in pools.rbs.iter().enumerate() {
+            for rb2 in &pools.rbs[i + 1..] {
+                let rb_score: f32 = score + rb1.score + rb2.score;
+                if !pools.after_rbs.can_beat(rb_score, self.best_score()) {
+                    break;
+                }
+                // Scored as zero by LineupBuilder::score
+                if rb1.opp == rb2.team {
+                    continue;
                 }
-            });
+                let lineup: LineupBuilder =
+                    lineup.set_pos(rb1, Slot::First).set_pos(rb2, Slot::Second);
+                if pools
+                    .after_rbs
+                    .salary_in_reach(lineup.salary_used, pools.config)
+                {
+                    self.add_te(lineup, rb_score);
+                }
+            }
+        }
     }
-    best_lineup
-}
 
-pub fn add_dst_to_lineups(
-    players: &Vec<LitePlayer>,
-    lineups: Vec<LineupBuilder>,
-) -> Vec<LineupBuilder> {
-    let mut lineups_with_def: Vec<LineupBuilder> = Vec::new();
-    let mut iterations: i64 = 0;
-    for lineup in &lineups {
-        players.iter().for_each(|def| {
-            lineups_with_def.push(lineup.clone().set_pos(&def, Slot::None));
-            iterations += 1;
-        });
+    fn add_te(&mut self, lineup: LineupBuilder, score: f32) {
+        let pools: &ComboPools = self.pools;
+        for te in &pools.tes {
+            if !pools.after_te.can_beat(score + te.score, self.best_score()) {
+                break;
+            }
+            let lineup: LineupBuilder = lineup.set_pos(te, Slot::None);
+            if pools
+                .after_te
+                .salary_in_reach(lineup.salary_used, pools.config)
+            {
+                self.add_dst(lineup, score + te.score);
+            }
+        }
+    }
+
+    fn add_dst(&mut self, lineup: LineupBuilder, score: f32) {
+        let pools: &ComboPools = self.pools;
+        for def in &pools.defs {
+            if !pools
+                .after_def
+                .can_beat(score + def.score, self.best_score())
+            {
+                break;
+            }
+            if lineup.qb.unwrap().opp == def.team {
+                continue;
+            }
+            let lineup: LineupBuilder = lineup.set_pos(def, Slot::None);
+            if pools
+                .after_def
+                .salary_in_reach(lineup.salary_used, pools.config)
+            {
+                self.add_flex(lineup, score + def.score);
+            }
+        }
+    }
+
+    fn add_flex(&mut self, lineup: LineupBuilder, score: f32) {
+        let pools: &ComboPools = self.pools;
+        let taken_ids: [i16; 3] = [
+            lineup.rb1.unwrap().id,
+            lineup.rb2.unwrap().id,
+            lineup.te.unwrap().id,
+        ];
+        for flex in &pools.flex {
+            if !SlotBounds::default().can_beat(score + flex.score, self.best_score()) {
+                break;
+            }
+            let salary: i32 = lineup.salary_used + flex.salary as i32;
+            if taken_ids.contains(&flex.id)
+                || salary >= pools.config.salary_cap()
+                || salary <= pools.config.min_sal()
+            {
+                continue;
+            }
+            let finished_lineup: LineupBuilder = lineup.set_pos(flex, Slot::Flex);
+            if !finished_lineup.fits_own_brackets() || !finished_lineup.contains_all(pools.locked) {
+                continue;
+            }
+            self.top
+                .push(pools.scorer.lineup_score(&finished_lineup), finished_lineup);
+        }
     }
-    lineups_with_def
 }
 
-// TODO Test build lineup should be sorted and under salary cap.
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
+    use super::*;
+    use crate::scorer::ProjectionScorer;
+
+    const TEAMS: [Team; 8] = [
+        Team::Ari,
+        Team::Atl,
+        Team::Bal,
+        Team::Buf,
+        Team::Car,
+        Team::Chi,
+        Team::Cin,
+        Team::Cle,
+    ];
+    const OWNS: [f32; 8] = [1.0, 4.0, 8.0, 10.0, 15.0, 18.0, 3.0, 40.0];
+
+    /// Small made up slate that passes the ownership brackets
+    pub(crate) fn test_pool() -> Vec<LitePlayer> {
+        let counts: [(Pos, i16); 5] = [
+            (Pos::Qb, 3),
+            (Pos::D, 3),
+            (Pos::Te, 3),
+            (Pos::Rb, 5),
+            (Pos::Wr, 7),
+        ];
+        let mut players: Vec<LitePlayer> = Vec::new();
+        for (pos, count) in counts {
+            for _ in 0..count {
+                let id: i16 = players.len() as i16;
+                let team: usize = (id as usize * 3) % TEAMS.len();
+                players.push(LitePlayer {
+                    id,
+                    pos,
+                    salary: 3000 + (id * 733) % 5000,
+                    score: ((id * 37) % 19) as f32 / 10.0,
+                    own_proj: OWNS[id as usize % OWNS.len()],
+                    team: TEAMS[team],
+                    // Teams play in pairs, 0 vs 1, 2 vs 3...
+                    opp: TEAMS[team ^ 1],
+                });
+            }
+        }
+        players
+    }
+
+    /// Keeps the made up scores, there are no projections to look up
+    pub(crate) fn test_scorer() -> SlateScorer {
+        SlateScorer::new(Arc::new(ProjectionScorer), HashMap::new(), false)
+    }
+
+    pub(crate) fn test_config() -> SlateConfig {
+        SlateConfig {
+            salary_cap: Some(45000),
+            min_sal: Some(30000),
+            exact_top_n: 10,
+            ..SlateConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_pruned_matches_unpruned() {
+        let players: Vec<LitePlayer> = test_pool();
+        let config: SlateConfig = test_config();
+        let wrs: Vec<LitePlayer> = players
+            .iter()
+            .filter(|p| p.pos == Pos::Wr)
+            .cloned()
+            .collect();
+        for combo in wrs.into_iter().combinations(3) {
+            let expected: f32 =
+                unpruned_best_for_wrs(&combo, &players, &config, &[], &test_scorer());
+            let found: f32 = top_lineups_for_wrs(&combo, &players, &config, &[], &test_scorer())
+                .first()
+                .map(|l| l.score())
+                .unwrap_or(0.0);
+            assert!((expected - found).abs() < BOUND_SLACK);
+        }
+    }
+
+    #[test]
+    fn test_top_k_per_combo() {
+        let players: Vec<LitePlayer> = test_pool();
+        let config: SlateConfig = SlateConfig {
+            combo_top_k: 4,
+            ..test_config()
+        };
+        let wrs: Vec<LitePlayer> = players
+            .iter()
+            .filter(|p| p.pos == Pos::Wr)
+            .take(3)
+            .cloned()
+            .collect();
+        let lineups: Vec<LineupBuilder> =
+            top_lineups_for_wrs(&wrs, &players, &config, &[], &test_scorer());
+        let best: Vec<LineupBuilder> = top_lineups_for_wrs(
+            &wrs,
+            &players,
+            &SlateConfig {
+                combo_top_k: 1,
+                ..config.clone()
+            },
+            &[],
+            &test_scorer(),
+        );
+        assert_eq!(lineups.len(), 4);
+        assert_eq!(lineups[0].score(), best[0].score());
+        for pair in lineups.windows(2) {
+            assert!(pair[0].score() >= pair[1].score());
+        }
+        let sets: Vec<Vec<i16>> = lineups
+            .iter()
+            .map(|l| l.array_of_players().iter().map(|p| p.id).sorted().collect())
+            .collect();
+        assert_eq!(sets.iter().unique().count(), sets.len());
+    }
+
+    #[test]
+    fn test_top_lineups_keeps_best() {
+        let lineup: LineupBuilder = LineupBuilder::new();
+        let mut top: TopLineups = TopLineups::new(2);
+        assert_eq!(top.threshold(), None);
+        for (i, score) in [1.0, 3.0, 2.0, 0.5].into_iter().enumerate() {
+            let players: Vec<LitePlayer> = (0..9)
+                .map(|id| LitePlayer {
+                    id: id + 10 * i as i16,
+                    ..LitePlayer::test()
+                })
+                .collect();
+            let full: LineupBuilder = LineupBuilder {
+                qb: Some(players[0]),
+                rb1: Some(players[1]),
+                rb2: Some(players[2]),
+                wr1: Some(players[3]),
+                wr2: Some(players[4]),
+                wr3: Some(players[5]),
+                te: Some(players[6]),
+                flex: Some(players[7]),
+                def: Some(players[8]),
+                ..lineup
+            };
+            top.push(score, full);
+            // Same players again are ignored
+            top.push(score, full);
+        }
+        assert_eq!(top.threshold(), Some(2.0));
+        let sorted: Vec<LineupBuilder> = top.into_sorted();
+        assert_eq!(sorted.len(), 2);
+        assert_eq!(sorted[0].qb.unwrap().id, 10);
+    }
+
+    #[test]
+    fn test_slot_bounds() {
+        let players: Vec<LitePlayer> = test_pool();
+        let config: SlateConfig = test_config();
+        let bounds: SlotBounds = SlotBounds::new(&players, 2);
+        assert!(bounds.cheapest <= bounds.priciest);
+        assert!(bounds.salary_in_reach(config.min_sal() - bounds.priciest + 1, &config));
+        assert!(!bounds.salary_in_reach(config.min_sal() - bounds.priciest, &config));
+        assert!(!bounds.salary_in_reach(config.salary_cap() - bounds.cheapest, &config));
+        assert!(bounds.can_beat(0.0, None));
+        assert!(!bounds.can_beat(0.0, Some(bounds.max_score + 1.0)));
+    }
+
+    #[test]
+    fn test_single_slot_pool() {
+        let qb: LitePlayer = LitePlayer {
+            pos: Pos::Qb,
+            ..LitePlayer::test()
+        };
+        let players: Vec<LitePlayer> = vec![qb, LitePlayer { id: 2, ..qb }, LitePlayer::test()];
+        assert_eq!(single_slot_pool(&players, Pos::Qb, &[]).len(), 2);
+        let locked: Vec<LitePlayer> = single_slot_pool(&players, Pos::Qb, &[2]);
+        assert_eq!(locked.len(), 1);
+        assert_eq!(locked[0].id, 2);
+        // A locked RB doesn't narrow the QB pool
+        assert_eq!(single_slot_pool(&players, Pos::Qb, &[1]).len(), 1);
+    }
 
-    // use super::*;
     // Helper function for creating line ups
     // fn create_test_lineup(price: i32) -> Lineup {
     //     let conn: Connection = Connection::open(DATABASE_FILE).expect("Failed to open DB");
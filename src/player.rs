@@ -6,8 +6,12 @@ use std::{collections::HashMap, hash::Hash};
 use rusqlite::{params, Connection, OptionalExtension};
 use serde::{Deserialize, Serialize};
 
+use crate::config::slate_config;
 use crate::data_loader::*;
 use crate::lineup::score_player;
+use crate::portfolio::Exposure;
+use crate::site::Site;
+use crate::Day;
 
 // TODO! Should populate all of these first so read writes are not blocked
 lazy_static! {
@@ -16,10 +20,12 @@ lazy_static! {
     pub static ref QB_PROJ_CACHE: RwLock<HashMap<i16, QbProj>> = RwLock::new(HashMap::new());
     pub static ref DEF_PROJ_CACHE: RwLock<HashMap<i16, DefProj>> = RwLock::new(HashMap::new());
     pub static ref KICK_PROJ_CACHE: RwLock<HashMap<i16, KickProj>> = RwLock::new(HashMap::new());
-    pub static ref DEF_VS_QB_CACHE: RwLock<HashMap<Team, DefVsPos>> = RwLock::new(HashMap::new());
-    pub static ref DEF_VS_RB_CACHE: RwLock<HashMap<Team, DefVsPos>> = RwLock::new(HashMap::new());
-    pub static ref DEF_VS_WR_CACHE: RwLock<HashMap<Team, DefVsPos>> = RwLock::new(HashMap::new());
-    pub static ref DEF_VS_TE_CACHE: RwLock<HashMap<Team, DefVsPos>> = RwLock::new(HashMap::new());
+    pub static ref DEF_VS_QB_CACHE: RwLock<HashMap<(Team, i16, i8), DefVsPos>> = RwLock::new(HashMap::new());
+    pub static ref DEF_VS_RB_CACHE: RwLock<HashMap<(Team, i16, i8), DefVsPos>> = RwLock::new(HashMap::new());
+    pub static ref DEF_VS_WR_CACHE: RwLock<HashMap<(Team, i16, i8), DefVsPos>> = RwLock::new(HashMap::new());
+    pub static ref DEF_VS_TE_CACHE: RwLock<HashMap<(Team, i16, i8), DefVsPos>> = RwLock::new(HashMap::new());
+    pub static ref GAME_ENV_CACHE: RwLock<HashMap<Team, GameEnv>> = RwLock::new(HashMap::new());
+    pub static ref PLAYER_SHARE_CACHE: RwLock<HashMap<i16, PlayerShare>> = RwLock::new(HashMap::new());
     pub static ref DEF_ID_CACHE: RwLock<HashMap<Team, i16>> = RwLock::new(HashMap::new());
     pub static ref PLAYER_NAME_CACHE: RwLock<HashMap<i16, String>> = RwLock::new(HashMap::new());
     /// ID = name-pos-team
@@ -68,13 +74,18 @@ pub enum Team {
 
 impl Team {
     pub fn from_str(team_abv: &String) -> Self {
-        let no_at: &str = &team_abv.replace("@", "");
-        match no_at {
+        Self::try_from_str(team_abv).expect("Not a team")
+    }
+
+    pub fn try_from_str(team_abv: &str) -> Option<Self> {
+        let no_at: &str = &team_abv.trim().replace("@", "");
+        let team: Team = match no_at {
             "LAC" => Self::Lac,
             "PHI" => Self::Phi,
             "CHI" => Self::Chi,
             "MIA" => Self::Mia,
             "JAX" => Self::Jax,
+            "JAC" => Self::Jax,
             "CIN" => Self::Cin,
             "DET" => Self::Det,
             "IND" => Self::Ind,
@@ -94,6 +105,7 @@ impl Team {
             "BLT" => Self::Bal,
             "SF" => Self::Sf,
             "LA" => Self::La,
+            "LAR" => Self::La,
             "NYJ" => Self::Nyj,
             "BUF" => Self::Buf,
             "CAR" => Self::Car,
@@ -106,8 +118,10 @@ impl Team {
             "CLV" => Self::Cle,
             "NO" => Self::No,
             "WAS" => Self::Was,
-            _ => panic!("Not a team"),
-        }
+            "WSH" => Self::Was,
+            _ => return None,
+        };
+        Some(team)
     }
 
     pub fn to_str(&self) -> &str {
@@ -215,6 +229,8 @@ pub struct QbProj {
     pub yds_per_pass_att: f32,
     pub opp_def_pts_given: f32,
     pub avg_rush_yards: f32,
+    /// Score multiplier for the game's weather
+    pub weather: f32,
 }
 
 #[derive(Clone, Debug, Default, Copy)]
@@ -246,6 +262,8 @@ pub struct RecProj {
     pub month_consistency: f32,
     pub month_upside: f32,
     pub opp_def_pts_given: f32,
+    /// Score multiplier for the game's weather
+    pub weather: f32,
 }
 
 #[derive(Debug, Clone, Default, Copy)]
@@ -265,6 +283,8 @@ pub struct DefProj {
     pub rating: f32,
     pub vegas_opp_total: f32,
     pub vegas_team_total: f32,
+    /// Score multiplier for the game's weather
+    pub weather: f32,
 }
 
 #[derive(Debug, Clone, Default, Copy)]
@@ -282,6 +302,8 @@ pub struct KickProj {
     pub salary: i32,
     pub own_proj: f32,
     pub rating: f32,
+    /// Score multiplier for the game's weather
+    pub weather: f32,
 }
 
 #[derive(Debug, Clone, Default, Copy)]
@@ -290,6 +312,44 @@ pub struct DefVsPos {
     // pub team_name: String,
     pub pts_given_pg: f32,
     pub pos: Pos,
+    /// The slate week this was looked up for
+    pub season: i16,
+    pub week: i8,
+}
+
+/// Vegas lines and weather for one side of a game
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GameEnv {
+    pub team: Team,
+    pub opp: Team,
+    /// Negative when the team is favored
+    pub spread: f32,
+    pub total: f32,
+    pub team_total: f32,
+    pub opp_total: f32,
+    pub temperature: f32,
+    pub wind_speed: f32,
+    /// Chance of rain or snow, 0-100
+    pub precip_per: f32,
+}
+
+/// Per player usage from the projection file, zero when the file doesn't have it
+#[derive(Debug, Clone, Default, Copy, PartialEq)]
+pub struct PlayerShare {
+    pub id: i16,
+    pub imp_pts_proj: f32,
+    pub snaps_share: f32,
+    pub rec_tgt_share: f32,
+    pub rec_td_tgt_share: f32,
+    pub rec_yds_share: f32,
+    pub rush_td_share: f32,
+    pub rush_yds_share: f32,
+    pub pass_to_rb_per: f32,
+    pub proj_rush_yds: f32,
+    pub yds_per_carry: f32,
+    pub yds_per_tgt: f32,
+    pub yds_per_rec: f32,
+    pub avg_long_rec_yds: f32,
 }
 
 // Should be Enum will reduce code
@@ -349,6 +409,27 @@ impl Proj {
             Proj::KickProj(k) => return k.own_proj,
         }
     }
+    pub fn get_pts_proj(&self) -> f32 {
+        match self {
+            Proj::QbProj(qb) => qb.pts_proj,
+            Proj::DefProj(def) => def.pts_proj,
+            Proj::RecProj(rec) => rec.pts_proj,
+            Proj::RbProj(rb) => rb.pts_proj,
+            Proj::KickProj(k) => k.pts_proj,
+        }
+    }
+
+    /// (cieling, floor) of the projection
+    pub fn get_cieling_floor(&self) -> (f32, f32) {
+        match self {
+            Proj::QbProj(qb) => (qb.cieling_proj, qb.floor_proj),
+            Proj::DefProj(def) => (def.cieling_proj, def.floor_proj),
+            Proj::RecProj(rec) => (rec.cieling_proj, rec.floor_proj),
+            Proj::RbProj(rb) => (rb.cieling_proj, rb.floor_proj),
+            Proj::KickProj(k) => (k.cieling_proj, k.floor_proj),
+        }
+    }
+
     pub fn get_id(&self) -> i16 {
         match self {
             Proj::QbProj(qb) => return qb.id,
@@ -394,7 +475,7 @@ impl Proj {
     }
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Copy)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, Hash, Copy)]
 pub enum Pos {
     Qb = 0,
     Rb = 1,
@@ -511,6 +592,19 @@ pub fn get_player_name(id: i16, conn: &Connection) -> String {
     player
 }
 
+/// Empties the caches keyed by player or team alone, they only hold one week
+pub fn clear_proj_caches() {
+    REC_PROJ_CACHE.write().unwrap().clear();
+    RB_PROJ_CACHE.write().unwrap().clear();
+    QB_PROJ_CACHE.write().unwrap().clear();
+    DEF_PROJ_CACHE.write().unwrap().clear();
+    KICK_PROJ_CACHE.write().unwrap().clear();
+    GAME_ENV_CACHE.write().unwrap().clear();
+    PLAYER_SHARE_CACHE.write().unwrap().clear();
+    ID_LITEPLAYER_CACHE.write().unwrap().clear();
+    ID_LITEPLAYER_NO_SAL_CACHE.write().unwrap().clear();
+}
+
 pub fn get_past_score(week: i8, id: i16, season: i16, conn: &Connection) -> f32 {
     let query = "SELECT pts FROM fan_pts WHERE week = ?1 AND id = ?2 AND season = ?3";
     let mut stmt = conn.prepare_cached(query).unwrap();
@@ -520,6 +614,93 @@ pub fn get_past_score(week: i8, id: i16, season: i16, conn: &Connection) -> f32
     score
 }
 
+/// Finds the ids for a lock or exclude list
+pub fn resolve_player_refs(refs: &[String], conn: &Connection) -> Result<Vec<i16>, String> {
+    refs.iter().map(|r| resolve_player_ref(r, conn)).collect()
+}
+
+/// A player id or `Name-TEAM`, names can have dashes so split on the last one
+fn resolve_player_ref(player_ref: &str, conn: &Connection) -> Result<i16, String> {
+    if let Ok(id) = player_ref.parse::<i16>() {
+        return Ok(id);
+    }
+    let (name, team) = player_ref
+        .rsplit_once('-')
+        .ok_or(format!("Expected a player id or Name-TEAM: {}", player_ref))?;
+    conn.query_row(
+        "SELECT id FROM player WHERE name = ?1 AND team = ?2",
+        (name.trim(), team.trim().to_uppercase()),
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(|e| e.to_string())?
+    .ok_or(format!("No player found for {}", player_ref))
+}
+
+/// Exposure percentages from the projection files keyed by player id
+pub fn get_exposures(
+    season: i16,
+    week: i8,
+    day: &Day,
+    conn: &Connection,
+) -> HashMap<i16, Exposure> {
+    let mut exposures: HashMap<i16, Exposure> = HashMap::new();
+    for pos in [Pos::Qb, Pos::Rb, Pos::Wr, Pos::Te, Pos::D] {
+        let mut stmt = conn
+            .prepare_cached(
+                format!(
+                    "SELECT id, exposure_min, exposure_max FROM {}
+                    WHERE season = ?1 AND week = ?2 AND day = ?3",
+                    pos.get_proj_table()
+                )
+                .as_str(),
+            )
+            .unwrap();
+        stmt.query_map((season, week, day.to_str()), |row| {
+            Ok((
+                row.get(0)?,
+                Exposure {
+                    min: row.get(1)?,
+                    max: row.get(2)?,
+                },
+            ))
+        })
+        .unwrap()
+        .for_each(|r| {
+            let (id, exposure): (i16, Exposure) = r.unwrap();
+            exposures.insert(id, exposure);
+        });
+    }
+    exposures
+}
+
+/// Site player ids keyed by player id, from the site's salary file or the
+/// projection files when no salary file is loaded for the slate
+pub fn get_site_ids(
+    season: i16,
+    week: i8,
+    day: &Day,
+    site: Site,
+    conn: &Connection,
+) -> HashMap<i16, String> {
+    let mut stmt = conn
+        .prepare_cached(
+            "SELECT id, site_id FROM slate
+            WHERE season = ?1 AND week = ?2 AND day = ?3 AND site = ?4
+            UNION ALL
+            SELECT id, site_id FROM ownership
+            WHERE season = ?1 AND week = ?2 AND day = ?3 AND site_id IS NOT NULL
+            AND NOT EXISTS (SELECT 1 FROM slate WHERE season = ?1 AND week = ?2 AND day = ?3)",
+        )
+        .unwrap();
+    stmt.query_map((season, week, day.to_str(), site.to_str()), |row| {
+        Ok((row.get(0)?, row.get(1)?))
+    })
+    .unwrap()
+    .map(|r| r.unwrap())
+    .collect()
+}
+
 pub fn get_player_by_id(
     week: i8,
     id: i16,
@@ -603,23 +784,12 @@ impl LitePlayer {
 }
 
 fn add_def_to_cache(def_vs_pos: DefVsPos) {
+    let key: (Team, i16, i8) = (def_vs_pos.team, def_vs_pos.season, def_vs_pos.week);
     match def_vs_pos.pos {
-        Pos::Qb => DEF_VS_QB_CACHE
-            .write()
-            .unwrap()
-            .insert(def_vs_pos.team, def_vs_pos),
-        Pos::Rb => DEF_VS_RB_CACHE
-            .write()
-            .unwrap()
-            .insert(def_vs_pos.team, def_vs_pos),
-        Pos::Wr => DEF_VS_WR_CACHE
-            .write()
-            .unwrap()
-            .insert(def_vs_pos.team, def_vs_pos),
-        Pos::Te => DEF_VS_TE_CACHE
-            .write()
-            .unwrap()
-            .insert(def_vs_pos.team, def_vs_pos),
+        Pos::Qb => DEF_VS_QB_CACHE.write().unwrap().insert(key, def_vs_pos),
+        Pos::Rb => DEF_VS_RB_CACHE.write().unwrap().insert(key, def_vs_pos),
+        Pos::Wr => DEF_VS_WR_CACHE.write().unwrap().insert(key, def_vs_pos),
+        Pos::Te => DEF_VS_TE_CACHE.write().unwrap().insert(key, def_vs_pos),
         _ => panic!("No Def Vs Pos"),
     };
 }
@@ -639,60 +809,103 @@ pub fn query_def_id(opp: &Team, conn: &Connection) -> Result<i16, rusqlite::Erro
 // TODO most expensive query
 // This should just be team to avoid ID lookup
 // and then if it's missing we get id by team!
-pub fn query_def_vs_pos(opp: Team, player_pos: &Pos, conn: &Connection) -> DefVsPos {
+/// Points the defense gave up going into `week`, the latest load at or before
+/// it so a backtest never sees later weeks. None when nothing was loaded for
+/// the defense by then, see `def_pts_given`.
+pub fn query_def_vs_pos(
+    opp: Team,
+    player_pos: &Pos,
+    week: i8,
+    season: i16,
+    conn: &Connection,
+) -> Option<DefVsPos> {
+    let key: (Team, i16, i8) = (opp, season, week);
     let cache_hit: Option<DefVsPos> = match player_pos {
-        Pos::Qb => {
-            if DEF_VS_QB_CACHE.read().unwrap().get(&opp).is_some() {
-                Some(*DEF_VS_QB_CACHE.read().unwrap().get(&opp).unwrap())
-            } else {
-                None
-            }
-        }
-        Pos::Rb => {
-            if DEF_VS_RB_CACHE.read().unwrap().get(&opp).is_some() {
-                Some(*DEF_VS_RB_CACHE.read().unwrap().get(&opp).unwrap())
-            } else {
-                None
-            }
-        }
-        Pos::Wr => {
-            if DEF_VS_WR_CACHE.read().unwrap().get(&opp).is_some() {
-                Some(*DEF_VS_WR_CACHE.read().unwrap().get(&opp).unwrap())
-            } else {
-                None
-            }
-        }
-        Pos::Te => {
-            if DEF_VS_TE_CACHE.read().unwrap().get(&opp).is_some() {
-                Some(*DEF_VS_TE_CACHE.read().unwrap().get(&opp).unwrap())
-            } else {
-                None
-            }
-        }
+        Pos::Qb => DEF_VS_QB_CACHE.read().unwrap().get(&key).copied(),
+        Pos::Rb => DEF_VS_RB_CACHE.read().unwrap().get(&key).copied(),
+        Pos::Wr => DEF_VS_WR_CACHE.read().unwrap().get(&key).copied(),
+        Pos::Te => DEF_VS_TE_CACHE.read().unwrap().get(&key).copied(),
         _ => panic!("No Def Vs For Pos for that Pos"),
     };
 
     if cache_hit.is_some() {
-        return cache_hit.unwrap().to_owned();
+        return cache_hit;
     }
-    let id = query_def_id(&opp, conn).unwrap();
+    let id: i16 = query_def_id(&opp, conn).ok()?;
     let mut stmt = conn
         .prepare_cached(
-            format!("SELECT * FROM {} WHERE id = ?1", player_pos.get_def_table()).as_str(),
+            format!(
+                "SELECT pts_given_pg FROM {} WHERE id = ?1 AND season = ?2 AND week <= ?3
+                ORDER BY week DESC LIMIT 1",
+                player_pos.get_def_table()
+            )
+            .as_str(),
         )
         .unwrap();
     let def_vs_pos: DefVsPos = stmt
-        .query_row(params![id], |row| {
+        .query_row(params![id, season, week], |row| {
             Ok(DefVsPos {
                 team: opp,
-                // team_name: row.get(1).unwrap(),
-                pts_given_pg: row.get(2).unwrap(),
+                pts_given_pg: row.get(0)?,
                 pos: *player_pos,
+                season,
+                week,
             })
         })
-        .unwrap();
+        .optional()
+        .unwrap()?;
     add_def_to_cache(def_vs_pos);
-    def_vs_pos
+    Some(def_vs_pos)
+}
+
+/// Points the defense gave up, or the average over the defenses with numbers by
+/// `week` when it has none so the matchup neither helps nor hurts. 0 when no
+/// defense has any, `missing_def_vs_pos` lists the teams for the CLI to report.
+pub fn def_pts_given(opp: Team, player_pos: &Pos, week: i8, season: i16, conn: &Connection) -> f32 {
+    if let Some(def_vs_pos) = query_def_vs_pos(opp, player_pos, week, season, conn) {
+        return def_vs_pos.pts_given_pg;
+    }
+    let mut stmt = conn
+        .prepare_cached(
+            format!(
+                "SELECT AVG(pts_given_pg) FROM {table} WHERE season = ?1 AND week = (
+                    SELECT MAX(week) FROM {table} WHERE season = ?1 AND week <= ?2)",
+                table = player_pos.get_def_table()
+            )
+            .as_str(),
+        )
+        .unwrap();
+    let average: Option<f32> = stmt.query_row((season, week), |row| row.get(0)).unwrap();
+    average.unwrap_or(0.0)
+}
+
+/// Slate opponents with no def vs pos numbers by the slate's week, as `TEAM vs POS`
+pub fn missing_def_vs_pos(season: i16, week: i8, day: &Day, conn: &Connection) -> Vec<String> {
+    let mut missing: Vec<String> = Vec::new();
+    for pos in [Pos::Qb, Pos::Rb, Pos::Wr, Pos::Te] {
+        let mut stmt = conn
+            .prepare_cached(
+                "SELECT DISTINCT opp FROM ownership
+                WHERE season = ?1 AND week = ?2 AND day = ?3 AND pos = ?4 ORDER BY opp",
+            )
+            .unwrap();
+        let opps: Vec<String> = stmt
+            .query_map((season, week, day.to_str(), pos.to_str().unwrap()), |row| {
+                row.get(0)
+            })
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        for opp in opps {
+            let found: bool = Team::try_from_str(&opp)
+                .and_then(|team| query_def_vs_pos(team, &pos, week, season, conn))
+                .is_some();
+            if !found {
+                missing.push(format!("{} vs {}", opp, pos.to_str().unwrap()));
+            }
+        }
+    }
+    missing
 }
 
 pub fn query_proj_helper(id: i16, pos: &Pos, week: i8, season: i16, conn: &Connection) -> Proj {
@@ -826,10 +1039,11 @@ pub fn query_kick_proj(id: i16, week: i8, season: i16, conn: &Connection) -> Opt
         .expect("Couldn't prepare query");
     let kick_proj: Option<KickProj> = query
         .query_row((id, week, season), |row| {
+            let team: Team = Team::from_str(&row.get(4)?);
             Ok(KickProj {
                 // name: row.get(3)?,
                 id: row.get(0)?,
-                team: Team::from_str(&row.get(4)?),
+                team,
                 opp: Team::from_str(&row.get(5)?),
                 pts_proj: row.get(6)?,
                 cieling_proj: row.get(7)?,
@@ -840,6 +1054,7 @@ pub fn query_kick_proj(id: i16, week: i8, season: i16, conn: &Connection) -> Opt
                 salary: row.get(12)?,
                 own_proj: row.get(13)?,
                 rating: row.get(14)?,
+                weather: weather_multiplier(&team, &Pos::K, week, season, conn),
             })
         })
         .optional()
@@ -891,10 +1106,11 @@ pub fn query_rec_proj(
     let rec_proj: Option<RecProj> = query
         .query_row((id, week, season), |row| {
             let opp: Team = Team::from_str(&row.get(5)?);
+            let team: Team = Team::from_str(&row.get(4)?);
             Ok(RecProj {
                 id: row.get(0)?,
                 // name: row.get(3)?,
-                team: Team::from_str(&row.get(4)?),
+                team,
                 opp: opp, // 5
                 pos: *pos,
                 pts_proj: row.get(6)?,
@@ -919,7 +1135,8 @@ pub fn query_rec_proj(
                 month_consistency: row.get(25)?,
                 month_upside: row.get(26)?,
                 // Day 27
-                opp_def_pts_given: query_def_vs_pos(opp, pos, &conn).pts_given_pg,
+                opp_def_pts_given: def_pts_given(opp, pos, week, season, conn),
+                weather: weather_multiplier(&team, pos, week, season, conn),
             })
         })
         .optional()
@@ -978,7 +1195,7 @@ pub fn query_rb_proj(id: i16, week: i8, season: i16, conn: &Connection) -> Optio
                 vegas_team_total: row.get(22)?,
                 month_consistency: row.get(23)?,
                 // Day 24
-                opp_def_pts_given: query_def_vs_pos(opp, &Pos::Rb, &conn).pts_given_pg,
+                opp_def_pts_given: def_pts_given(opp, &Pos::Rb, week, season, conn),
             })
         })
         .optional()
@@ -1010,11 +1227,12 @@ pub fn query_qb_proj(id: i16, week: i8, season: i16, conn: &Connection) -> Optio
     let qb_proj: Option<QbProj> = query
         .query_row((id, week, season), |row| {
             let opp = Team::from_str(&row.get(5)?);
+            let team: Team = Team::from_str(&row.get(4)?);
 
             Ok(QbProj {
                 id: row.get(0)?,
                 // name: row.get(3)?,
-                team: Team::from_str(&row.get(4)?),
+                team,
                 opp: opp, // 5
                 pts_proj: row.get(6)?,
                 cieling_proj: row.get(7)?,
@@ -1039,8 +1257,9 @@ pub fn query_qb_proj(id: i16, week: i8, season: i16, conn: &Connection) -> Optio
                 month_consistency: row.get(26)?,
                 yds_per_pass_att: row.get(27)?,
                 // Day 28
-                opp_def_pts_given: query_def_vs_pos(opp, &Pos::Qb, &conn).pts_given_pg,
+                opp_def_pts_given: def_pts_given(opp, &Pos::Qb, week, season, conn),
                 avg_rush_yards: row.get(29)?,
+                weather: weather_multiplier(&team, &Pos::Qb, week, season, conn),
             })
         })
         .optional()
@@ -1071,10 +1290,11 @@ pub fn query_def_proj(id: i16, week: i8, season: i16, conn: &Connection) -> Opti
         .expect("Couldn't prepare query");
     let def_proj: Option<DefProj> = query
         .query_row((id, week, season), |row| {
+            let team: Team = Team::from_str(&row.get(4)?);
             Ok(DefProj {
                 id: row.get(0)?,
                 // name: row.get(3)?,
-                team: Team::from_str(&row.get(4)?),
+                team,
                 opp: Team::from_str(&row.get(5)?),
                 pts_proj: row.get(6)?,
                 cieling_proj: row.get(7)?,
@@ -1088,6 +1308,7 @@ pub fn query_def_proj(id: i16, week: i8, season: i16, conn: &Connection) -> Opti
                 vegas_opp_total: row.get(15)?,
                 // Day 16
                 vegas_team_total: row.get(17)?,
+                weather: weather_multiplier(&team, &Pos::D, week, season, conn),
             })
         })
         .optional()
@@ -1102,15 +1323,88 @@ pub fn query_def_proj(id: i16, week: i8, season: i16, conn: &Connection) -> Opti
     def_proj
 }
 
+pub fn query_game_env(team: &Team, week: i8, season: i16, conn: &Connection) -> Option<GameEnv> {
+    if let Some(env) = GAME_ENV_CACHE.read().unwrap().get(team) {
+        return Some(*env);
+    }
+    let mut query = conn
+        .prepare_cached(
+            "SELECT opp, spread, total, team_total, opp_total, temperature, wind_speed, precip_per
+            FROM game_env WHERE team = ?1 AND week = ?2 AND season = ?3",
+        )
+        .expect("Couldn't prepare query");
+    let game_env: GameEnv = query
+        .query_row((team.to_str(), week, season), |row| {
+            Ok(GameEnv {
+                team: *team,
+                opp: Team::from_str(&row.get(0)?),
+                spread: row.get(1)?,
+                total: row.get(2)?,
+                team_total: row.get(3)?,
+                opp_total: row.get(4)?,
+                temperature: row.get(5)?,
+                wind_speed: row.get(6)?,
+                precip_per: row.get(7)?,
+            })
+        })
+        .optional()
+        .unwrap()?;
+    GAME_ENV_CACHE.write().unwrap().insert(*team, game_env);
+    Some(game_env)
+}
+
+pub fn query_player_share(
+    id: i16,
+    week: i8,
+    season: i16,
+    conn: &Connection,
+) -> Option<PlayerShare> {
+    if let Some(share) = PLAYER_SHARE_CACHE.read().unwrap().get(&id) {
+        return Some(*share);
+    }
+    let mut query = conn
+        .prepare_cached("SELECT * FROM player_share WHERE id = ?1 AND week = ?2 AND season = ?3")
+        .expect("Couldn't prepare query");
+    let share: PlayerShare = query
+        .query_row((id, week, season), |row| {
+            Ok(PlayerShare {
+                id: row.get(0)?,
+                // Season 1, week 2
+                imp_pts_proj: row.get(3)?,
+                snaps_share: row.get(4)?,
+                rec_tgt_share: row.get(5)?,
+                rec_td_tgt_share: row.get(6)?,
+                rec_yds_share: row.get(7)?,
+                rush_td_share: row.get(8)?,
+                rush_yds_share: row.get(9)?,
+                pass_to_rb_per: row.get(10)?,
+                proj_rush_yds: row.get(11)?,
+                yds_per_carry: row.get(12)?,
+                yds_per_tgt: row.get(13)?,
+                yds_per_rec: row.get(14)?,
+                avg_long_rec_yds: row.get(15)?,
+            })
+        })
+        .optional()
+        .unwrap()?;
+    PLAYER_SHARE_CACHE.write().unwrap().insert(id, share);
+    Some(share)
+}
+
+/// From the installed config's weather rules
+fn weather_multiplier(team: &Team, pos: &Pos, week: i8, season: i16, conn: &Connection) -> f32 {
+    let env: Option<GameEnv> = query_game_env(team, week, season, conn);
+    slate_config().weather.multiplier(env.as_ref(), pos)
+}
+
 pub fn get_player_id_create_if_missing(
     name: &String,
     team: &Team,
     pos: &Pos,
     conn: &Connection,
-) -> i16 {
-    let id: Option<i16> = get_player_id(name, team, pos, conn);
-    if id.is_some() {
-        return id.unwrap();
+) -> Result<i16, String> {
+    if let Some(id) = get_player_id(name, team, pos, conn)? {
+        return Ok(id);
     }
     let player: Player = Player {
         id: 0,
@@ -1132,29 +1426,28 @@ pub fn proj_exists(id: i16, week: i8, season: i16, pos: Pos, conn: &Connection)
     }
 }
 
-pub fn get_def_id(team: &Team, conn: &Connection) -> Option<i16> {
+pub fn get_def_id(team: &Team, conn: &Connection) -> Result<Option<i16>, String> {
     let select_player: &str = "SELECT id FROM player WHERE pos = ?1 AND team = ?2";
-    let id: Option<i16> = conn
-        .query_row(
-            select_player,
-            (Pos::D.to_str().unwrap(), team.to_str()),
-            |row| row.get(0),
-        )
-        .optional()
-        .unwrap();
-    if id.is_some() {
-        return id;
-    } else {
-        panic!("No Defense Id!")
-    }
+    conn.query_row(
+        select_player,
+        (Pos::D.to_str().unwrap(), team.to_str()),
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(|e| e.to_string())
 }
 
 // Get Player ID, Searches D, Then Exact, Then Fuzzy
-pub fn get_player_id(name: &String, team: &Team, pos: &Pos, conn: &Connection) -> Option<i16> {
+pub fn get_player_id(
+    name: &String,
+    team: &Team,
+    pos: &Pos,
+    conn: &Connection,
+) -> Result<Option<i16>, String> {
     // Try Exact Match
     let key = format!("{}-{}-{}", name, team.to_str(), pos.to_str().unwrap());
     if PLAYER_ID_CACHE.read().unwrap().get(&key).is_some() {
-        return Some(*PLAYER_ID_CACHE.read().unwrap().get(&key).unwrap());
+        return Ok(Some(*PLAYER_ID_CACHE.read().unwrap().get(&key).unwrap()));
     }
 
     let select_player: &str = "SELECT id FROM player WHERE name = ?1 AND pos = ?2 AND team = ?3";
@@ -1165,18 +1458,22 @@ pub fn get_player_id(name: &String, team: &Team, pos: &Pos, conn: &Connection) -
             |row| row.get(0),
         )
         .optional()
-        .unwrap();
+        .map_err(|e| e.to_string())?;
     if id.is_some() {
         PLAYER_ID_CACHE.write().unwrap().insert(key, id.unwrap());
-        return id;
+        return Ok(id);
     }
 
     // No hit on exact match
     let fuzzy_select: &str = "SELECT id FROM player WHERE name LIKE ?1 and pos = ?2 AND team = ?3";
     let mut name_split: Split<'_, &str> = name.trim().split(" ");
-    let first_name: &str = name_split.next().unwrap();
-    let last_name: &str = name_split.next().unwrap();
-    let fuzzy_name: String = first_name.chars().nth(0).unwrap().to_string() + "%" + last_name + "%";
+    let initial: Option<char> = name_split.next().and_then(|first| first.chars().next());
+    // Single word names only get the exact match
+    let (initial, last_name) = match (initial, name_split.next()) {
+        (Some(initial), Some(last_name)) => (initial, last_name),
+        _ => return Ok(None),
+    };
+    let fuzzy_name: String = initial.to_string() + "%" + last_name + "%";
 
     let id: Option<i16> = conn
         .query_row(
@@ -1185,23 +1482,248 @@ pub fn get_player_id(name: &String, team: &Team, pos: &Pos, conn: &Connection) -
             |row| row.get(0),
         )
         .optional()
-        .unwrap();
+        .map_err(|e| e.to_string())?;
     if id.is_some() {
         PLAYER_ID_CACHE.write().unwrap().insert(key, id.unwrap());
     }
-    id
+    Ok(id)
+}
+
+/// Most edits between two normalized names that still count as a match
+pub const MAX_NAME_DISTANCE: usize = 2;
+const NAME_SUFFIXES: [&str; 6] = ["jr", "sr", "ii", "iii", "iv", "v"];
+
+/// Lowercase without punctuation or suffixes, `D.J. Moore Jr.` is `dj moore`
+pub fn normalize_name(name: &str) -> String {
+    let cleaned: String = name
+        .to_lowercase()
+        .chars()
+        .filter(|c| !matches!(c, '.' | '\'' | ','))
+        .map(|c| if c == '-' { ' ' } else { c })
+        .collect();
+    cleaned
+        .split_whitespace()
+        .filter(|word| !NAME_SUFFIXES.contains(word))
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut diagonal: usize = row[0];
+        row[0] = i + 1;
+        for j in 0..b.len() {
+            let above: usize = row[j + 1];
+            row[j + 1] = if a_char == b[j] {
+                diagonal
+            } else {
+                1 + diagonal.min(above).min(row[j])
+            };
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
+
+// `c mccaffrey` against `christian mccaffrey`
+fn initial_matches(short: &str, full: &str) -> bool {
+    match (short.split_once(' '), full.split_once(' ')) {
+        (Some((initial, rest)), Some((first, full_rest))) => {
+            initial.len() == 1 && first.starts_with(initial) && rest == full_rest
+        }
+        _ => false,
+    }
+}
+
+/// A name that matched more than one player
+#[derive(Debug, Clone, PartialEq)]
+pub struct AmbiguousName {
+    pub name: String,
+    pub pos: Pos,
+    /// Id, name and team of each player it could be
+    pub candidates: Vec<(i16, String, String)>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum NameMatch {
+    Found(i16),
+    Ambiguous(AmbiguousName),
+    NotFound,
+}
+
+/// Looks a name up by exact match, then saved aliases, then normalized name,
+/// first initial and finally edit distance. The closest kind of match wins and
+/// a player on the given team wins over one that changed teams.
+pub fn match_player(
+    name: &str,
+    team: &Team,
+    pos: &Pos,
+    conn: &Connection,
+) -> Result<NameMatch, String> {
+    if let Some(id) = get_player_id(&name.to_string(), team, pos, conn)? {
+        return Ok(NameMatch::Found(id));
+    }
+    let normalized: String = normalize_name(name);
+    let alias: Option<i16> = conn
+        .query_row(
+            "SELECT id FROM player_alias WHERE alias = ?1 AND pos = ?2",
+            (&normalized, pos.to_str().unwrap()),
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    if let Some(id) = alias {
+        return Ok(NameMatch::Found(id));
+    }
+    let mut stmt = conn
+        .prepare_cached("SELECT id, name, team FROM player WHERE pos = ?1")
+        .map_err(|e| e.to_string())?;
+    let players: Vec<(i16, String, String)> = stmt
+        .query_map((pos.to_str().unwrap(),), |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        })
+        .and_then(|rows| rows.collect())
+        .map_err(|e| e.to_string())?;
+    let tiers: [&dyn Fn(&str) -> bool; 3] = [
+        &|other: &str| other == normalized,
+        &|other: &str| initial_matches(&normalized, other),
+        &|other: &str| edit_distance(&normalized, other) <= MAX_NAME_DISTANCE,
+    ];
+    for matches in tiers {
+        let found: Vec<&(i16, String, String)> = players
+            .iter()
+            .filter(|(_, other, _)| matches(&normalize_name(other)))
+            .collect();
+        let same_team: Vec<&&(i16, String, String)> = found
+            .iter()
+            .filter(|(_, _, t)| t == team.to_str())
+            .collect();
+        match (same_team.len(), found.len()) {
+            (0, 0) => continue,
+            (1, _) | (0, 1) => {
+                return Ok(NameMatch::Found(same_team.first().unwrap_or(&&found[0]).0))
+            }
+            _ => {
+                return Ok(NameMatch::Ambiguous(AmbiguousName {
+                    name: name.to_string(),
+                    pos: *pos,
+                    candidates: found.into_iter().cloned().collect(),
+                }))
+            }
+        }
+    }
+    Ok(NameMatch::NotFound)
+}
+
+/// Saves another spelling for a player, later imports match it directly
+pub fn add_player_alias(alias: &str, id: i16, conn: &Connection) -> Result<(), String> {
+    let pos: String = conn
+        .query_row("SELECT pos FROM player WHERE id = ?1", (id,), |row| {
+            row.get(0)
+        })
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or(format!("No player with id {}", id))?;
+    conn.execute(
+        "INSERT INTO player_alias (alias, pos, id) VALUES (?1, ?2, ?3)",
+        (normalize_name(alias), pos, id),
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_normalize_name() {
+        assert_eq!(normalize_name("D.J. Moore Jr."), "dj moore");
+        assert_eq!(normalize_name(" Marvin  Harrison  III "), "marvin harrison");
+        assert_eq!(normalize_name("Amon-Ra St. Brown"), "amon ra st brown");
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+
+    #[test]
+    fn test_missing_def_vs_pos() {
+        // Season 1999 so nothing cached by the db tests gets in the way
+        let conn: Connection = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE player (id INTEGER PRIMARY KEY, name TEXT, team TEXT, pos TEXT);
+            CREATE TABLE def_vs_qb (id INTEGER, season INTEGER, week INTEGER, team_name TEXT, pts_given_pg REAL);
+            INSERT INTO def_vs_qb VALUES (90, 1999, 3, 'KC', 20.0);
+            INSERT INTO def_vs_qb VALUES (91, 1999, 3, 'LV', 10.0);",
+        )
+        .unwrap();
+        assert!(query_def_vs_pos(Team::Den, &Pos::Qb, 4, 1999, &conn).is_none());
+        assert_eq!(def_pts_given(Team::Den, &Pos::Qb, 4, 1999, &conn), 15.0);
+        assert_eq!(def_pts_given(Team::Den, &Pos::Qb, 2, 1999, &conn), 0.0);
+    }
+
+    #[test]
+    fn test_match_player() {
+        let conn: Connection = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE player (id INTEGER PRIMARY KEY, name TEXT, team TEXT, pos TEXT);
+            CREATE TABLE player_alias (alias TEXT, pos TEXT, id INTEGER, UNIQUE(alias, pos) on CONFLICT REPLACE);
+            INSERT INTO player VALUES (1, 'Christian McCaffrey', 'SF', 'RB');
+            INSERT INTO player VALUES (2, 'Mike Williams', 'LAC', 'WR');
+            INSERT INTO player VALUES (3, 'Mike Williams', 'NYJ', 'WR');
+            INSERT INTO player VALUES (4, 'Odell Beckham Jr.', 'BAL', 'WR');",
+        )
+        .unwrap();
+        let find =
+            |name: &str, team: Team, pos: Pos| match_player(name, &team, &pos, &conn).unwrap();
+        assert_eq!(find("C. McCaffrey", Team::Sf, Pos::Rb), NameMatch::Found(1));
+        assert_eq!(
+            find("Odell Beckham", Team::Mia, Pos::Wr),
+            NameMatch::Found(4)
+        );
+        assert_eq!(
+            find("Mike Wiliams", Team::Nyj, Pos::Wr),
+            NameMatch::Found(3)
+        );
+        match find("Mike Williams", Team::Pit, Pos::Wr) {
+            NameMatch::Ambiguous(ambiguous) => assert_eq!(ambiguous.candidates.len(), 2),
+            other => panic!("Expected ambiguous, got {:?}", other),
+        }
+        assert_eq!(find("Nobody Here", Team::Sf, Pos::Rb), NameMatch::NotFound);
+        add_player_alias("Mike Williams", 2, &conn).unwrap();
+        assert_eq!(
+            find("Mike Williams", Team::Pit, Pos::Wr),
+            NameMatch::Found(2)
+        );
+        assert!(add_player_alias("Nobody", 99, &conn).is_err());
+    }
+
     #[test]
     fn test_enum_compartor() {
         let pos: Pos = Pos::from_str("QB").unwrap();
         assert!(pos == Pos::Qb)
     }
 
+    #[test]
+    fn test_resolve_player_refs() {
+        let conn: Connection = Connection::open_in_memory().unwrap();
+        conn.execute(
+            "CREATE TABLE player (id INTEGER PRIMARY KEY, name TEXT, team TEXT, pos TEXT)",
+            (),
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO player (id, name, team, pos) VALUES (5, 'Amon-Ra St. Brown', 'DET', 'WR')",
+            (),
+        )
+        .unwrap();
+        let refs: Vec<String> = vec!["12".to_string(), "Amon-Ra St. Brown-det".to_string()];
+        assert_eq!(resolve_player_refs(&refs, &conn), Ok(vec![12, 5]));
+        assert!(resolve_player_refs(&["Nobody-DET".to_string()], &conn).is_err());
+        assert!(resolve_player_refs(&["Nobody".to_string()], &conn).is_err());
+    }
+
     //85546-69531,Jalen Hurts,PHI,NYG,QB,9000,18.6
     // #[test]
     // fn test_new_from_fd() {
@@ -0,0 +1,260 @@
+use std::collections::HashMap;
+
+use crate::lineup::{IslandLineup, Lineup};
+
+/// Min and max percent of the portfolio a player can be in, 0-100
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Exposure {
+    pub min: f32,
+    pub max: f32,
+}
+
+impl Exposure {
+    /// Most lineups the player can be in, a max of 0 means it was left blank
+    pub fn max_count(&self, lineup_count: usize) -> usize {
+        if self.max <= 0.0 {
+            return lineup_count;
+        }
+        (self.max / 100.0 * lineup_count as f32).floor() as usize
+    }
+
+    pub fn min_count(&self, lineup_count: usize) -> usize {
+        (self.min / 100.0 * lineup_count as f32).ceil() as usize
+    }
+}
+
+pub trait PortfolioLineup: Clone {
+    fn player_ids(&self) -> Vec<i16>;
+    fn portfolio_score(&self) -> f32;
+}
+
+impl PortfolioLineup for Lineup {
+    fn player_ids(&self) -> Vec<i16> {
+        self.get_id_array().to_vec()
+    }
+
+    fn portfolio_score(&self) -> f32 {
+        self.scorer_total
+    }
+}
+
+impl PortfolioLineup for IslandLineup {
+    fn player_ids(&self) -> Vec<i16> {
+        self.get_as_arr().iter().map(|p| p.get_id()).collect()
+    }
+
+    fn portfolio_score(&self) -> f32 {
+        self.score
+    }
+}
+
+struct PortfolioBuilder<'a, T: PortfolioLineup> {
+    count: usize,
+    min_unique: usize,
+    exposures: &'a HashMap<i16, Exposure>,
+    accepted: Vec<T>,
+    accepted_ids: Vec<Vec<i16>>,
+    player_counts: HashMap<i16, usize>,
+}
+
+impl<'a, T: PortfolioLineup> PortfolioBuilder<'a, T> {
+    fn is_full(&self) -> bool {
+        self.accepted.len() >= self.count
+    }
+
+    fn player_count(&self, id: &i16) -> usize {
+        *self.player_counts.get(id).unwrap_or(&0)
+    }
+
+    fn fits(&self, ids: &[i16]) -> bool {
+        let too_similar: bool = self
+            .accepted_ids
+            .iter()
+            .any(|other| players_different(ids, other) < self.min_unique);
+        if too_similar {
+            return false;
+        }
+        ids.iter().all(|id| match self.exposures.get(id) {
+            Some(exposure) => self.player_count(id) < exposure.max_count(self.count),
+            None => true,
+        })
+    }
+
+    fn try_accept(&mut self, lineup: &T) -> bool {
+        let ids: Vec<i16> = lineup.player_ids();
+        if self.is_full() || !self.fits(&ids) {
+            return false;
+        }
+        for id in &ids {
+            *self.player_counts.entry(*id).or_insert(0) += 1;
+        }
+        self.accepted_ids.push(ids);
+        self.accepted.push(lineup.clone());
+        true
+    }
+}
+
+/// Number of players in `ids` that are not in `other`
+pub fn players_different(ids: &[i16], other: &[i16]) -> usize {
+    ids.iter().filter(|id| !other.contains(id)).count()
+}
+
+/// Picks `count` lineups highest score first while keeping every player between
+/// their min and max exposure. Players with a min exposure are filled first.
+/// Every pair of accepted lineups differs by at least `min_unique` players,
+/// 1 only drops exact duplicates.
+pub fn build_portfolio<T: PortfolioLineup>(
+    mut lineups: Vec<T>,
+    count: usize,
+    min_unique: usize,
+    exposures: &HashMap<i16, Exposure>,
+) -> Vec<T> {
+    lineups.sort_by(|a, b| b.portfolio_score().total_cmp(&a.portfolio_score()));
+    let lineup_ids: Vec<Vec<i16>> = lineups.iter().map(|l| l.player_ids()).collect();
+    let mut builder: PortfolioBuilder<T> = PortfolioBuilder {
+        count,
+        min_unique,
+        exposures,
+        accepted: Vec::new(),
+        accepted_ids: Vec::new(),
+        player_counts: HashMap::new(),
+    };
+
+    let mut min_players: Vec<(&i16, usize)> = exposures
+        .iter()
+        .map(|(id, e)| (id, e.min_count(count)))
+        .filter(|(_, min)| *min > 0)
+        .collect();
+    min_players.sort_by_key(|m| std::cmp::Reverse(m.1));
+    for (id, min) in min_players {
+        for (lineup, ids) in lineups.iter().zip(&lineup_ids) {
+            if builder.player_count(id) >= min || builder.is_full() {
+                break;
+            }
+            if ids.contains(id) {
+                builder.try_accept(lineup);
+            }
+        }
+        if builder.player_count(id) < min {
+            println!(
+                "Could not reach min exposure for player {}: {} of {}",
+                id,
+                builder.player_count(id),
+                min
+            );
+        }
+    }
+
+    for lineup in &lineups {
+        if builder.is_full() {
+            break;
+        }
+        builder.try_accept(lineup);
+    }
+    let mut portfolio: Vec<T> = builder.accepted;
+    portfolio.sort_by(|a, b| b.portfolio_score().total_cmp(&a.portfolio_score()));
+    portfolio
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug)]
+    struct TestLineup {
+        ids: Vec<i16>,
+        score: f32,
+    }
+
+    impl PortfolioLineup for TestLineup {
+        fn player_ids(&self) -> Vec<i16> {
+            self.ids.clone()
+        }
+
+        fn portfolio_score(&self) -> f32 {
+            self.score
+        }
+    }
+
+    fn test_lineups() -> Vec<TestLineup> {
+        vec![
+            TestLineup {
+                ids: vec![1, 2, 3],
+                score: 10.0,
+            },
+            TestLineup {
+                ids: vec![1, 2, 4],
+                score: 9.0,
+            },
+            TestLineup {
+                ids: vec![1, 3, 4],
+                score: 8.0,
+            },
+            TestLineup {
+                ids: vec![2, 3, 5],
+                score: 7.0,
+            },
+            TestLineup {
+                ids: vec![3, 4, 5],
+                score: 6.0,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_max_exposure() {
+        let exposures: HashMap<i16, Exposure> = HashMap::from([(
+            1,
+            Exposure {
+                min: 0.0,
+                max: 50.0,
+            },
+        )]);
+        let portfolio: Vec<TestLineup> = build_portfolio(test_lineups(), 4, 1, &exposures);
+        assert_eq!(portfolio.len(), 4);
+        assert_eq!(portfolio.iter().filter(|l| l.ids.contains(&1)).count(), 2);
+        assert_eq!(portfolio[0].score, 10.0);
+    }
+
+    #[test]
+    fn test_min_exposure() {
+        let exposures: HashMap<i16, Exposure> = HashMap::from([(
+            5,
+            Exposure {
+                min: 100.0,
+                max: 100.0,
+            },
+        )]);
+        let portfolio: Vec<TestLineup> = build_portfolio(test_lineups(), 2, 1, &exposures);
+        assert!(portfolio.iter().all(|l| l.ids.contains(&5)));
+    }
+
+    #[test]
+    fn test_skips_duplicates() {
+        let mut lineups: Vec<TestLineup> = test_lineups();
+        lineups.push(TestLineup {
+            ids: vec![3, 2, 1],
+            score: 10.0,
+        });
+        let portfolio: Vec<TestLineup> = build_portfolio(lineups, 3, 1, &HashMap::new());
+        assert_eq!(portfolio[1].score, 9.0);
+    }
+
+    #[test]
+    fn test_min_unique() {
+        let portfolio: Vec<TestLineup> = build_portfolio(test_lineups(), 5, 2, &HashMap::new());
+        let scores: Vec<f32> = portfolio.iter().map(|l| l.score).collect();
+        assert_eq!(scores, vec![10.0, 6.0]);
+        assert_eq!(players_different(&[1, 2, 3], &[3, 2, 4]), 1);
+    }
+
+    #[test]
+    fn test_exposure_counts() {
+        let exposure: Exposure = Exposure {
+            min: 10.0,
+            max: 0.0,
+        };
+        assert_eq!(exposure.max_count(150), 150);
+        assert_eq!(exposure.min_count(150), 15);
+    }
+}
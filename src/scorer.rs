@@ -0,0 +1,389 @@
+use std::collections::HashMap;
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use crate::config::SlateConfig;
+use crate::lineup::{Lineup, LineupBuilder};
+use crate::player::{query_proj, LitePlayer, Pos, Proj, RecProj, Team};
+use crate::scoring::{scoring_model, ScoringModel};
+
+/// Scores players and lineups for the optimizers. The classic optimizers prune
+/// on added up player scores, so `max_correlation` has to cover `correlation`.
+pub trait Scorer: Send + Sync {
+    fn player(&self, proj: &Proj, any_flex: bool) -> f32;
+
+    /// Added to the players' total for how the lineup's players go together.
+    /// `projs` come in slot order, a classic lineup's flex after its WR slots.
+    fn correlation(&self, _projs: &[&Proj]) -> f32 {
+        0.0
+    }
+
+    /// Most `correlation` adds to a classic lineup, None when there is no lineup term
+    fn max_correlation(&self) -> Option<f32> {
+        None
+    }
+
+    /// False when `correlation` costs too much to run on every lineup a search
+    /// finishes, the searches then go on player scores and `rank_lineups` adds
+    /// it to the lineups they keep
+    fn correlation_in_search(&self) -> bool {
+        true
+    }
+
+    fn lineup(&self, projs: &[&Proj], any_flex: bool) -> f32 {
+        let players: f32 = projs.iter().map(|p| self.player(p, any_flex)).sum();
+        match self.max_correlation() {
+            Some(_) => players + self.correlation(projs),
+            None => players,
+        }
+    }
+}
+
+/// Scorer the optimizers use, `--scorer` on the command line
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScorerKind {
+    /// The installed scoring model with QB stacks as the lineup term
+    #[default]
+    Heuristic,
+    /// Projected points
+    Projection,
+    /// Simulated points at a percentile, team stacks lose the least to the lineup term
+    Sim,
+}
+
+impl ScorerKind {
+    pub fn try_from_str(input: &str) -> Option<ScorerKind> {
+        match input.to_lowercase().as_str() {
+            "heuristic" => Some(ScorerKind::Heuristic),
+            "projection" | "proj" => Some(ScorerKind::Projection),
+            "sim" => Some(ScorerKind::Sim),
+            _ => None,
+        }
+    }
+
+    /// Install the scoring model first, the heuristic scorer keeps the one it starts with
+    pub fn build(&self) -> Arc<dyn Scorer> {
+        match self {
+            ScorerKind::Heuristic => Arc::new(HeuristicScorer::new()),
+            ScorerKind::Projection => Arc::new(ProjectionScorer),
+            ScorerKind::Sim => Arc::new(SimScorer::default()),
+        }
+    }
+}
+
+/// The per position scores from scoring.rs
+pub struct HeuristicScorer {
+    model: Arc<ScoringModel>,
+}
+
+impl HeuristicScorer {
+    pub fn new() -> HeuristicScorer {
+        HeuristicScorer {
+            model: scoring_model(),
+        }
+    }
+}
+
+impl Default for HeuristicScorer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Scorer for HeuristicScorer {
+    fn player(&self, proj: &Proj, any_flex: bool) -> f32 {
+        match proj {
+            Proj::QbProj(qb) => self.model.qb.score(qb, any_flex),
+            Proj::RbProj(rb) => self.model.rb.score(rb, any_flex),
+            Proj::RecProj(rec) => self.model.position(&rec.pos).score(rec, any_flex),
+            Proj::DefProj(def) => self.model.dst.score(def, any_flex),
+            // Kickers only play in any flex contests
+            Proj::KickProj(k) => self.model.k.score(k, true),
+        }
+    }
+
+    /// Each QB's best stack with a WR on his team, like `Lineup::score` only
+    /// the three WR slots count so a flex WR doesn't stack
+    fn correlation(&self, projs: &[&Proj]) -> f32 {
+        let wrs: Vec<&RecProj> = projs
+            .iter()
+            .filter_map(|p| match p {
+                Proj::RecProj(rec) if rec.pos == Pos::Wr => Some(rec),
+                _ => None,
+            })
+            .take(3)
+            .collect();
+        projs
+            .iter()
+            .filter_map(|p| match p {
+                Proj::QbProj(qb) => Some(self.model.stack.score(&wrs, qb)),
+                _ => None,
+            })
+            .sum()
+    }
+
+    fn max_correlation(&self) -> Option<f32> {
+        let stack = &self.model.stack;
+        let features: f32 = stack.features.iter().map(|f| f.weight.max(0.0)).sum();
+        Some((stack.base + features).max(0.0))
+    }
+}
+
+/// Projected points, nothing for the lineup
+pub struct ProjectionScorer;
+
+impl Scorer for ProjectionScorer {
+    fn player(&self, proj: &Proj, _: bool) -> f32 {
+        proj.get_pts_proj()
+    }
+}
+
+/// Most players a simulated lineup can have
+const MAX_PLAYERS: usize = 9;
+/// Standard normal at the 90th percentile, projections' ceilings and floors are
+/// taken as the 90th and 10th
+const Z_90: f32 = 1.2816;
+
+/// Points are normal around the projection with a spread from the ceiling and
+/// floor. Players score at `percentile` of their own points, the lineup term is
+/// how far the lineup's `percentile` falls under those added up. Teammates share
+/// part of their spread, so a stack loses less than players from different teams.
+pub struct SimScorer {
+    /// 0-1
+    pub percentile: f32,
+    /// Share of a player's spread that moves with the team
+    pub team_corr: f32,
+    z: f32,
+    // Same draws for every lineup so lineups compare on the same games
+    team_draws: Vec<[f32; MAX_PLAYERS]>,
+    player_draws: Vec<[f32; MAX_PLAYERS]>,
+}
+
+impl SimScorer {
+    pub fn new(sims: usize, percentile: f32, team_corr: f32, seed: u64) -> SimScorer {
+        let mut rng: StdRng = StdRng::seed_from_u64(seed);
+        let draws = |rng: &mut StdRng| -> Vec<[f32; MAX_PLAYERS]> {
+            (0..sims)
+                .map(|_| [0; MAX_PLAYERS].map(|_| standard_normal(rng)))
+                .collect()
+        };
+        let team_draws: Vec<[f32; MAX_PLAYERS]> = draws(&mut rng);
+        let player_draws: Vec<[f32; MAX_PLAYERS]> = draws(&mut rng);
+        let own: Vec<f32> = player_draws.iter().map(|d| d[0]).collect();
+        SimScorer {
+            percentile,
+            team_corr,
+            z: at_percentile(own, percentile),
+            team_draws,
+            player_draws,
+        }
+    }
+
+    fn spread(proj: &Proj) -> f32 {
+        let (cieling, floor) = proj.get_cieling_floor();
+        ((cieling - floor) / (2.0 * Z_90)).max(0.0)
+    }
+
+    /// `percentile` of the lineup's simulated total, players as (pts, spread, team)
+    fn lineup_percentile(&self, players: &[(f32, f32, Team)]) -> f32 {
+        assert!(players.len() <= MAX_PLAYERS, "Too many players to simulate");
+        let mut teams: Vec<Team> = Vec::new();
+        let slots: Vec<usize> = players
+            .iter()
+            .map(|(_, _, team)| match teams.iter().position(|t| t == team) {
+                Some(slot) => slot,
+                None => {
+                    teams.push(*team);
+                    teams.len() - 1
+                }
+            })
+            .collect();
+        let shared: f32 = self.team_corr.sqrt();
+        let own: f32 = (1.0 - self.team_corr).sqrt();
+        let totals: Vec<f32> = self
+            .team_draws
+            .iter()
+            .zip(&self.player_draws)
+            .map(|(team, player)| {
+                players
+                    .iter()
+                    .enumerate()
+                    .map(|(i, (pts, spread, _))| {
+                        pts + spread * (shared * team[slots[i]] + own * player[i])
+                    })
+                    .sum()
+            })
+            .collect();
+        at_percentile(totals, self.percentile)
+    }
+}
+
+impl Default for SimScorer {
+    fn default() -> Self {
+        SimScorer::new(1000, 0.8, 0.35, 7)
+    }
+}
+
+impl Scorer for SimScorer {
+    fn player(&self, proj: &Proj, _: bool) -> f32 {
+        proj.get_pts_proj() + self.z * Self::spread(proj)
+    }
+
+    fn correlation(&self, projs: &[&Proj]) -> f32 {
+        let players: Vec<(f32, f32, Team)> = projs
+            .iter()
+            .map(|p| (p.get_pts_proj(), Self::spread(p), p.get_team()))
+            .collect();
+        let apart: f32 = projs.iter().map(|p| self.player(p, false)).sum();
+        // Above the percentile the lineup can't beat its players added up,
+        // anything over 0 is sampling noise
+        (self.lineup_percentile(&players) - apart).min(0.0)
+    }
+
+    fn max_correlation(&self) -> Option<f32> {
+        Some(0.0)
+    }
+
+    // A thousand sims and a sort per lineup
+    fn correlation_in_search(&self) -> bool {
+        false
+    }
+}
+
+fn standard_normal(rng: &mut StdRng) -> f32 {
+    let u1: f32 = rng.gen_range(f32::EPSILON..1.0);
+    let u2: f32 = rng.gen();
+    (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos()
+}
+
+fn at_percentile(mut values: Vec<f32>, percentile: f32) -> f32 {
+    values.sort_by(f32::total_cmp);
+    let index: usize = ((values.len() - 1) as f32 * percentile.clamp(0.0, 1.0)).round() as usize;
+    values[index]
+}
+
+/// Projections for the players in a slate
+pub fn load_projs(
+    players: &[LitePlayer],
+    config: &SlateConfig,
+    conn: &Connection,
+) -> HashMap<i16, Proj> {
+    players
+        .iter()
+        .map(|p| (p.id, query_proj(Some(p), config.week, config.season, conn)))
+        .collect()
+}
+
+/// A scorer with the slate's projections on hand so it works on `LitePlayer`s.
+/// The pool itself is still picked with the built in scores.
+pub struct SlateScorer {
+    pub scorer: Arc<dyn Scorer>,
+    projs: HashMap<i16, Proj>,
+    any_flex: bool,
+}
+
+impl SlateScorer {
+    pub fn new(scorer: Arc<dyn Scorer>, projs: HashMap<i16, Proj>, any_flex: bool) -> SlateScorer {
+        SlateScorer {
+            scorer,
+            projs,
+            any_flex,
+        }
+    }
+
+    /// The players with `score` from the scorer
+    pub fn score_players(&self, players: &[LitePlayer]) -> Vec<LitePlayer> {
+        players
+            .iter()
+            .map(|p| LitePlayer {
+                score: self.scorer.player(&self.projs[&p.id], self.any_flex),
+                ..*p
+            })
+            .collect()
+    }
+
+    /// Most the search's lineup term adds
+    pub fn max_correlation(&self) -> f32 {
+        if !self.scorer.correlation_in_search() {
+            return 0.0;
+        }
+        self.scorer.max_correlation().unwrap_or(0.0)
+    }
+
+    /// The lineup term the searches add, 0 when it's left to `rank_lineups`
+    pub fn correlation(&self, players: &[LitePlayer]) -> f32 {
+        if self.scorer.max_correlation().is_none() || !self.scorer.correlation_in_search() {
+            return 0.0;
+        }
+        let projs: Vec<&Proj> = players.iter().map(|p| &self.projs[&p.id]).collect();
+        self.scorer.correlation(&projs)
+    }
+
+    /// `LineupBuilder::score` plus the search's lineup term, what the classic
+    /// optimizers keep
+    pub fn lineup_score(&self, lineup: &LineupBuilder) -> f32 {
+        let score: f32 = lineup.score();
+        // Lineups the builder filters out stay at 0
+        if score == 0.0 {
+            return 0.0;
+        }
+        score + self.correlation(&lineup.array_of_players())
+    }
+}
+
+/// Sets `scorer_total` on each lineup, best first
+pub fn rank_lineups(mut lineups: Vec<Lineup>, scorer: &dyn Scorer) -> Vec<Lineup> {
+    for lineup in lineups.iter_mut() {
+        let projs: Vec<Proj> = lineup.projs();
+        lineup.scorer_total = scorer.lineup(&projs.iter().collect::<Vec<&Proj>>(), false);
+    }
+    lineups.sort_by(|a, b| b.scorer_total.total_cmp(&a.scorer_total));
+    lineups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sim_stacks() {
+        let scorer: SimScorer = SimScorer::new(2000, 0.8, 0.5, 1);
+        assert!((scorer.z - 0.84).abs() < 0.1);
+        let stacked: [(f32, f32, Team); 3] = [
+            (20.0, 6.0, Team::Buf),
+            (15.0, 5.0, Team::Buf),
+            (12.0, 4.0, Team::Buf),
+        ];
+        let spread_out: [(f32, f32, Team); 3] = [
+            (20.0, 6.0, Team::Buf),
+            (15.0, 5.0, Team::Mia),
+            (12.0, 4.0, Team::Kc),
+        ];
+        let apart: f32 = 47.0 + scorer.z * 15.0;
+        let stacked: f32 = scorer.lineup_percentile(&stacked);
+        let spread_out: f32 = scorer.lineup_percentile(&spread_out);
+        assert!(stacked > spread_out);
+        assert!(stacked < apart + 0.5);
+        assert!(spread_out > 47.0);
+    }
+
+    #[test]
+    fn test_scorer_kind() {
+        assert_eq!(ScorerKind::try_from_str("Sim"), Some(ScorerKind::Sim));
+        assert_eq!(
+            ScorerKind::try_from_str("proj"),
+            Some(ScorerKind::Projection)
+        );
+        assert_eq!(ScorerKind::try_from_str("model"), None);
+        assert!(ScorerKind::Projection.build().max_correlation().is_none());
+        assert_eq!(ScorerKind::Sim.build().max_correlation(), Some(0.0));
+        assert!(!ScorerKind::Sim.build().correlation_in_search());
+        assert!(ScorerKind::Heuristic.build().correlation_in_search());
+    }
+}
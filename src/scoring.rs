@@ -0,0 +1,827 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::sync::{Arc, OnceLock, RwLock};
+
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+
+use crate::config::SlateConfig;
+use crate::lineup::get_normalized_score;
+use crate::normalize::{Normalize, SlateStats};
+use crate::player::{DefProj, KickProj, Pos, QbProj, RbProj, RecProj};
+use crate::{
+    get_def_max_min, get_def_values, get_inverse_max_min, get_max_min, get_max_min_all,
+    get_slate_values, Day, SLATE_CONFIG,
+};
+
+lazy_static! {
+    static ref SCORING_MODEL: RwLock<Arc<ScoringModel>> =
+        RwLock::new(Arc::new(ScoringModel::default()));
+    /// Normalizer stats by slate, position and range, then column
+    static ref STATS_CACHE: RwLock<HashMap<RangeKey, ColumnStats>> =
+        RwLock::new(HashMap::new());
+}
+
+type RangeKey = (i16, i8, Day, Pos, Range);
+type ColumnStats = HashMap<String, Arc<SlateStats>>;
+
+/// Which contests a feature counts in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum When {
+    #[default]
+    Always,
+    Classic,
+    AnyFlex,
+}
+
+impl When {
+    fn applies(&self, any_flex: bool) -> bool {
+        match self {
+            When::Always => true,
+            When::Classic => !any_flex,
+            When::AnyFlex => any_flex,
+        }
+    }
+}
+
+/// Players a feature is normalized against, all from the installed slate
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Range {
+    /// Same position
+    #[default]
+    Pos,
+    /// Every position but kickers
+    All,
+    /// Same position, lower is better so the value is negated
+    Inverse,
+    /// Points the defenses gave up to the position, for `opp_def_pts_given`
+    DefVsPos,
+}
+
+/// One weighted input to a position's score
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Feature {
+    pub name: String,
+    /// Column of the position's proj table
+    pub field: String,
+    pub weight: f32,
+    #[serde(default)]
+    pub range: Range,
+    /// Column the range comes from when it isn't `field`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub range_field: Option<String>,
+    #[serde(default)]
+    pub when: When,
+    #[serde(default)]
+    pub normalize: Normalize,
+    /// Index into `ModelInput::COLUMNS`, filled in on first use
+    #[serde(skip)]
+    column: OnceLock<usize>,
+    /// The range over the installed slate, filled in on first use
+    #[serde(skip)]
+    stats: OnceLock<Arc<SlateStats>>,
+}
+
+impl Feature {
+    fn new(name: &str, field: &str, weight: f32, range: Range, when: When) -> Feature {
+        Feature {
+            name: name.to_string(),
+            field: field.to_string(),
+            weight,
+            range,
+            range_field: None,
+            when,
+            normalize: Normalize::MinMax,
+            column: OnceLock::new(),
+            stats: OnceLock::new(),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.column = OnceLock::new();
+        self.stats = OnceLock::new();
+    }
+
+    /// The projection's value for `field`
+    pub fn raw<P: ModelInput>(&self, proj: &P) -> f32 {
+        let column: usize = *self.column.get_or_init(|| {
+            P::COLUMNS
+                .iter()
+                .position(|(name, _)| *name == self.field)
+                .unwrap_or_else(|| {
+                    panic!(
+                        "Scoring model reads {} which {:?} projections don't have",
+                        self.field,
+                        proj.pos()
+                    )
+                })
+        });
+        (P::COLUMNS[column].1)(proj)
+    }
+
+    /// The value scaled to 0-1 against the slate
+    pub fn normalized<P: ModelInput>(&self, proj: &P) -> f32 {
+        let pos: Pos = proj.pos();
+        let value: f32 = match self.range {
+            Range::Inverse => -self.raw(proj),
+            _ => self.raw(proj),
+        };
+        self.stats
+            .get_or_init(|| {
+                let column: &str = self.range_field.as_deref().unwrap_or(&self.field);
+                slate_stats(pos, self.range, column)
+            })
+            .normalize(value, self.normalize)
+    }
+}
+
+/// Score for one position, the weighted features over `cap` so a player
+/// lands between 0 and 1 before `flat_bonus` and the weather
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PositionModel {
+    pub features: Vec<Feature>,
+    /// Added to the total in any-flex contests
+    pub any_flex_bonus: f32,
+    /// Total that maps to a score of 1.0
+    pub cap: f32,
+    /// Players projected under this ownership get nothing
+    pub min_own: f32,
+    /// Added to the total for players projected under `low_own`
+    pub low_own: f32,
+    pub low_own_bonus: f32,
+    /// Added after the cap
+    pub flat_bonus: f32,
+}
+
+impl Default for PositionModel {
+    fn default() -> Self {
+        PositionModel {
+            features: Vec::new(),
+            any_flex_bonus: 0.0,
+            cap: 1.0,
+            min_own: 0.0,
+            low_own: 0.0,
+            low_own_bonus: 0.0,
+            flat_bonus: 0.0,
+        }
+    }
+}
+
+impl PositionModel {
+    pub fn score<P: ModelInput>(&self, proj: &P, any_flex: bool) -> f32 {
+        self.score_with(proj, any_flex, None)
+    }
+
+    /// `score` with every feature and rule that went into it
+    pub fn explain<P: ModelInput>(&self, proj: &P, any_flex: bool) -> Breakdown {
+        let mut breakdown: Breakdown = Breakdown::default();
+        breakdown.score = self.score_with(proj, any_flex, Some(&mut breakdown));
+        breakdown
+    }
+
+    fn score_with<P: ModelInput>(
+        &self,
+        proj: &P,
+        any_flex: bool,
+        mut breakdown: Option<&mut Breakdown>,
+    ) -> f32 {
+        let mut score: f32 = 0.0;
+        for feature in self.features.iter().filter(|f| f.when.applies(any_flex)) {
+            let normalized: f32 = feature.normalized(proj);
+            score += normalized * feature.weight;
+            if let Some(breakdown) = breakdown.as_deref_mut() {
+                breakdown.features.push(FeatureScore {
+                    name: feature.name.clone(),
+                    raw: feature.raw(proj),
+                    normalized,
+                    weight: feature.weight,
+                    contribution: normalized * feature.weight,
+                });
+            }
+        }
+        if any_flex {
+            score += self.any_flex_bonus;
+            if self.any_flex_bonus != 0.0 {
+                note(&mut breakdown, || {
+                    format!("any flex bonus {:+}", self.any_flex_bonus)
+                });
+            }
+        }
+        if proj.own_proj() < self.low_own {
+            score += self.low_own_bonus;
+            note(&mut breakdown, || {
+                format!(
+                    "own {} under {}: {:+}",
+                    proj.own_proj(),
+                    self.low_own,
+                    self.low_own_bonus
+                )
+            });
+        }
+        if proj.own_proj() < self.min_own {
+            score = 0.0;
+            note(&mut breakdown, || {
+                format!("own {} under {}: zeroed", proj.own_proj(), self.min_own)
+            });
+        }
+        if score > self.cap {
+            note(&mut breakdown, || {
+                format!("{} capped at {}", score, self.cap)
+            });
+        }
+        if self.flat_bonus != 0.0 {
+            note(&mut breakdown, || {
+                format!("flat bonus {:+}", self.flat_bonus)
+            });
+        }
+        if proj.weather() != 1.0 {
+            note(&mut breakdown, || format!("weather x{}", proj.weather()));
+        }
+        (get_normalized_score(score, (self.cap, 0.0)) + self.flat_bonus) * proj.weather()
+    }
+}
+
+/// One feature's part of a score
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeatureScore {
+    pub name: String,
+    pub raw: f32,
+    pub normalized: f32,
+    pub weight: f32,
+    pub contribution: f32,
+}
+
+/// How a score was reached, the features in model order then the rules that fired
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Breakdown {
+    pub features: Vec<FeatureScore>,
+    pub rules: Vec<String>,
+    pub score: f32,
+}
+
+// Only formats the rule when something is explaining
+fn note(breakdown: &mut Option<&mut Breakdown>, rule: impl FnOnce() -> String) {
+    if let Some(breakdown) = breakdown.as_deref_mut() {
+        breakdown.rules.push(rule());
+    }
+}
+
+/// Bonus for a QB with a pass catcher from his team, the best one counts
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StackModel {
+    pub base: f32,
+    /// Read from the pass catcher
+    pub features: Vec<Feature>,
+}
+
+impl StackModel {
+    pub fn score(&self, wrs: &[&RecProj], qb: &QbProj) -> f32 {
+        wrs.iter()
+            .filter(|wr| wr.team == qb.team)
+            .map(|wr| self.pair_score(wr, None))
+            .fold(0.0, f32::max)
+    }
+
+    /// The pass catcher that sets the bonus and its breakdown, None when
+    /// none of them are on the QB's team
+    pub fn explain(&self, wrs: &[&RecProj], qb: &QbProj) -> Option<(i16, Breakdown)> {
+        let (id, mut breakdown) = wrs
+            .iter()
+            .filter(|wr| wr.team == qb.team)
+            .map(|wr| {
+                let mut breakdown: Breakdown = Breakdown::default();
+                breakdown.score = self.pair_score(wr, Some(&mut breakdown));
+                (wr.id, breakdown)
+            })
+            .max_by(|a, b| a.1.score.total_cmp(&b.1.score))?;
+        if breakdown.score < 0.0 {
+            breakdown
+                .rules
+                .push(format!("{} counts as 0", breakdown.score));
+            breakdown.score = 0.0;
+        }
+        Some((id, breakdown))
+    }
+
+    fn pair_score(&self, wr: &RecProj, mut breakdown: Option<&mut Breakdown>) -> f32 {
+        let mut score: f32 = 0.0;
+        for feature in &self.features {
+            let normalized: f32 = feature.normalized(wr);
+            score += normalized * feature.weight;
+            if let Some(breakdown) = breakdown.as_deref_mut() {
+                breakdown.features.push(FeatureScore {
+                    name: feature.name.clone(),
+                    raw: feature.raw(wr),
+                    normalized,
+                    weight: feature.weight,
+                    contribution: normalized * feature.weight,
+                });
+            }
+        }
+        note(&mut breakdown, || format!("stack base {:+}", self.base));
+        self.base + score
+    }
+}
+
+/// Weights for every position, loaded with `--model` or the built in one.
+/// Positions left out of a model file keep the built in weights.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScoringModel {
+    pub qb: PositionModel,
+    pub rb: PositionModel,
+    pub wr: PositionModel,
+    pub te: PositionModel,
+    pub dst: PositionModel,
+    pub k: PositionModel,
+    pub stack: StackModel,
+}
+
+impl ScoringModel {
+    pub fn from_file(path: &str) -> Result<ScoringModel, Box<dyn Error>> {
+        let contents: String = fs::read_to_string(path)?;
+        let model: ScoringModel = serde_json::from_str(&contents)?;
+        model
+            .validate()
+            .map_err(|e| format!("Bad scoring model {}: {}", path, e))?;
+        Ok(model)
+    }
+
+    pub fn write_file(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Used by every scoring function from here on. Ranges are looked up
+    /// again for whatever slate is installed.
+    pub fn install(&self) {
+        let mut model: ScoringModel = self.clone();
+        for pos in [Pos::Qb, Pos::Rb, Pos::Wr, Pos::Te, Pos::D, Pos::K] {
+            model
+                .position_mut(&pos)
+                .features
+                .iter_mut()
+                .for_each(Feature::reset);
+        }
+        model.stack.features.iter_mut().for_each(Feature::reset);
+        *SCORING_MODEL.write().unwrap() = Arc::new(model);
+    }
+
+    pub fn position(&self, pos: &Pos) -> &PositionModel {
+        match pos {
+            Pos::Qb => &self.qb,
+            Pos::Rb => &self.rb,
+            Pos::Wr => &self.wr,
+            Pos::Te => &self.te,
+            Pos::D => &self.dst,
+            Pos::K => &self.k,
+        }
+    }
+
+    fn position_mut(&mut self, pos: &Pos) -> &mut PositionModel {
+        match pos {
+            Pos::Qb => &mut self.qb,
+            Pos::Rb => &mut self.rb,
+            Pos::Wr => &mut self.wr,
+            Pos::Te => &mut self.te,
+            Pos::D => &mut self.dst,
+            Pos::K => &mut self.k,
+        }
+    }
+
+    /// Every feature reads a column its position has
+    pub fn validate(&self) -> Result<(), String> {
+        for pos in [Pos::Qb, Pos::Rb, Pos::Wr, Pos::Te, Pos::D, Pos::K] {
+            let model: &PositionModel = self.position(&pos);
+            if model.cap <= 0.0 {
+                return Err(format!("{:?} cap has to be over 0", pos));
+            }
+            check_features(&model.features, &pos)?;
+        }
+        check_features(&self.stack.features, &Pos::Wr)
+    }
+}
+
+fn check_features(features: &[Feature], pos: &Pos) -> Result<(), String> {
+    for feature in features {
+        if !columns(pos).contains(&feature.field.as_str()) {
+            return Err(format!(
+                "{} reads {} which {:?} projections don't have",
+                feature.name, feature.field, pos
+            ));
+        }
+        if feature.range == Range::DefVsPos && matches!(pos, Pos::D | Pos::K) {
+            return Err(format!("{} has no def vs pos for {:?}", feature.name, pos));
+        }
+    }
+    Ok(())
+}
+
+/// The hand tuned weights the scoring functions started with
+impl Default for ScoringModel {
+    fn default() -> Self {
+        use Range::{All, DefVsPos, Inverse};
+        use When::{Always, AnyFlex, Classic};
+        let pos: Range = Range::Pos;
+        ScoringModel {
+            qb: PositionModel {
+                features: vec![
+                    Feature::new("red_zone", "red_zone_op_pg", 1.0, pos, Always),
+                    Feature::new("pass_td", "avg_pass_tds", 1.0, pos, Always),
+                    Feature::new("rush_yds", "avg_rush_yds", 0.5, pos, Always),
+                    Feature::new("salary", "salary", 1.2, Inverse, Always),
+                    Feature::new("team_total", "vegas_team_total", 2.0, All, Classic),
+                    Feature::new("ceiling", "cieling_proj", 2.0, pos, Classic),
+                ],
+                any_flex_bonus: 2.5,
+                cap: 8.25,
+                min_own: 2.5,
+                ..PositionModel::default()
+            },
+            rb: PositionModel {
+                features: vec![
+                    Feature::new("opp_def", "opp_def_pts_given", 0.5, DefVsPos, Always),
+                    Feature::new("attempts", "avg_atts", 0.5, pos, Always),
+                    Feature::new("targets", "avg_rec_tgts", 0.5, pos, Always),
+                    Feature::new("td", "avg_td", 1.0, pos, Always),
+                    Feature::new("salary", "salary", 3.0, Inverse, Always),
+                    Feature::new("ceiling", "cieling_proj", 2.0, All, AnyFlex),
+                    Feature::new("team_total", "vegas_team_total", 2.0, All, Classic),
+                    Feature::new("ceiling", "cieling_proj", 3.0, All, Classic),
+                ],
+                any_flex_bonus: 2.0,
+                cap: 9.25,
+                min_own: 5.5,
+                ..PositionModel::default()
+            },
+            wr: PositionModel {
+                features: vec![
+                    Feature::new("opp_def", "opp_def_pts_given", 0.75, DefVsPos, Always),
+                    Feature::new("tgt_share", "rec_tgt_share", 0.5, pos, Always),
+                    Feature::new("td", "avg_td", 1.5, pos, Always),
+                    Feature::new("red_zone", "red_zone_op_pg", 0.75, pos, Always),
+                    Feature::new("team_total", "vegas_team_total", 2.5, All, Classic),
+                    Feature::new("value", "pts_sal_proj", 2.5, pos, Classic),
+                    Feature::new("ceiling", "cieling_proj", 3.5, pos, Classic),
+                    Feature::new("upside", "month_upside", 0.5, pos, Always),
+                ],
+                any_flex_bonus: 2.5,
+                cap: 11.35,
+                min_own: 3.5,
+                low_own: 8.0,
+                low_own_bonus: 0.05,
+                flat_bonus: 0.05,
+            },
+            te: PositionModel {
+                features: vec![
+                    Feature::new("td", "avg_td", 1.0, pos, Always),
+                    Feature::new("upside", "month_upside", 0.5, pos, Always),
+                    Feature::new("salary", "salary", 0.5, Inverse, Always),
+                    Feature::new("team_total", "vegas_team_total", 1.0, All, Classic),
+                    Feature::new("value", "pts_sal_proj", 2.0, pos, Classic),
+                ],
+                any_flex_bonus: 2.0,
+                cap: 8.0,
+                min_own: 5.0,
+                ..PositionModel::default()
+            },
+            dst: PositionModel {
+                features: vec![
+                    Feature::new("opp_total", "vegas_opp_total", 1.0, Inverse, Always),
+                    Feature::new("plus_minus", "pts_plus_minus_proj", 1.0, All, AnyFlex),
+                    Feature::new("plus_minus", "pts_plus_minus_proj", 1.0, pos, Classic),
+                ],
+                cap: 3.0,
+                min_own: 1.0,
+                ..PositionModel::default()
+            },
+            k: PositionModel {
+                features: vec![Feature {
+                    range_field: Some("pts_proj".to_string()),
+                    ..Feature::new("plus_minus", "pts_plus_minus_proj", 1.0, All, Always)
+                }],
+                ..PositionModel::default()
+            },
+            stack: StackModel {
+                base: 0.3,
+                features: vec![Feature::new("tgt_share", "rec_tgt_share", 0.5, pos, Always)],
+            },
+        }
+    }
+}
+
+/// The installed model
+pub fn scoring_model() -> Arc<ScoringModel> {
+    SCORING_MODEL.read().unwrap().clone()
+}
+
+/// A column's values over the installed slate, looked up once per slate
+pub(crate) fn slate_stats(pos: Pos, range: Range, column: &str) -> Arc<SlateStats> {
+    let key: RangeKey = {
+        let config = SLATE_CONFIG.read().unwrap();
+        (config.season, config.week, config.day, pos, range)
+    };
+    if let Some(stats) = STATS_CACHE
+        .read()
+        .unwrap()
+        .get(&key)
+        .and_then(|columns| columns.get(column))
+    {
+        return stats.clone();
+    }
+    let config: SlateConfig = SLATE_CONFIG.read().unwrap().clone();
+    let stats: SlateStats = match range {
+        Range::Pos => SlateStats::new(
+            get_max_min(&config, column, pos),
+            get_slate_values(&config, column, &pos),
+        ),
+        Range::All => SlateStats::new(
+            get_max_min_all(&config, column),
+            [Pos::D, Pos::Qb, Pos::Rb, Pos::Te, Pos::Wr]
+                .iter()
+                .flat_map(|pos| get_slate_values(&config, column, pos))
+                .collect(),
+        ),
+        Range::Inverse => SlateStats::new(
+            get_inverse_max_min(&config, column, &pos),
+            get_slate_values(&config, column, &pos)
+                .into_iter()
+                .map(|v| -v)
+                .collect(),
+        ),
+        Range::DefVsPos => SlateStats::new(
+            get_def_max_min(&config, &pos),
+            get_def_values(&config, &pos),
+        ),
+    };
+    let stats: Arc<SlateStats> = Arc::new(stats);
+    STATS_CACHE
+        .write()
+        .unwrap()
+        .entry(key)
+        .or_default()
+        .insert(column.to_string(), stats.clone());
+    stats
+}
+
+/// Getter for one proj table column
+pub type Column<P> = (&'static str, fn(&P) -> f32);
+
+/// Projection columns a model can read, named as in the proj tables
+pub trait ModelInput: Sized + 'static {
+    const COLUMNS: &'static [Column<Self>];
+    fn pos(&self) -> Pos;
+    fn own_proj(&self) -> f32;
+    /// Score multiplier for the game's weather
+    fn weather(&self) -> f32 {
+        1.0
+    }
+
+    fn field(&self, column: &str) -> Option<f32> {
+        Self::COLUMNS
+            .iter()
+            .find(|(name, _)| *name == column)
+            .map(|(_, get)| get(self))
+    }
+}
+
+/// Columns `ModelInput::field` knows for each position
+pub fn columns(pos: &Pos) -> Vec<&'static str> {
+    fn names<P: ModelInput>() -> Vec<&'static str> {
+        P::COLUMNS.iter().map(|(name, _)| *name).collect()
+    }
+    match pos {
+        Pos::Qb => names::<QbProj>(),
+        Pos::Rb => names::<RbProj>(),
+        Pos::Wr | Pos::Te => names::<RecProj>(),
+        Pos::D => names::<DefProj>(),
+        Pos::K => names::<KickProj>(),
+    }
+}
+
+/// The columns in every proj table, then the ones given
+macro_rules! columns {
+    ($proj:ty; $($name:literal => $field:ident),* $(,)?) => {
+        &[
+            ("pts_proj", |p: &$proj| p.pts_proj),
+            ("cieling_proj", |p: &$proj| p.cieling_proj),
+            ("floor_proj", |p: &$proj| p.floor_proj),
+            ("pts_plus_minus_proj", |p: &$proj| p.pts_plus_minus_proj),
+            ("pts_sal_proj", |p: &$proj| p.pts_sal_proj),
+            ("vegas_total", |p: &$proj| p.vegas_total),
+            ("salary", |p: &$proj| p.salary as f32),
+            ("own_proj", |p: &$proj| p.own_proj),
+            ("rating", |p: &$proj| p.rating),
+            $(($name, |p: &$proj| p.$field),)*
+        ]
+    };
+}
+
+impl ModelInput for QbProj {
+    const COLUMNS: &'static [Column<Self>] = columns!(QbProj;
+        "vegas_team_total" => vegas_team_total,
+        "opp_def_pts_given" => opp_def_pts_given,
+        "avg_pass_atts" => avg_pass_atts,
+        "avg_pass_comps" => avg_pass_comps,
+        "avg_pass_yds" => avg_pass_yds,
+        "avg_pass_tds" => avg_pass_tds,
+        "avg_rush_atts" => avg_rush_atts,
+        "avg_long_pass_yds" => avg_long_pass_yds,
+        "pass_to_wr_per" => pass_to_wr_per,
+        "pass_to_te_per" => pass_to_te_per,
+        "wind_speed" => wind_speed,
+        "red_zone_op_pg" => red_zone_op_pg,
+        "month_consistency" => month_consistency,
+        "yds_per_pass_att" => yds_per_pass_att,
+        "avg_rush_yds" => avg_rush_yards,
+    );
+
+    fn pos(&self) -> Pos {
+        Pos::Qb
+    }
+
+    fn own_proj(&self) -> f32 {
+        self.own_proj
+    }
+
+    fn weather(&self) -> f32 {
+        self.weather
+    }
+}
+
+impl ModelInput for RbProj {
+    const COLUMNS: &'static [Column<Self>] = columns!(RbProj;
+        "vegas_team_total" => vegas_team_total,
+        "opp_def_pts_given" => opp_def_pts_given,
+        "rush_yds_share" => rush_yds_share,
+        "avg_atts" => avg_att,
+        "avg_td" => avg_td,
+        "avg_rush_yds" => avg_rush_yds,
+        "avg_rec_tgts" => avg_rec_tgts,
+        "snaps_per" => snaps_per,
+        "year_consistency" => year_consistency,
+        "month_consistency" => month_consistency,
+    );
+
+    fn pos(&self) -> Pos {
+        Pos::Rb
+    }
+
+    fn own_proj(&self) -> f32 {
+        self.own_proj
+    }
+}
+
+impl ModelInput for RecProj {
+    const COLUMNS: &'static [Column<Self>] = columns!(RecProj;
+        "vegas_team_total" => vegas_team_total,
+        "opp_def_pts_given" => opp_def_pts_given,
+        "avg_recp" => avg_rec,
+        "avg_tgts" => avg_tgts,
+        "avg_td" => avg_td,
+        "avg_rec_yds" => avg_rec_yds,
+        "avg_rush_yds" => avg_rush_yds,
+        "red_zone_op_pg" => red_zone_op_pg,
+        "rec_tgt_share" => rec_tgt_share,
+        "year_consistency" => year_consistency,
+        "year_upside" => year_upside,
+        "month_consistency" => month_consistency,
+        "month_upside" => month_upside,
+    );
+
+    fn pos(&self) -> Pos {
+        self.pos
+    }
+
+    fn own_proj(&self) -> f32 {
+        self.own_proj
+    }
+
+    fn weather(&self) -> f32 {
+        self.weather
+    }
+}
+
+impl ModelInput for DefProj {
+    const COLUMNS: &'static [Column<Self>] = columns!(DefProj;
+        "vegas_team_total" => vegas_team_total,
+        "vegas_opp_total" => vegas_opp_total,
+    );
+
+    fn pos(&self) -> Pos {
+        Pos::D
+    }
+
+    fn own_proj(&self) -> f32 {
+        self.own_proj
+    }
+
+    fn weather(&self) -> f32 {
+        self.weather
+    }
+}
+
+impl ModelInput for KickProj {
+    const COLUMNS: &'static [Column<Self>] = columns!(KickProj;);
+
+    fn pos(&self) -> Pos {
+        Pos::K
+    }
+
+    fn own_proj(&self) -> f32 {
+        self.own_proj
+    }
+
+    fn weather(&self) -> f32 {
+        self.weather
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::Team;
+
+    #[test]
+    fn test_default_model_round_trip() {
+        let model: ScoringModel = ScoringModel::default();
+        assert!(model.validate().is_ok());
+        let json: String = serde_json::to_string(&model).unwrap();
+        let read: ScoringModel = serde_json::from_str(&json).unwrap();
+        assert_eq!(read, model);
+        assert_eq!(read.wr.cap, 11.35);
+        assert_eq!(read.k.features[0].range_field.as_deref(), Some("pts_proj"));
+    }
+
+    #[test]
+    fn test_partial_model() {
+        let model: ScoringModel = serde_json::from_str(
+            r#"{"te": {"cap": 2.0, "features": [
+                {"name": "td", "field": "avg_td", "weight": 2.0, "when": "any_flex"}
+            ]}}"#,
+        )
+        .unwrap();
+        assert!(model.validate().is_ok());
+        assert_eq!(model.te.features[0].range, Range::Pos);
+        assert!(model.te.features[0].when.applies(true));
+        assert!(!model.te.features[0].when.applies(false));
+        // Positions left out keep the built in weights
+        assert_eq!(model.qb, ScoringModel::default().qb);
+
+        let bad: ScoringModel = serde_json::from_str(
+            r#"{"dst": {"features": [{"name": "td", "field": "avg_td", "weight": 1.0}]}}"#,
+        )
+        .unwrap();
+        assert!(bad.validate().is_err());
+    }
+
+    #[test]
+    fn test_explain_rules() {
+        // No features so nothing reads the slate
+        let model: PositionModel = PositionModel {
+            any_flex_bonus: 0.5,
+            cap: 1.0,
+            min_own: 2.0,
+            low_own: 5.0,
+            low_own_bonus: 0.25,
+            flat_bonus: 0.05,
+            ..PositionModel::default()
+        };
+        let mut k: KickProj = KickProj {
+            id: 1,
+            team: Team::Ari,
+            opp: Team::Atl,
+            pts_proj: 0.0,
+            cieling_proj: 0.0,
+            floor_proj: 0.0,
+            pts_plus_minus_proj: 0.0,
+            pts_sal_proj: 0.0,
+            vegas_total: 0.0,
+            salary: 0,
+            own_proj: 4.0,
+            rating: 0.0,
+            weather: 1.0,
+        };
+        let breakdown: Breakdown = model.explain(&k, true);
+        assert_eq!(breakdown.score, model.score(&k, true));
+        assert_eq!(breakdown.score, 0.8);
+        assert_eq!(
+            breakdown.rules,
+            vec![
+                "any flex bonus +0.5",
+                "own 4 under 5: +0.25",
+                "flat bonus +0.05"
+            ]
+        );
+
+        k.own_proj = 1.0;
+        let breakdown: Breakdown = model.explain(&k, false);
+        assert_eq!(breakdown.score, model.score(&k, false));
+        assert_eq!(breakdown.rules[1], "own 1 under 2: zeroed");
+    }
+}
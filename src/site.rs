@@ -0,0 +1,230 @@
+use serde::{Deserialize, Serialize};
+
+use crate::player::Pos;
+
+/// Contest site the lineups are built for
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Site {
+    FanDuel,
+    DraftKings,
+}
+
+impl Site {
+    pub fn try_from_str(input: &str) -> Option<Site> {
+        match input.to_lowercase().as_str() {
+            "fanduel" | "fd" => Some(Site::FanDuel),
+            "draftkings" | "dk" => Some(Site::DraftKings),
+            _ => None,
+        }
+    }
+
+    pub fn to_str(&self) -> &str {
+        match self {
+            Site::FanDuel => "fanduel",
+            Site::DraftKings => "draftkings",
+        }
+    }
+
+    pub fn salary_cap(&self) -> i32 {
+        match self {
+            Site::FanDuel => 60000,
+            Site::DraftKings => 50000,
+        }
+    }
+
+    /// Least a lineup spends when `--min-sal` isn't set, leaves a little of the
+    /// cap on the table
+    pub fn min_salary(&self) -> i32 {
+        self.salary_cap() - 500
+    }
+
+    /// Classic roster in the order the site lists it
+    pub fn roster_slots(&self) -> [&str; 9] {
+        match self {
+            Site::FanDuel => ["QB", "RB", "RB", "WR", "WR", "WR", "TE", "FLEX", "DEF"],
+            Site::DraftKings => ["QB", "RB", "RB", "WR", "WR", "WR", "TE", "FLEX", "DST"],
+        }
+    }
+
+    pub fn flex_positions(&self) -> &[Pos] {
+        match self {
+            Site::FanDuel => &[Pos::Rb, Pos::Wr],
+            Site::DraftKings => &[Pos::Rb, Pos::Wr, Pos::Te],
+        }
+    }
+
+    pub fn island_format(&self) -> IslandFormat {
+        match self {
+            Site::FanDuel => IslandFormat::Mvp,
+            Site::DraftKings => IslandFormat::Showdown,
+        }
+    }
+
+    pub fn scoring(&self) -> ScoringRules {
+        match self {
+            Site::FanDuel => ScoringRules {
+                reception: 0.5,
+                fumble_lost: -2.0,
+                yardage_bonus: 0.0,
+                ..ScoringRules::BASE
+            },
+            Site::DraftKings => ScoringRules {
+                reception: 1.0,
+                fumble_lost: -1.0,
+                yardage_bonus: 3.0,
+                ..ScoringRules::BASE
+            },
+        }
+    }
+}
+
+/// Single game roster, one boosted captain plus flex slots
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IslandFormat {
+    /// FanDuel MVP, 1.5x points at normal salary and four UTIL
+    Mvp,
+    /// DraftKings Showdown, Captain costs and scores 1.5x and five FLEX
+    Showdown,
+}
+
+impl IslandFormat {
+    pub const CAPTAIN_POINTS: f32 = 1.5;
+    /// Kickers in the slate, one for each team in the game. Showdown allows
+    /// them in every slot and MVP in the flex.
+    pub const KICKER_COUNT: i8 = 2;
+
+    pub fn captain_salary(&self, salary: i32) -> i32 {
+        match self {
+            IslandFormat::Mvp => salary,
+            IslandFormat::Showdown => salary * 3 / 2,
+        }
+    }
+
+    pub fn flex_count(&self) -> usize {
+        match self {
+            IslandFormat::Mvp => 4,
+            IslandFormat::Showdown => 5,
+        }
+    }
+
+    /// Positions tried in the captain slot, Showdown lets kickers and DSTs in
+    pub fn captain_positions(&self) -> &[Pos] {
+        match self {
+            IslandFormat::Mvp => &[Pos::Qb],
+            IslandFormat::Showdown => &[Pos::Qb, Pos::Rb, Pos::Wr, Pos::Te, Pos::K, Pos::D],
+        }
+    }
+
+    /// Bulk upload column headers, captain first
+    pub fn roster_slots(&self) -> Vec<&str> {
+        let (captain, flex) = match self {
+            IslandFormat::Mvp => ("MVP - 1.5X Points", "AnyFLEX"),
+            IslandFormat::Showdown => ("CPT", "FLEX"),
+        };
+        let mut slots: Vec<&str> = vec![captain];
+        slots.extend(vec![flex; self.flex_count()]);
+        slots
+    }
+
+    pub fn captain_label(&self) -> &str {
+        match self {
+            IslandFormat::Mvp => "MVP",
+            IslandFormat::Showdown => "CPT",
+        }
+    }
+}
+
+/// Offensive player scoring, points per stat
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScoringRules {
+    pub pass_yd: f32,
+    pub pass_td: f32,
+    pub interception: f32,
+    pub rush_yd: f32,
+    pub rec_yd: f32,
+    pub td: f32,
+    pub reception: f32,
+    pub fumble_lost: f32,
+    /// Bonus for 300 passing or 100 rushing/receiving yards
+    pub yardage_bonus: f32,
+}
+
+impl ScoringRules {
+    const BASE: ScoringRules = ScoringRules {
+        pass_yd: 0.04,
+        pass_td: 4.0,
+        interception: -1.0,
+        rush_yd: 0.1,
+        rec_yd: 0.1,
+        td: 6.0,
+        reception: 0.0,
+        fumble_lost: 0.0,
+        yardage_bonus: 0.0,
+    };
+
+    pub fn points(&self, stats: &StatLine) -> f32 {
+        let mut points: f32 = stats.pass_yds * self.pass_yd
+            + stats.pass_tds * self.pass_td
+            + stats.interceptions * self.interception
+            + stats.rush_yds * self.rush_yd
+            + stats.rec_yds * self.rec_yd
+            + (stats.rush_tds + stats.rec_tds) * self.td
+            + stats.receptions * self.reception
+            + stats.fumbles_lost * self.fumble_lost;
+        let bonuses: [bool; 3] = [
+            stats.pass_yds >= 300.0,
+            stats.rush_yds >= 100.0,
+            stats.rec_yds >= 100.0,
+        ];
+        points += bonuses.iter().filter(|b| **b).count() as f32 * self.yardage_bonus;
+        points
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct StatLine {
+    pub pass_yds: f32,
+    pub pass_tds: f32,
+    pub interceptions: f32,
+    pub rush_yds: f32,
+    pub rush_tds: f32,
+    pub receptions: f32,
+    pub rec_yds: f32,
+    pub rec_tds: f32,
+    pub fumbles_lost: f32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scoring() {
+        let stats: StatLine = StatLine {
+            receptions: 8.0,
+            rec_yds: 110.0,
+            rec_tds: 1.0,
+            fumbles_lost: 1.0,
+            ..StatLine::default()
+        };
+        assert_eq!(Site::FanDuel.scoring().points(&stats), 19.0);
+        assert_eq!(Site::DraftKings.scoring().points(&stats), 27.0);
+    }
+
+    #[test]
+    fn test_flex() {
+        assert!(!Site::FanDuel.flex_positions().contains(&Pos::Te));
+        assert!(Site::DraftKings.flex_positions().contains(&Pos::Te));
+        assert_eq!(Site::try_from_str("DK"), Some(Site::DraftKings));
+    }
+
+    #[test]
+    fn test_island_format() {
+        let showdown: IslandFormat = Site::DraftKings.island_format();
+        assert_eq!(showdown.captain_salary(7100), 10650);
+        assert_eq!(showdown.flex_count(), 5);
+        assert!(showdown.captain_positions().contains(&Pos::K));
+        assert_eq!(Site::FanDuel.island_format().captain_salary(7100), 7100);
+    }
+}
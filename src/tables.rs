@@ -1,9 +1,261 @@
-use rusqlite::Connection;
+use std::error::Error;
+
+use rusqlite::{Connection, OptionalExtension};
 
 use crate::DATABASE_FILE;
 
-pub fn init_tables() {
-    let conn: Connection = Connection::open(DATABASE_FILE).expect("Can't open DB File");
+/// Bump with every migration, the optimizer won't run on an older database
+pub const SCHEMA_VERSION: i32 = 5;
+
+enum Change {
+    /// Every table in `create_tables` that doesn't exist yet
+    CreateTables,
+    /// Added to each table that doesn't have it yet, new databases already
+    /// get it from `create_tables`
+    AddColumn {
+        tables: &'static [&'static str],
+        column: &'static str,
+        definition: &'static str,
+    },
+    /// For key changes sqlite can't alter in place. Each table without
+    /// `column` is recreated from `create_tables` and its old rows copied over
+    /// with `copy` as the select list for the new table's columns.
+    Rebuild {
+        tables: &'static [&'static str],
+        column: &'static str,
+        copy: &'static str,
+    },
+    /// Like `Rebuild` for a new UNIQUE key over columns the table already has.
+    /// Each table whose schema doesn't have `key` yet is recreated and its
+    /// rows copied over as they are.
+    Rekey {
+        tables: &'static [&'static str],
+        key: &'static str,
+    },
+}
+
+struct Migration {
+    version: i32,
+    changes: &'static [Change],
+}
+
+const DEF_VS_POS_TABLES: &[&str] = &["def_vs_qb", "def_vs_rb", "def_vs_wr", "def_vs_te"];
+
+/// Rows loaded before def vs pos had a week go to the latest loaded slate
+const LATEST_SLATE_WEEK: &str = "id,
+    (SELECT COALESCE(MAX(season), 0) FROM ownership),
+    (SELECT COALESCE(MAX(week), 0) FROM ownership
+        WHERE season = (SELECT MAX(season) FROM ownership)),
+    team_name, pts_given_pg";
+
+const PROJ_TABLES: &[&str] = &[
+    "qb_proj",
+    "rb_proj",
+    "wr_proj",
+    "te_proj",
+    "dst_proj",
+    "kick_proj",
+];
+
+/// Version 1 is the schema from before there was a version table, a database
+/// with tables but no version starts there
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 2,
+        changes: &[
+            Change::CreateTables,
+            Change::AddColumn {
+                tables: PROJ_TABLES,
+                column: "exposure_min",
+                definition: "REAL NOT NULL DEFAULT 0.0",
+            },
+            Change::AddColumn {
+                tables: PROJ_TABLES,
+                column: "exposure_max",
+                definition: "REAL NOT NULL DEFAULT 100.0",
+            },
+            Change::AddColumn {
+                tables: &["ownership"],
+                column: "site_id",
+                definition: "TEXT",
+            },
+        ],
+    },
+    Migration {
+        version: 3,
+        changes: &[Change::CreateTables],
+    },
+    Migration {
+        version: 4,
+        changes: &[Change::Rebuild {
+            tables: DEF_VS_POS_TABLES,
+            column: "season",
+            copy: LATEST_SLATE_WEEK,
+        }],
+    },
+    Migration {
+        version: 5,
+        changes: &[Change::Rekey {
+            tables: &["slate"],
+            key: "UNIQUE(id, season, week, day, site)",
+        }],
+    },
+];
+
+/// Creates or upgrades the database file
+pub fn init_tables() -> Result<(), Box<dyn Error>> {
+    let conn: Connection = Connection::open(DATABASE_FILE)?;
+    let from: i32 = schema_version(&conn)?;
+    let to: i32 = migrate(&conn)?;
+    if from == to {
+        println!("Schema is up to date at version {}", to);
+    } else {
+        println!("Migrated schema from version {} to {}", from, to);
+    }
+    Ok(())
+}
+
+/// 0 for an empty database
+pub fn schema_version(conn: &Connection) -> rusqlite::Result<i32> {
+    if table_exists(conn, "schema_version")? {
+        return conn.query_row("SELECT version FROM schema_version", (), |row| row.get(0));
+    }
+    Ok(if table_exists(conn, "player")? { 1 } else { 0 })
+}
+
+/// Runs every migration newer than the database, each one in a transaction
+pub fn migrate(conn: &Connection) -> rusqlite::Result<i32> {
+    let version: i32 = schema_version(conn)?;
+    if version == 0 {
+        let tx = conn.unchecked_transaction()?;
+        create_tables(&tx)?;
+        set_version(&tx, SCHEMA_VERSION)?;
+        tx.commit()?;
+        return Ok(SCHEMA_VERSION);
+    }
+    for migration in MIGRATIONS.iter().filter(|m| m.version > version) {
+        let tx = conn.unchecked_transaction()?;
+        for change in migration.changes {
+            apply(&tx, change)?;
+        }
+        set_version(&tx, migration.version)?;
+        tx.commit()?;
+    }
+    schema_version(conn)
+}
+
+/// Errors unless the database is at `SCHEMA_VERSION`
+pub fn check_schema(conn: &Connection) -> Result<(), String> {
+    let version: i32 = schema_version(conn).map_err(|e| e.to_string())?;
+    match version {
+        SCHEMA_VERSION => Ok(()),
+        0 => Err("Database has no tables, run `dfstimizer init-db`".to_string()),
+        _ if version > SCHEMA_VERSION => Err(format!(
+            "Database schema version {} is newer than this build ({})",
+            version, SCHEMA_VERSION
+        )),
+        _ => Err(format!(
+            "Database schema version {} is out of date, run `dfstimizer migrate` to upgrade to {}",
+            version, SCHEMA_VERSION
+        )),
+    }
+}
+
+fn apply(conn: &Connection, change: &Change) -> rusqlite::Result<()> {
+    match change {
+        Change::CreateTables => create_tables(conn),
+        Change::AddColumn {
+            tables,
+            column,
+            definition,
+        } => {
+            for table in tables.iter() {
+                if table_exists(conn, table)? && !column_exists(conn, table, column)? {
+                    conn.execute(
+                        &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition),
+                        (),
+                    )?;
+                }
+            }
+            Ok(())
+        }
+        Change::Rebuild {
+            tables,
+            column,
+            copy,
+        } => {
+            for table in tables.iter() {
+                if !table_exists(conn, table)? || column_exists(conn, table, column)? {
+                    continue;
+                }
+                let old: String = format!("{}_old", table);
+                conn.execute(&format!("ALTER TABLE {} RENAME TO {}", table, old), ())?;
+                create_tables(conn)?;
+                conn.execute(
+                    &format!("INSERT INTO {} SELECT {} FROM {}", table, copy, old),
+                    (),
+                )?;
+                conn.execute(&format!("DROP TABLE {}", old), ())?;
+            }
+            Ok(())
+        }
+        Change::Rekey { tables, key } => {
+            for table in tables.iter() {
+                if !table_exists(conn, table)? || table_sql(conn, table)?.contains(key) {
+                    continue;
+                }
+                let old: String = format!("{}_old", table);
+                conn.execute(&format!("ALTER TABLE {} RENAME TO {}", table, old), ())?;
+                create_tables(conn)?;
+                conn.execute(&format!("INSERT INTO {} SELECT * FROM {}", table, old), ())?;
+                conn.execute(&format!("DROP TABLE {}", old), ())?;
+            }
+            Ok(())
+        }
+    }
+}
+
+fn set_version(conn: &Connection, version: i32) -> rusqlite::Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS schema_version (version INTEGER NOT NULL)",
+        (),
+    )?;
+    conn.execute("DELETE FROM schema_version", ())?;
+    conn.execute(
+        "INSERT INTO schema_version (version) VALUES (?1)",
+        (version,),
+    )?;
+    Ok(())
+}
+
+fn table_exists(conn: &Connection, table: &str) -> rusqlite::Result<bool> {
+    conn.query_row(
+        "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1",
+        (table,),
+        |_| Ok(()),
+    )
+    .optional()
+    .map(|found| found.is_some())
+}
+
+fn table_sql(conn: &Connection, table: &str) -> rusqlite::Result<String> {
+    conn.query_row(
+        "SELECT sql FROM sqlite_master WHERE type = 'table' AND name = ?1",
+        (table,),
+        |row| row.get(0),
+    )
+}
+
+fn column_exists(conn: &Connection, table: &str, column: &str) -> rusqlite::Result<bool> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let columns: Vec<String> = stmt
+        .query_map((), |row| row.get(1))?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(columns.iter().any(|c| c == column))
+}
+
+/// The current schema, what a new database starts with
+fn create_tables(conn: &Connection) -> rusqlite::Result<()> {
     let player: &str = "
         CREATE TABLE IF NOT EXISTS player (
             id INTEGER PRIMARY KEY AUTOINCREMENT,
@@ -41,6 +293,8 @@ pub fn init_tables() {
             vegas_team_total REAL NOT NULL,
             month_consistency REAL NOT NULL,
             day TEXT NOT NULL,
+            exposure_min REAL NOT NULL DEFAULT 0.0,
+            exposure_max REAL NOT NULL DEFAULT 100.0,
             FOREIGN key(id) REFERENCES player(id),
             UNIQUE(id, season, week) on CONFLICT REPLACE
         )
@@ -66,6 +320,8 @@ pub fn init_tables() {
             vegas_opp_total REAL NOT NULL,
             day TEXT NOT NULL,
             vegas_team_total REAL NOT NULL,
+            exposure_min REAL NOT NULL DEFAULT 0.0,
+            exposure_max REAL NOT NULL DEFAULT 100.0,
             FOREIGN key(id) REFERENCES player(id),
             UNIQUE(id, season, week) on CONFLICT REPLACE
         )
@@ -89,6 +345,8 @@ pub fn init_tables() {
             own_proj REAL NOT NULL,
             rating REAL NOT NULL,
             day TEXT NOT NULL,
+            exposure_min REAL NOT NULL DEFAULT 0.0,
+            exposure_max REAL NOT NULL DEFAULT 100.0,
             FOREIGN key(id) REFERENCES player(id),
             UNIQUE(id, season, week) on CONFLICT REPLACE
         )
@@ -126,6 +384,8 @@ pub fn init_tables() {
             yds_per_pass_att REAL NOT NULL,
             day TEXT NOT NULL,
             avg_rush_yds REAL NOT NULL,
+            exposure_min REAL NOT NULL DEFAULT 0.0,
+            exposure_max REAL NOT NULL DEFAULT 100.0,
             FOREIGN key(id) REFERENCES player(id),
             UNIQUE(id, season, week) on CONFLICT REPLACE
         )
@@ -161,6 +421,8 @@ pub fn init_tables() {
             month_consistency REAL NOT NULL,
             month_upside REAL NOT NULL,
             day TEXT NOT NULL,
+            exposure_min REAL NOT NULL DEFAULT 0.0,
+            exposure_max REAL NOT NULL DEFAULT 100.0,
             FOREIGN key(id) REFERENCES player(id),
             UNIQUE(id, season, week) on CONFLICT REPLACE
         )
@@ -196,6 +458,8 @@ pub fn init_tables() {
             month_consistency REAL NOT NULL,
             month_upside REAL NOT NULL,
             day TEXT NOT NULL,
+            exposure_min REAL NOT NULL DEFAULT 0.0,
+            exposure_max REAL NOT NULL DEFAULT 100.0,
             FOREIGN key(id) REFERENCES player(id),
             UNIQUE(id, season, week) on CONFLICT REPLACE
         )
@@ -214,6 +478,77 @@ pub fn init_tables() {
             pos REAL NOT NULL,
             salary INTEGER NOT NULL,
             own_per REAL NOT NULL,
+            site_id TEXT,
+            FOREIGN key(id) REFERENCES player(id),
+            UNIQUE(id, season, week) on CONFLICT REPLACE
+        )
+    ";
+
+    // Other spellings of a player's name, keyed by the normalized name
+    let player_alias: &str = "
+        CREATE TABLE IF NOT EXISTS player_alias (
+            alias TEXT NOT NULL,
+            pos TEXT NOT NULL,
+            id INTEGER NOT NULL,
+            FOREIGN key(id) REFERENCES player(id),
+            UNIQUE(alias, pos) on CONFLICT REPLACE
+        )
+    ";
+
+    // Official site salary file, the player pool when one is loaded
+    let slate: &str = "
+        CREATE TABLE IF NOT EXISTS slate (
+            id INTEGER NOT NULL,
+            season INTEGER NOT NULL,
+            week INTEGER NOT NULL,
+            day TEXT NOT NULL,
+            site TEXT NOT NULL,
+            site_id TEXT NOT NULL,
+            salary INTEGER NOT NULL,
+            game TEXT NOT NULL,
+            injury TEXT,
+            FOREIGN key(id) REFERENCES player(id),
+            UNIQUE(id, season, week, day, site) on CONFLICT REPLACE
+        )
+    ";
+
+    // Vegas lines and weather for each team's game, one row per side
+    let game_env: &str = "
+        CREATE TABLE IF NOT EXISTS game_env (
+            season INTEGER NOT NULL,
+            week INTEGER NOT NULL,
+            team TEXT NOT NULL,
+            opp TEXT NOT NULL,
+            spread REAL NOT NULL,
+            total REAL NOT NULL,
+            team_total REAL NOT NULL,
+            opp_total REAL NOT NULL,
+            temperature REAL NOT NULL,
+            wind_speed REAL NOT NULL,
+            precip_per REAL NOT NULL,
+            UNIQUE(season, week, team) on CONFLICT REPLACE
+        )
+    ";
+
+    // Usage numbers from the projection file the proj tables don't keep
+    let player_share: &str = "
+        CREATE TABLE IF NOT EXISTS player_share (
+            id INTEGER NOT NULL,
+            season INTEGER NOT NULL,
+            week INTEGER NOT NULL,
+            imp_pts_proj REAL NOT NULL,
+            snaps_share REAL NOT NULL,
+            rec_tgt_share REAL NOT NULL,
+            rec_td_tgt_share REAL NOT NULL,
+            rec_yds_share REAL NOT NULL,
+            rush_td_share REAL NOT NULL,
+            rush_yds_share REAL NOT NULL,
+            pass_to_rb_per REAL NOT NULL,
+            proj_rush_yds REAL NOT NULL,
+            yds_per_carry REAL NOT NULL,
+            yds_per_tgt REAL NOT NULL,
+            yds_per_rec REAL NOT NULL,
+            avg_long_rec_yds REAL NOT NULL,
             FOREIGN key(id) REFERENCES player(id),
             UNIQUE(id, season, week) on CONFLICT REPLACE
         )
@@ -222,37 +557,45 @@ pub fn init_tables() {
     let def_vs_qb: &str = "
         CREATE TABLE IF NOT EXISTS def_vs_qb (
             id INTEGER NOT NULL,
+            season INTEGER NOT NULL,
+            week INTEGER NOT NULL,
             team_name TEXT NOT NULL,
             pts_given_pg REAL NOT NULL,
             FOREIGN key(id) REFERENCES player(id),
-            UNIQUE(id) on CONFLICT REPLACE 
+            UNIQUE(id, season, week) on CONFLICT REPLACE
         )
     ";
     let def_vs_te: &str = "
         CREATE TABLE IF NOT EXISTS def_vs_te (
             id INTEGER NOT NULL,
+            season INTEGER NOT NULL,
+            week INTEGER NOT NULL,
             team_name TEXT NOT NULL,
             pts_given_pg REAL NOT NULL,
             FOREIGN key(id) REFERENCES player(id),
-            UNIQUE(id) on CONFLICT REPLACE 
+            UNIQUE(id, season, week) on CONFLICT REPLACE
         )
     ";
     let def_vs_wr: &str = "
         CREATE TABLE IF NOT EXISTS def_vs_wr (
             id INTEGER NOT NULL,
+            season INTEGER NOT NULL,
+            week INTEGER NOT NULL,
             team_name TEXT NOT NULL,
             pts_given_pg REAL NOT NULL,
             FOREIGN key(id) REFERENCES player(id),
-            UNIQUE(id) on CONFLICT REPLACE 
+            UNIQUE(id, season, week) on CONFLICT REPLACE
         )
     ";
     let def_vs_rb: &str = "
         CREATE TABLE IF NOT EXISTS def_vs_rb (
             id INTEGER NOT NULL,
+            season INTEGER NOT NULL,
+            week INTEGER NOT NULL,
             team_name TEXT NOT NULL,
             pts_given_pg REAL NOT NULL,
             FOREIGN key(id) REFERENCES player(id),
-            UNIQUE(id) on CONFLICT REPLACE 
+            UNIQUE(id, season, week) on CONFLICT REPLACE
         )
     ";
 
@@ -277,11 +620,135 @@ pub fn init_tables() {
         )
     ";
 
-    let tables: [&str; 14] = [
-        player, qb_proj, wr_proj, dst_proj, te_proj, rb_proj, ownership, kick_proj, def_vs_qb,
-        def_vs_rb, def_vs_te, def_vs_wr, max_score, stats,
+    let tables: [&str; 18] = [
+        player,
+        player_alias,
+        qb_proj,
+        wr_proj,
+        dst_proj,
+        te_proj,
+        rb_proj,
+        ownership,
+        slate,
+        kick_proj,
+        game_env,
+        player_share,
+        def_vs_qb,
+        def_vs_rb,
+        def_vs_te,
+        def_vs_wr,
+        max_score,
+        stats,
     ];
     for table in tables {
-        conn.execute(table, ()).expect("Could not create table");
+        conn.execute(table, ())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrate_new_database() {
+        let conn: Connection = Connection::open_in_memory().unwrap();
+        assert_eq!(schema_version(&conn).unwrap(), 0);
+        assert!(check_schema(&conn).is_err());
+        assert_eq!(migrate(&conn).unwrap(), SCHEMA_VERSION);
+        assert!(check_schema(&conn).is_ok());
+        assert!(column_exists(&conn, "ownership", "site_id").unwrap());
+    }
+
+    #[test]
+    fn test_migrate_legacy_database() {
+        let conn: Connection = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE player (id INTEGER PRIMARY KEY, name TEXT, team TEXT, pos TEXT);
+            CREATE TABLE wr_proj (id INTEGER NOT NULL, season INTEGER NOT NULL);
+            CREATE TABLE ownership (id INTEGER NOT NULL, own_per REAL NOT NULL);
+            INSERT INTO wr_proj VALUES (1, 2023);",
+        )
+        .unwrap();
+        assert_eq!(schema_version(&conn).unwrap(), 1);
+        assert!(check_schema(&conn).is_err());
+        assert_eq!(migrate(&conn).unwrap(), SCHEMA_VERSION);
+        let exposure_max: f32 = conn
+            .query_row("SELECT exposure_max FROM wr_proj", (), |row| row.get(0))
+            .unwrap();
+        assert_eq!(exposure_max, 100.0);
+        assert!(table_exists(&conn, "slate").unwrap());
+        assert!(table_exists(&conn, "game_env").unwrap());
+        // Nothing left to do the second time
+        assert_eq!(migrate(&conn).unwrap(), SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn test_migrate_def_vs_pos_week() {
+        let conn: Connection = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE player (id INTEGER PRIMARY KEY, name TEXT, team TEXT, pos TEXT);
+            CREATE TABLE ownership (id INTEGER NOT NULL, season INTEGER NOT NULL, week INTEGER NOT NULL);
+            CREATE TABLE def_vs_wr (id INTEGER NOT NULL, team_name TEXT NOT NULL,
+                pts_given_pg REAL NOT NULL, UNIQUE(id) on CONFLICT REPLACE);
+            INSERT INTO player VALUES (1, 'New York Jets', 'NYJ', 'D');
+            INSERT INTO ownership VALUES (1, 2022, 17), (1, 2023, 3), (1, 2023, 4);
+            INSERT INTO def_vs_wr VALUES (1, 'NYJ', 31.5);",
+        )
+        .unwrap();
+        assert_eq!(migrate(&conn).unwrap(), SCHEMA_VERSION);
+        let row: (i16, i8, f32) = conn
+            .query_row(
+                "SELECT season, week, pts_given_pg FROM def_vs_wr",
+                (),
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!(row, (2023, 4, 31.5));
+        // A later week is a new row instead of replacing this one
+        conn.execute(
+            "INSERT INTO def_vs_wr (id, season, week, team_name, pts_given_pg)
+            VALUES (1, 2023, 5, 'NYJ', 28.0)",
+            (),
+        )
+        .unwrap();
+        let count: i32 = conn
+            .query_row("SELECT COUNT(*) FROM def_vs_wr", (), |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 2);
+        assert!(!table_exists(&conn, "def_vs_wr_old").unwrap());
+    }
+
+    #[test]
+    fn test_migrate_slate_site_key() {
+        let conn: Connection = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE player (id INTEGER PRIMARY KEY, name TEXT, team TEXT, pos TEXT);
+            CREATE TABLE slate (id INTEGER NOT NULL, season INTEGER NOT NULL,
+                week INTEGER NOT NULL, day TEXT NOT NULL, site TEXT NOT NULL,
+                site_id TEXT NOT NULL, salary INTEGER NOT NULL, game TEXT NOT NULL,
+                injury TEXT, UNIQUE(id, season, week, day) on CONFLICT REPLACE);
+            INSERT INTO player VALUES (1, 'Josh Allen', 'BUF', 'QB');
+            INSERT INTO slate VALUES (1, 2023, 4, 'sun', 'fanduel', '91142-1', 9000, 'BUF@MIA', NULL);
+            CREATE TABLE schema_version (version INTEGER NOT NULL);
+            INSERT INTO schema_version VALUES (4);",
+        )
+        .unwrap();
+        assert_eq!(migrate(&conn).unwrap(), SCHEMA_VERSION);
+        // The other site's row for the same player no longer replaces it
+        conn.execute(
+            "INSERT INTO slate VALUES (1, 2023, 4, 'sun', 'draftkings', '3001', 7000, 'BUF@MIA', NULL)",
+            (),
+        )
+        .unwrap();
+        let salaries: Vec<i32> = conn
+            .prepare("SELECT salary FROM slate ORDER BY salary")
+            .unwrap()
+            .query_map((), |row| row.get(0))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        assert_eq!(salaries, vec![7000, 9000]);
+        assert!(!table_exists(&conn, "slate_old").unwrap());
     }
 }
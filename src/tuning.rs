@@ -0,0 +1,340 @@
+use std::error::Error;
+use std::sync::Arc;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rusqlite::Connection;
+
+use crate::clear_slate_caches;
+use crate::config::SlateConfig;
+use crate::exact_optimizer::build_exact_lineups;
+use crate::lineup::Lineup;
+use crate::player::*;
+use crate::scorer::{HeuristicScorer, Scorer, ScorerKind};
+use crate::scoring::ScoringModel;
+use crate::{Day, DATABASE_FILE};
+
+/// What a tuning run tries to raise
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Objective {
+    /// Mean correlation between player scores and actual points, by slate and position
+    #[default]
+    Correlation,
+    /// Lineups from the exact solver whose actual points beat the threshold,
+    /// runs the solver for every slate on every try so keep the iterations low
+    TopLineups,
+}
+
+impl Objective {
+    pub fn try_from_str(input: &str) -> Option<Objective> {
+        match input.to_lowercase().as_str() {
+            "correlation" | "corr" => Some(Objective::Correlation),
+            "top-lineups" | "top" => Some(Objective::TopLineups),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TuneOptions {
+    pub objective: Objective,
+    pub iterations: usize,
+    /// Actual points a lineup needs to count for `Objective::TopLineups`
+    pub threshold: f32,
+    pub seed: u64,
+    /// Most a weight moves in one try, as a share of the weight
+    pub step: f32,
+}
+
+impl Default for TuneOptions {
+    fn default() -> Self {
+        TuneOptions {
+            objective: Objective::Correlation,
+            iterations: 200,
+            threshold: 160.0,
+            seed: 7,
+            step: 0.5,
+        }
+    }
+}
+
+/// A loaded week with actual points, `config` is the tuning config pointed at it
+pub struct TuneSlate {
+    pub config: SlateConfig,
+    /// Projections and the points each player scored
+    pub players: Vec<(Proj, f32)>,
+}
+
+/// The best model found and what the objective gave it and the starting model
+pub struct Tuned {
+    pub model: ScoringModel,
+    pub start: f32,
+    pub best: f32,
+}
+
+/// Every week in the database with both projections and fantasy points, the
+/// rest of the config carries over to each one
+pub fn tuning_slates(config: &SlateConfig, conn: &Connection) -> Vec<TuneSlate> {
+    let mut stmt = conn
+        .prepare_cached(
+            "SELECT DISTINCT o.season, o.week, o.day FROM ownership o
+            WHERE EXISTS (SELECT 1 FROM fan_pts f
+                WHERE f.id = o.id AND f.week = o.week AND f.season = o.season)
+            ORDER BY o.season, o.week, o.day",
+        )
+        .unwrap();
+    let weeks: Vec<(i16, i8, String)> = stmt
+        .query_map((), |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .unwrap()
+        .map(|r| r.unwrap())
+        .collect();
+    let mut slates: Vec<TuneSlate> = Vec::new();
+    for (season, week, day) in weeks {
+        let config: SlateConfig = SlateConfig {
+            season,
+            week,
+            day: Day::from_str(&day),
+            ..config.clone()
+        };
+        clear_slate_caches();
+        config.install();
+        let missing: Vec<String> = missing_def_vs_pos(season, week, &config.day, conn);
+        if !missing.is_empty() {
+            println!(
+                "{} week {}: no def vs pos numbers for {}, using the league average",
+                season,
+                week,
+                missing.join(", ")
+            );
+        }
+        let mut players: Vec<(Proj, f32)> = Vec::new();
+        for pos in [Pos::Qb, Pos::Rb, Pos::Wr, Pos::Te, Pos::D, Pos::K] {
+            for (id, pts) in slate_points(&config, &pos, conn) {
+                players.push((query_proj_helper(id, &pos, week, season, conn), pts));
+            }
+        }
+        slates.push(TuneSlate { config, players });
+    }
+    clear_slate_caches();
+    slates
+}
+
+/// (id, actual points) for everyone with a projection at the position
+fn slate_points(config: &SlateConfig, pos: &Pos, conn: &Connection) -> Vec<(i16, f32)> {
+    let mut stmt = conn
+        .prepare_cached(
+            format!(
+                "SELECT p.id, f.pts FROM {} p
+                JOIN fan_pts f ON f.id = p.id AND f.week = p.week AND f.season = p.season
+                WHERE p.season = ?1 AND p.week = ?2 AND p.day = ?3",
+                pos.get_proj_table()
+            )
+            .as_str(),
+        )
+        .unwrap();
+    stmt.query_map((config.season, config.week, config.day.to_str()), |row| {
+        Ok((row.get(0)?, row.get(1)?))
+    })
+    .unwrap()
+    .map(|r| r.unwrap())
+    .collect()
+}
+
+/// Random search from `start`, each try moves one weight and is kept when
+/// the objective goes up. Puts `config` and `start` back when it's done.
+pub fn tune(
+    start: &ScoringModel,
+    slates: &[TuneSlate],
+    options: &TuneOptions,
+    config: &SlateConfig,
+) -> Result<Tuned, Box<dyn Error>> {
+    let tuned: Result<Tuned, Box<dyn Error>> = search(start, slates, options);
+    config.install();
+    start.install();
+    tuned
+}
+
+fn search(
+    start: &ScoringModel,
+    slates: &[TuneSlate],
+    options: &TuneOptions,
+) -> Result<Tuned, Box<dyn Error>> {
+    let mut rng: StdRng = StdRng::seed_from_u64(options.seed);
+    let mut model: ScoringModel = start.clone();
+    let first: f32 = evaluate(&model, slates, options)?;
+    let mut best: f32 = first;
+    println!("Start: {}", first);
+    for iteration in 0..options.iterations {
+        let mut candidate: ScoringModel = model.clone();
+        let name: String = perturb(&mut candidate, options, &mut rng);
+        let value: f32 = evaluate(&candidate, slates, options)?;
+        if value > best {
+            println!("{}: {} -> {} ({})", iteration, best, value, name);
+            best = value;
+            model = candidate;
+        }
+    }
+    Ok(Tuned {
+        model,
+        start: first,
+        best,
+    })
+}
+
+/// Moves one weight by up to `step` of itself, weights at 0 move by up to
+/// `step`. Returns the feature's name.
+fn perturb(model: &mut ScoringModel, options: &TuneOptions, rng: &mut StdRng) -> String {
+    let with_stack: bool = options.objective == Objective::TopLineups;
+    let mut features: Vec<(String, &mut f32)> = Vec::new();
+    for (pos, position) in [
+        ("qb", &mut model.qb),
+        ("rb", &mut model.rb),
+        ("wr", &mut model.wr),
+        ("te", &mut model.te),
+        ("dst", &mut model.dst),
+        ("k", &mut model.k),
+    ] {
+        for feature in position.features.iter_mut() {
+            features.push((format!("{}.{}", pos, feature.name), &mut feature.weight));
+        }
+    }
+    if with_stack {
+        for feature in model.stack.features.iter_mut() {
+            features.push((format!("stack.{}", feature.name), &mut feature.weight));
+        }
+    }
+    if features.is_empty() {
+        return String::new();
+    }
+    let index: usize = rng.gen_range(0..features.len());
+    let (name, weight) = &mut features[index];
+    let change: f32 = rng.gen_range(-options.step..=options.step);
+    if **weight == 0.0 {
+        **weight = change;
+    } else {
+        **weight *= 1.0 + change;
+    }
+    name.clone()
+}
+
+fn evaluate(
+    model: &ScoringModel,
+    slates: &[TuneSlate],
+    options: &TuneOptions,
+) -> Result<f32, Box<dyn Error>> {
+    match options.objective {
+        Objective::Correlation => {
+            let correlations: Vec<f32> = slates
+                .iter()
+                .flat_map(|slate| slate_correlations(model, slate))
+                .collect();
+            if correlations.is_empty() {
+                return Ok(0.0);
+            }
+            Ok(correlations.iter().sum::<f32>() / correlations.len() as f32)
+        }
+        Objective::TopLineups => {
+            let mut total: f32 = 0.0;
+            for slate in slates {
+                total += top_lineups(model, slate, options.threshold)? as f32;
+            }
+            Ok(total)
+        }
+    }
+}
+
+/// Correlation for each position on the slate with enough players to have one
+fn slate_correlations(model: &ScoringModel, slate: &TuneSlate) -> Vec<f32> {
+    slate.config.install();
+    model.install();
+    let scorer: HeuristicScorer = HeuristicScorer::new();
+    [Pos::Qb, Pos::Rb, Pos::Wr, Pos::Te, Pos::D, Pos::K]
+        .iter()
+        .filter_map(|pos| {
+            let pairs: Vec<(f32, f32)> = slate
+                .players
+                .iter()
+                .filter(|(proj, _)| proj.get_pos() == *pos)
+                .map(|(proj, pts)| (scorer.player(proj, false), *pts))
+                .collect();
+            pearson(&pairs)
+        })
+        .collect()
+}
+
+fn top_lineups(
+    model: &ScoringModel,
+    slate: &TuneSlate,
+    threshold: f32,
+) -> Result<usize, Box<dyn Error>> {
+    clear_slate_caches();
+    slate.config.install();
+    model.install();
+    let scorer: Arc<dyn Scorer> = ScorerKind::Heuristic.build();
+    let lineups: Vec<Lineup> = build_exact_lineups(&slate.config, &scorer)?;
+    let conn: Connection = Connection::open(DATABASE_FILE)?;
+    Ok(lineups
+        .iter()
+        .filter(|l| l.historic_score(slate.config.week, slate.config.season, &conn) > threshold)
+        .count())
+}
+
+/// None with under 3 pairs or when either side doesn't vary
+fn pearson(pairs: &[(f32, f32)]) -> Option<f32> {
+    if pairs.len() < 3 {
+        return None;
+    }
+    let count: f32 = pairs.len() as f32;
+    let mean_x: f32 = pairs.iter().map(|(x, _)| x).sum::<f32>() / count;
+    let mean_y: f32 = pairs.iter().map(|(_, y)| y).sum::<f32>() / count;
+    let mut cov: f32 = 0.0;
+    let mut var_x: f32 = 0.0;
+    let mut var_y: f32 = 0.0;
+    for (x, y) in pairs {
+        cov += (x - mean_x) * (y - mean_y);
+        var_x += (x - mean_x).powi(2);
+        var_y += (y - mean_y).powi(2);
+    }
+    if var_x == 0.0 || var_y == 0.0 {
+        return None;
+    }
+    Some(cov / (var_x * var_y).sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pearson() {
+        let up: Vec<(f32, f32)> = vec![(1.0, 2.0), (2.0, 4.0), (3.0, 6.0)];
+        assert!((pearson(&up).unwrap() - 1.0).abs() < 1e-6);
+        let down: Vec<(f32, f32)> = vec![(1.0, 3.0), (2.0, 2.0), (3.0, 1.0)];
+        assert!((pearson(&down).unwrap() + 1.0).abs() < 1e-6);
+        assert_eq!(pearson(&up[..2]), None);
+        assert_eq!(pearson(&[(1.0, 1.0), (1.0, 2.0), (1.0, 3.0)]), None);
+    }
+
+    #[test]
+    fn test_perturb_moves_one_weight() {
+        let options: TuneOptions = TuneOptions::default();
+        let mut rng: StdRng = StdRng::seed_from_u64(options.seed);
+        let start: ScoringModel = ScoringModel::default();
+        let mut model: ScoringModel = start.clone();
+        let name: String = perturb(&mut model, &options, &mut rng);
+        let weights = |m: &ScoringModel| -> Vec<f32> {
+            [&m.qb, &m.rb, &m.wr, &m.te, &m.dst, &m.k]
+                .iter()
+                .flat_map(|p| p.features.iter().map(|f| f.weight))
+                .collect()
+        };
+        let changed: usize = weights(&start)
+            .iter()
+            .zip(weights(&model))
+            .filter(|(a, b)| **a != *b)
+            .count();
+        assert_eq!(changed, 1);
+        assert!(!name.starts_with("stack"));
+        assert_eq!(model.stack, start.stack);
+    }
+}
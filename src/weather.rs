@@ -0,0 +1,111 @@
+use serde::{Deserialize, Serialize};
+
+use crate::player::{GameEnv, Pos};
+
+/// Score adjustments for bad weather. Passing and kicking games get marked
+/// down, the DST on the other side of them gets a boost.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WeatherRules {
+    pub enabled: bool,
+    /// Wind (mph) under this doesn't change anything
+    pub wind_threshold: f32,
+    /// Taken off for each mph over the threshold
+    pub wind_penalty: f32,
+    /// Precipitation chance (0-100) that counts as a wet game
+    pub precip_threshold: f32,
+    pub precip_penalty: f32,
+    /// Temperature (F) under this counts as a cold game
+    pub cold_threshold: f32,
+    pub cold_penalty: f32,
+    /// Kickers lose this many times the passing penalty
+    pub kicker_scale: f32,
+    /// DSTs gain this share of the passing penalty
+    pub dst_scale: f32,
+    /// Most a game can be marked down
+    pub max_penalty: f32,
+}
+
+impl Default for WeatherRules {
+    fn default() -> Self {
+        WeatherRules {
+            enabled: true,
+            wind_threshold: 15.0,
+            wind_penalty: 0.02,
+            precip_threshold: 50.0,
+            precip_penalty: 0.05,
+            cold_threshold: 25.0,
+            cold_penalty: 0.03,
+            kicker_scale: 1.5,
+            dst_scale: 0.5,
+            max_penalty: 0.3,
+        }
+    }
+}
+
+impl WeatherRules {
+    /// Share of a passing game's score lost to the weather, 0 for a dome
+    pub fn penalty(&self, env: &GameEnv) -> f32 {
+        if !self.enabled {
+            return 0.0;
+        }
+        let mut penalty: f32 = (env.wind_speed - self.wind_threshold).max(0.0) * self.wind_penalty;
+        if env.precip_per >= self.precip_threshold {
+            penalty += self.precip_penalty;
+        }
+        if env.temperature < self.cold_threshold {
+            penalty += self.cold_penalty;
+        }
+        penalty.min(self.max_penalty)
+    }
+
+    /// What a player's score is multiplied by, 1.0 when there's no game data
+    pub fn multiplier(&self, env: Option<&GameEnv>, pos: &Pos) -> f32 {
+        let penalty: f32 = env.map_or(0.0, |env| self.penalty(env));
+        match pos {
+            Pos::Qb | Pos::Wr | Pos::Te => 1.0 - penalty,
+            Pos::K => (1.0 - penalty * self.kicker_scale).max(0.0),
+            Pos::D => 1.0 + penalty * self.dst_scale,
+            Pos::Rb => 1.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::Team;
+
+    fn game(wind_speed: f32, precip_per: f32, temperature: f32) -> GameEnv {
+        GameEnv {
+            team: Team::Buf,
+            opp: Team::Mia,
+            spread: -3.0,
+            total: 45.0,
+            team_total: 24.0,
+            opp_total: 21.0,
+            temperature,
+            wind_speed,
+            precip_per,
+        }
+    }
+
+    #[test]
+    fn test_multiplier() {
+        let rules: WeatherRules = WeatherRules::default();
+        let windy: GameEnv = game(25.0, 0.0, 60.0);
+        assert!((rules.multiplier(Some(&windy), &Pos::Qb) - 0.8).abs() < 0.001);
+        assert!((rules.multiplier(Some(&windy), &Pos::K) - 0.7).abs() < 0.001);
+        assert!((rules.multiplier(Some(&windy), &Pos::D) - 1.1).abs() < 0.001);
+        assert_eq!(rules.multiplier(Some(&windy), &Pos::Rb), 1.0);
+        assert_eq!(rules.multiplier(None, &Pos::Qb), 1.0);
+
+        let storm: GameEnv = game(40.0, 90.0, 10.0);
+        assert_eq!(rules.penalty(&storm), rules.max_penalty);
+        let off: WeatherRules = WeatherRules {
+            enabled: false,
+            ..rules
+        };
+        assert_eq!(off.multiplier(Some(&storm), &Pos::Wr), 1.0);
+    }
+}